@@ -54,6 +54,47 @@ fn criterion_benchmark(c: &mut Criterion) {
                 .expect("could not call function");
         })
     });
+
+    c.bench_function("call_function_typed_with_args", |b| {
+        b.iter(|| {
+            let _: usize = runtime
+                .call_function_typed(Some(&modref), "test", &("test", 1, false))
+                .expect("could not call function");
+        })
+    });
+
+    // Structured arguments, where the JSON path must encode every field
+    #[derive(serde::Serialize)]
+    struct Record {
+        id: u64,
+        name: String,
+        tags: Vec<String>,
+    }
+    let record = || Record {
+        id: 1,
+        name: "test".to_string(),
+        tags: vec!["a".to_string(), "b".to_string()],
+    };
+
+    c.bench_function("call_function_with_struct", |b| {
+        b.iter(|| {
+            let _: usize = runtime
+                .call_function(
+                    Some(&modref),
+                    "test",
+                    &[Runtime::arg(record()).expect("could not encode arg")],
+                )
+                .expect("could not call function");
+        })
+    });
+
+    c.bench_function("call_function_typed_with_struct", |b| {
+        b.iter(|| {
+            let _: usize = runtime
+                .call_function_typed(Some(&modref), "test", &(record(),))
+                .expect("could not call function");
+        })
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
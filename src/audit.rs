@@ -0,0 +1,125 @@
+//! Records the interactions between scripts and the host
+//!
+//! See [crate::RuntimeOptions::audit_sink]
+use deno_core::serde_json;
+use std::{cell::RefCell, path::PathBuf, rc::Rc, time::SystemTime};
+
+/// Receives an event for every boundary crossing a script makes
+///
+/// Implemented for any `Fn(AuditEvent)`, so a closure can be used directly:
+/// ```rust
+/// use rustyscript::{ AuditEvent, Runtime, RuntimeOptions, Error, Undefined };
+///
+/// # fn main() -> Result<(), Error> {
+/// let mut runtime = Runtime::new(RuntimeOptions {
+///     audit_sink: Some(Box::new(|event: AuditEvent| {
+///         println!("{:?} {:?}: {:?}", event.timestamp, event.module, event.kind);
+///     })),
+///     ..Default::default()
+/// })?;
+///
+/// runtime.register_function("lookup", |_| Ok(1.into()))?;
+/// runtime.eval::<Undefined>("rustyscript.functions.lookup('key')")?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait AuditSink {
+    /// Called as each event happens
+    fn record(&self, event: AuditEvent);
+}
+
+impl<F> AuditSink for F
+where
+    F: Fn(AuditEvent),
+{
+    fn record(&self, event: AuditEvent) {
+        self(event);
+    }
+}
+
+/// A single interaction between a script and the host
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AuditEvent {
+    /// When the event happened
+    pub timestamp: SystemTime,
+
+    /// Specifier of the module or named script responsible, if known
+    /// For module loads, this is the importing module, or `None` if loaded from rust
+    /// For permission checks, this is the module that called the API, such as `fetch`, if the check
+    /// was made during that call; checks made later, such as for redirects, are `None`
+    pub module: Option<String>,
+
+    /// What happened
+    pub kind: AuditEventKind,
+}
+
+/// The types of interaction recorded by an [AuditSink]
+#[derive(Debug, Clone, serde::Serialize)]
+pub enum AuditEventKind {
+    /// A function registered with the runtime was called
+    /// `allowed` is false if the call was denied by [crate::FunctionCapabilities]
+    FunctionCall {
+        function: String,
+        args: Vec<serde_json::Value>,
+        allowed: bool,
+    },
+
+    /// A module was loaded
+    ModuleLoad { specifier: String },
+
+    /// Network access was checked, by fetch or the net extension
+    NetPermission { target: String, api_name: String },
+
+    /// Read access to a file was checked
+    ReadPermission { path: PathBuf, api_name: String },
+
+    /// Write access to a file was checked
+    WritePermission { path: PathBuf, api_name: String },
+
+    /// `localStorage` or `sessionStorage` was used
+    StorageAccess {
+        storage: String,
+        operation: String,
+        key: Option<String>,
+    },
+}
+
+/// Shared handle to the runtime's audit sink
+/// Stored in the OpState, the module loader, and the web permissions
+#[derive(Clone)]
+pub(crate) struct Auditor {
+    sink: Rc<dyn AuditSink>,
+
+    /// Module that called the host API currently running, set by `op_audit_caller`
+    /// Used for events recorded without access to the JS stack, such as permission checks
+    caller: Rc<RefCell<Option<String>>>,
+}
+
+impl Auditor {
+    pub fn new(sink: Box<dyn AuditSink>) -> Self {
+        Self {
+            sink: Rc::from(sink),
+            caller: Rc::default(),
+        }
+    }
+
+    /// Timestamp an event, and pass it to the sink
+    pub fn record(&self, module: Option<String>, kind: AuditEventKind) {
+        self.sink.record(AuditEvent {
+            timestamp: SystemTime::now(),
+            module,
+            kind,
+        });
+    }
+
+    /// Record an event, attributed to the module that called the host API currently running, if any
+    pub fn record_from_caller(&self, kind: AuditEventKind) {
+        let module = self.caller.borrow().clone();
+        self.record(module, kind);
+    }
+
+    /// Set or clear the module that called the host API currently running
+    pub fn set_caller(&self, module: Option<String>) {
+        *self.caller.borrow_mut() = module;
+    }
+}
@@ -0,0 +1,53 @@
+use crate::js_value::Value;
+use std::time::Duration;
+
+/// Determines how much of the event loop a call waits on before returning
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EventLoopPolicy {
+    /// Run the event loop only until the returned value is resolved, if it is a promise
+    /// Other pending work, such as timers the function started, is left for later
+    ///
+    /// This is the behaviour of [crate::Runtime::call_function]
+    #[default]
+    ResolvePromise,
+
+    /// Resolve the returned value, then run the event loop to completion
+    Drain,
+}
+
+/// Per-call settings for [crate::Runtime::call_function_with] and [crate::ModuleWrapper::call_with]
+///
+/// ```rust
+/// use rustyscript::{ json_args, CallOptions, EventLoopPolicy, Runtime, Module, Error };
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Error> {
+/// let mut runtime = Runtime::new(Default::default())?;
+/// let module = Module::new("test.js", "export function f() { return this.value; }");
+/// let module = runtime.load_module(&module)?;
+///
+/// let this = runtime.eval("({ value: 2 })")?;
+/// let options = CallOptions {
+///     timeout: Some(Duration::from_millis(500)),
+///     this: Some(this),
+///     event_loop: EventLoopPolicy::Drain,
+/// };
+///
+/// let value: usize = runtime.call_function_with(Some(&module), "f", json_args!(), &options)?;
+/// assert_eq!(value, 2);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CallOptions {
+    /// Deadline for this call, replacing the runtime's timeout
+    /// Uses [crate::RuntimeOptions::timeout] if `None`
+    pub timeout: Option<Duration>,
+
+    /// Value to use as `this` within the function
+    /// If `None`, the module's namespace is used, or `undefined` without a module
+    pub this: Option<Value>,
+
+    /// Whether to drain the event loop, or only resolve the returned promise
+    pub event_loop: EventLoopPolicy,
+}
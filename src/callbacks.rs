@@ -0,0 +1,258 @@
+//! Lets registered rust functions call the JS functions they are given
+//!
+//! See [crate::Runtime::register_typed_function] and [crate::Runtime::register_typed_async_function]
+use crate::{js_value::Function, traits::IntoArgs, watchdog::WatchdogHandle, Error};
+use deno_core::{
+    futures::channel::oneshot,
+    serde_json, serde_v8,
+    v8::{self, HandleScope},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    future::Future,
+    marker::PhantomData,
+    rc::Rc,
+    task::{Context, Poll, Waker},
+};
+
+/// Distinguishes typed functions that take a [FunctionContext] from those that do not
+/// See [crate::RsTypedFunction]
+pub struct WithContext<Args>(PhantomData<Args>);
+
+/// Passed to typed functions that take it as their first argument,
+/// so that they can call JS functions they were given before returning
+///
+/// ```rust
+/// use rustyscript::{ js_value::Function, FunctionContext, Runtime, Error };
+///
+/// # fn main() -> Result<(), Error> {
+/// let mut runtime = Runtime::new(Default::default())?;
+/// runtime.register_typed_function(
+///     "for_each_row",
+///     |context: &mut FunctionContext, rows: u32, callback: Function| {
+///         for row in 0..rows {
+///             context.call::<(), _>(&callback, &(row,))?;
+///         }
+///         Ok(())
+///     },
+/// )?;
+///
+/// let total: u32 = runtime.eval("
+///     let total = 0;
+///     rustyscript.functions.for_each_row(3, row => { total += row; });
+///     total
+/// ")?;
+/// assert_eq!(total, 3);
+/// # Ok(())
+/// # }
+/// ```
+pub struct FunctionContext<'s> {
+    scope: v8::HandleScope<'s>,
+}
+
+impl<'s> FunctionContext<'s> {
+    pub(crate) fn new(scope: &'s mut HandleScope) -> Self {
+        Self {
+            scope: v8::HandleScope::new(scope),
+        }
+    }
+
+    /// Call a JS function immediately, and deserialize its return value
+    /// Promises are not resolved - use [crate::js_value::Promise] as the return type to keep them
+    ///
+    /// # Arguments
+    /// * `function` - The function to call
+    /// * `args` - The arguments to pass to the function - a tuple of `Serialize` values, or any [IntoArgs]
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized return value,
+    /// or an error if the function throws, or the value cannot be deserialized
+    pub fn call<T, A>(&mut self, function: &Function, args: &A) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+        A: IntoArgs + ?Sized,
+    {
+        let mut scope = v8::TryCatch::new(&mut self.scope);
+        let function = function.as_global(&mut scope);
+        let function = v8::Local::new(&mut scope, function);
+        let args = args.to_v8_args(&mut scope)?;
+
+        let undefined = v8::undefined(&mut scope).into();
+        match function.call(&mut scope, undefined, &args) {
+            Some(value) => Ok(serde_v8::from_v8(&mut scope, value)?),
+            None => match scope.exception() {
+                Some(exception) => {
+                    Err(deno_core::error::JsError::from_v8_exception(&mut scope, exception).into())
+                }
+
+                // Report the same error the terminated call will return
+                None => Err(scope
+                    .get_slot::<WatchdogHandle>()
+                    .and_then(WatchdogHandle::termination_error)
+                    .unwrap_or(Error::Interrupted)),
+            },
+        }
+    }
+}
+
+/// Passed to typed async functions as their first argument,
+/// so that they can call JS functions they were given, at any point before their future resolves
+///
+/// Calls are queued, and run by the event loop
+///
+/// ```rust
+/// use rustyscript::{ js_value::Function, AsyncFunctionContext, Runtime, Error };
+///
+/// # fn main() -> Result<(), Error> {
+/// let mut runtime = Runtime::new(Default::default())?;
+/// runtime.register_typed_async_function(
+///     "for_each_row",
+///     |context: AsyncFunctionContext, rows: u32, callback: Function| async move {
+///         let mut total = 0;
+///         for row in 0..rows {
+///             total += context.call::<u32, _>(&callback, &(row,)).await?;
+///         }
+///         Ok(total)
+///     },
+/// )?;
+///
+/// let tokio_runtime = runtime.tokio_runtime();
+/// let total: u32 = tokio_runtime.block_on(
+///     runtime.eval_async("await rustyscript.async_functions.for_each_row(3, row => row * 2)")
+/// )?;
+/// assert_eq!(total, 6);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct AsyncFunctionContext {
+    queue: Rc<RefCell<CallbackQueue>>,
+}
+
+impl AsyncFunctionContext {
+    /// Queue a call to a JS function, and deserialize its return value
+    /// If the function returns a promise, it is resolved first
+    ///
+    /// Unlike [FunctionContext::call], this does not take [IntoArgs]: the call runs later, on the event loop,
+    /// so the arguments are serialized when it is queued, since there is no v8 scope to convert them into
+    ///
+    /// # Arguments
+    /// * `function` - The function to call
+    /// * `args` - The arguments to pass to the function - a tuple of `Serialize` values
+    ///
+    /// # Returns
+    /// A future resolving to the deserialized return value,
+    /// or an error if the function throws, or the value cannot be deserialized
+    pub fn call<T, A>(
+        &self,
+        function: &Function,
+        args: &A,
+    ) -> impl Future<Output = Result<T, Error>>
+    where
+        T: DeserializeOwned,
+        A: Serialize + ?Sized,
+    {
+        let queued = serde_json::to_value(args).map(|args| {
+            let (sender, receiver) = oneshot::channel();
+            self.queue.borrow_mut().push(QueuedCall {
+                call: Some((function.clone().into_v8(), args)),
+                result: sender,
+            });
+            receiver
+        });
+
+        async move {
+            let value = queued?
+                .await
+                .map_err(|_| Error::Runtime("The callback was dropped".to_string()))??;
+            Ok(serde_json::from_value(value)?)
+        }
+    }
+
+    /// Wait for the next queued call, returning its id
+    /// Calls nobody is waiting for anymore are skipped
+    pub(crate) fn poll_next(&self, cx: &mut Context<'_>) -> Poll<u32> {
+        let mut queue = self.queue.borrow_mut();
+        while let Some(id) = queue.queued.pop_front() {
+            if queue.calls.contains_key(&id) {
+                return Poll::Ready(id);
+            }
+        }
+
+        queue.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Returns the function and arguments of a queued call, as `[function, args]`
+    /// The call stays in the queue until it is resolved, or its caller stops waiting
+    pub(crate) fn take<'s>(
+        &self,
+        scope: &mut HandleScope<'s>,
+        id: u32,
+    ) -> Result<v8::Local<'s, v8::Value>, Error> {
+        let (function, args) = self
+            .queue
+            .borrow_mut()
+            .calls
+            .get_mut(&id)
+            .and_then(|call| call.call.take())
+            .ok_or_else(|| Error::Runtime(format!("No queued callback with id {id}")))?;
+
+        let function = v8::Local::new(scope, &function);
+        let args = match args {
+            serde_json::Value::Array(args) => args,
+            serde_json::Value::Null => Vec::new(),
+            arg => vec![arg],
+        };
+        let args = serde_v8::to_v8(scope, args)?;
+        Ok(v8::Array::new_with_elements(scope, &[function, args]).into())
+    }
+
+    /// Complete a queued call
+    pub(crate) fn resolve(&self, id: u32, result: Result<serde_json::Value, Error>) {
+        if let Some(call) = self.queue.borrow_mut().calls.remove(&id) {
+            // The caller may have stopped waiting
+            call.result.send(result).ok();
+        }
+    }
+
+    /// Drop every queued call, failing the futures waiting on them
+    pub(crate) fn clear(&self) {
+        let mut queue = self.queue.borrow_mut();
+        queue.calls.clear();
+        queue.queued.clear();
+    }
+}
+
+/// A call to a JS function, waiting to be run by the event loop
+struct QueuedCall {
+    /// The function and its arguments, until the event loop takes them to run the call
+    call: Option<(v8::Global<v8::Value>, serde_json::Value)>,
+    result: oneshot::Sender<Result<serde_json::Value, Error>>,
+}
+
+#[derive(Default)]
+struct CallbackQueue {
+    next_id: u32,
+    queued: VecDeque<u32>,
+    calls: HashMap<u32, QueuedCall>,
+    waker: Option<Waker>,
+}
+
+impl CallbackQueue {
+    fn push(&mut self, call: QueuedCall) {
+        // Forget calls whose caller stopped waiting, such as those whose JS promise never settled
+        self.calls.retain(|_, call| !call.result.is_canceled());
+
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.calls.insert(id, call);
+        self.queued.push_back(id);
+
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Per-module access to registered rust functions
+//!
+//! See [crate::RuntimeOptions::function_capabilities]
+use crate::{traits::ToModuleSpecifier, Error};
+use deno_core::v8;
+use std::collections::{HashMap, HashSet};
+
+/// Decides which modules may call which functions registered with the runtime
+///
+/// Calls are attributed to the innermost module on the JS stack, so a module calling a function
+/// exported by another module acts with the other module's grants.
+/// Denied calls throw a `rustyscript.CapabilityError` in JS.
+///
+/// ```rust
+/// use rustyscript::{ FunctionCapabilities, Module, Runtime, RuntimeOptions, Error };
+///
+/// # fn main() -> Result<(), Error> {
+/// let mut capabilities = FunctionCapabilities::default();
+/// capabilities.grant("plugin_a.js", &["db_read"])?;
+///
+/// let mut runtime = Runtime::new(RuntimeOptions {
+///     function_capabilities: Some(capabilities),
+///     ..Default::default()
+/// })?;
+/// runtime.register_function("db_read", |_| Ok("data".into()))?;
+///
+/// let plugin_a = Module::new("plugin_a.js", "export const data = rustyscript.functions.db_read();");
+/// let plugin_b = Module::new("plugin_b.js", "export const data = rustyscript.functions.db_read();");
+/// assert!(runtime.load_module(&plugin_a).is_ok());
+/// assert!(runtime.load_module(&plugin_b).is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FunctionCapabilities {
+    /// Functions each module may call, by module specifier
+    grants: HashMap<String, HashSet<String>>,
+
+    /// Functions any module may call
+    shared: HashSet<String>,
+}
+
+impl FunctionCapabilities {
+    /// Allow a module to call the given functions
+    ///
+    /// # Arguments
+    /// * `module` - The module's filename, as given to [crate::Module::new], or its specifier
+    /// * `functions` - Names of registered functions
+    ///
+    /// # Errors
+    /// Returns an error if the module's specifier cannot be resolved
+    pub fn grant(&mut self, module: &str, functions: &[&str]) -> Result<&mut Self, Error> {
+        let specifier = module.to_module_specifier()?;
+        self.grants
+            .entry(specifier.to_string())
+            .or_default()
+            .extend(functions.iter().map(ToString::to_string));
+        Ok(self)
+    }
+
+    /// Allow every module, and scripts run with [crate::Runtime::eval], to call the given functions
+    pub fn grant_all_modules(&mut self, functions: &[&str]) -> &mut Self {
+        self.shared
+            .extend(functions.iter().map(ToString::to_string));
+        self
+    }
+
+    /// Returns true if the module with the given specifier may call the function
+    pub fn is_allowed(&self, module: Option<&str>, function: &str) -> bool {
+        if self.shared.contains(function) {
+            return true;
+        }
+
+        let Some(module) = module else {
+            return false;
+        };
+        self.grants
+            .get(module)
+            .is_some_and(|functions| functions.contains(function))
+    }
+}
+
+/// A call to a registered function that was denied by [FunctionCapabilities]
+#[derive(Debug, Clone)]
+pub struct CapabilityDenial {
+    /// Specifier of the module that made the call, if it came from a module or named script
+    pub module: Option<String>,
+
+    /// Name of the function it tried to call
+    pub function: String,
+}
+
+/// Stored in the OpState of runtimes with function capabilities
+pub(crate) struct CapabilityState {
+    pub capabilities: FunctionCapabilities,
+    pub on_denied: Option<Box<dyn Fn(&CapabilityDenial)>>,
+}
+
+impl CapabilityState {
+    /// Check that the code currently running may call the given function
+    pub fn check(&self, scope: &mut v8::HandleScope, function: &str) -> Result<(), Error> {
+        let module = calling_module(scope);
+        if self.capabilities.is_allowed(module.as_deref(), function) {
+            return Ok(());
+        }
+
+        let denial = CapabilityDenial {
+            module,
+            function: function.to_string(),
+        };
+        if let Some(on_denied) = &self.on_denied {
+            on_denied(&denial);
+        }
+
+        Err(Error::CapabilityDenied(format!(
+            "{} may not call {function}",
+            denial.module.as_deref().unwrap_or("<anonymous>")
+        )))
+    }
+}
+
+/// Returns the specifier of the innermost script or module on the JS stack,
+/// skipping the extension code that forwards calls to registered functions
+///
+/// Modules reloaded after a reset are reported under their own specifier
+pub(crate) fn calling_module(scope: &mut v8::HandleScope) -> Option<String> {
+    let stack = v8::StackTrace::current_stack_trace(scope, 16)?;
+    for index in 0..stack.get_frame_count() {
+        let Some(name) = stack
+            .get_frame(scope, index)
+            .and_then(|frame| frame.get_script_name(scope))
+        else {
+            continue;
+        };
+
+        let name = name.to_rust_string_lossy(scope);
+        if !name.is_empty() && !name.starts_with("ext:") {
+            return Some(crate::module_loader::unversioned(&name));
+        }
+    }
+
+    None
+}
@@ -0,0 +1,139 @@
+//! Virtual time, controlled from rust
+//!
+//! See [crate::RuntimeOptions::clock]
+use deno_core::v8;
+use std::{cell::Cell, collections::BTreeMap, rc::Rc, time::Duration};
+
+/// A clock that only moves when told to
+/// Clones share the same time, so a clone can be kept to control a runtime's clock
+///
+/// When set as [crate::RuntimeOptions::clock], it drives `Date`, `performance.now`
+/// and the timers of the web stub - see [crate::Runtime::advance_time]
+#[derive(Debug, Clone, Default)]
+pub struct VirtualClock(Rc<Cell<Duration>>);
+
+impl VirtualClock {
+    /// Create a new clock, starting at the given time since the UNIX epoch
+    pub fn new(start: Duration) -> Self {
+        Self(Rc::new(Cell::new(start)))
+    }
+
+    /// Returns the current time, since the UNIX epoch
+    pub fn now(&self) -> Duration {
+        self.0.get()
+    }
+
+    /// Move the clock to the given time, since the UNIX epoch
+    pub fn set(&self, now: Duration) {
+        self.0.set(now);
+    }
+
+    /// Move the clock forward
+    pub fn advance(&self, duration: Duration) {
+        self.0.set(self.0.get() + duration);
+    }
+}
+
+/// Stored in the OpState of runtimes using a virtual clock
+pub(crate) struct ClockState {
+    pub clock: VirtualClock,
+
+    /// Time at which the runtime was created, used as the origin for `performance.now`
+    origin: Duration,
+}
+
+impl ClockState {
+    pub fn new(clock: VirtualClock) -> Self {
+        let origin = clock.now();
+        Self { clock, origin }
+    }
+
+    /// Time since the UNIX epoch, in milliseconds
+    pub fn now(&self) -> f64 {
+        self.clock.now().as_secs_f64() * 1000.0
+    }
+
+    /// Time since the runtime was created
+    pub fn elapsed(&self) -> Duration {
+        self.clock.now().saturating_sub(self.origin)
+    }
+}
+
+/// A timer waiting for the virtual clock to reach its due time
+pub(crate) struct VirtualTimer {
+    pub id: u32,
+    pub callback: v8::Global<v8::Function>,
+
+    /// Set for intervals
+    pub repeat: Option<Duration>,
+}
+
+/// Timers scheduled against the virtual clock
+/// Ordered by due time, then by the order in which they were scheduled
+#[derive(Default)]
+pub(crate) struct VirtualTimers {
+    queue: BTreeMap<(Duration, u64), VirtualTimer>,
+    next_id: u32,
+    sequence: u64,
+}
+
+impl VirtualTimers {
+    /// Schedule a timer to fire once the clock reaches `due`
+    /// Returns the id of the timer
+    pub fn queue(
+        &mut self,
+        due: Duration,
+        repeat: Option<Duration>,
+        callback: v8::Global<v8::Function>,
+    ) -> u32 {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.insert(
+            due,
+            VirtualTimer {
+                id,
+                callback,
+                repeat,
+            },
+        );
+        id
+    }
+
+    fn insert(&mut self, due: Duration, timer: VirtualTimer) {
+        self.sequence += 1;
+        self.queue.insert((due, self.sequence), timer);
+    }
+
+    /// Cancel a timer, if it has not yet fired
+    pub fn cancel(&mut self, id: u32) {
+        self.queue.retain(|_, timer| timer.id != id);
+    }
+
+    /// Cancel all timers
+    pub fn clear(&mut self) {
+        self.queue.clear();
+    }
+
+    /// Number of timers waiting to fire
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Remove the earliest timer due at or before `limit`, returning its due time and callback
+    /// Intervals are scheduled again before being returned, so they can cancel themselves
+    pub fn pop_due(&mut self, limit: Duration) -> Option<(Duration, v8::Global<v8::Function>)> {
+        let (&(due, _), _) = self.queue.first_key_value()?;
+        if due > limit {
+            return None;
+        }
+
+        let (_, timer) = self.queue.pop_first()?;
+        let callback = timer.callback.clone();
+        if let Some(repeat) = timer.repeat {
+            // A zero-length interval would never let the clock move forward
+            self.insert(due + repeat.max(Duration::from_millis(1)), timer);
+        }
+
+        Some((due, callback))
+    }
+}
@@ -0,0 +1,65 @@
+//! Controls whether scripts can generate code from strings
+//!
+//! See [crate::RuntimeOptions::disallow_code_generation]
+use deno_core::{v8, JsRuntime};
+use std::rc::Rc;
+
+/// Decides whether a string may be compiled, when code generation is disallowed
+pub(crate) type CodeGenerationFilter = Rc<dyn Fn(&str) -> bool>;
+
+/// Stored in the isolate and the OpState of runtimes that disallow code generation from strings
+#[derive(Clone)]
+pub(crate) struct CodeGenerationPolicy {
+    filter: Option<CodeGenerationFilter>,
+}
+
+impl CodeGenerationPolicy {
+    /// Install the policy on the runtime's isolate, and apply it to the main context
+    /// `eval`, `new Function` and the like will throw an `EvalError` unless `filter` allows the string
+    pub fn install(runtime: &mut JsRuntime, filter: Option<CodeGenerationFilter>) -> Self {
+        let policy = Self { filter };
+        let isolate = runtime.v8_isolate();
+        isolate.set_slot(policy.clone());
+        isolate.set_modify_code_generation_from_strings_callback(modify_code_generation);
+
+        let context = runtime.main_context();
+        let mut scope = runtime.handle_scope();
+        let context = v8::Local::new(&mut scope, context);
+        Self::apply(&context);
+        policy
+    }
+
+    /// Apply the policy to a context
+    /// v8 only asks the callback about strings compiled in contexts that disallow code generation
+    pub fn apply(context: &v8::Context) {
+        context.set_allow_generation_from_strings(false);
+    }
+
+    /// Returns true if the given string may be compiled
+    pub fn allows(&self, source: &str) -> bool {
+        self.filter.as_ref().is_some_and(|filter| filter(source))
+    }
+}
+
+/// Called by v8 whenever a script tries to compile a string
+extern "C" fn modify_code_generation<'s>(
+    context: v8::Local<'s, v8::Context>,
+    source: v8::Local<'s, v8::Value>,
+    _is_code_like: bool,
+) -> v8::ModifyCodeGenerationFromStringsResult<'s> {
+    // SAFETY: v8 calls this from within the context compiling the string
+    let scope = &mut unsafe { v8::CallbackScope::new(context) };
+    let policy = scope.get_slot::<CodeGenerationPolicy>().cloned();
+
+    // Only plain strings can be checked by the filter
+    let codegen_allowed = match policy {
+        Some(policy) if source.is_string() => policy.allows(&source.to_rust_string_lossy(scope)),
+        Some(_) => false,
+        None => true,
+    };
+
+    v8::ModifyCodeGenerationFromStringsResult {
+        codegen_allowed,
+        modified_source: None,
+    }
+}
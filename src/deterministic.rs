@@ -0,0 +1,65 @@
+//! Support for running scripts reproducibly
+//!
+//! See [crate::RuntimeOptions::deterministic]
+
+/// Options for running scripts reproducibly
+///
+/// With these set, `Math.random`, `crypto.getRandomValues` and `crypto.randomUUID` are seeded from `seed`,
+/// and `Date`, `Date.now` and `performance.now` are driven by [crate::RuntimeOptions::clock], which only moves when told to.
+/// If no clock is provided, one starting at the UNIX epoch is used.
+///
+/// Timers are scheduled against that clock as well, when using the web stub.
+/// A timer never fires by itself - `await new Promise(r => setTimeout(r, 10))` waits until
+/// [crate::Runtime::advance_time] moves the clock past it, so the runtime's timeout applies in the meantime.
+///
+/// Two runs of the same module with the same options will produce identical results
+///
+/// ```rust
+/// use rustyscript::{ DeterministicOptions, Runtime, RuntimeOptions, VirtualClock, Error };
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Error> {
+/// let clock = VirtualClock::new(Duration::from_secs(1_700_000_000));
+/// let mut runtime = Runtime::new(RuntimeOptions {
+///     deterministic: Some(DeterministicOptions { seed: 42 }),
+///     clock: Some(clock.clone()),
+///     ..Default::default()
+/// })?;
+///
+/// let start: u64 = runtime.eval("Date.now()")?;
+/// clock.advance(Duration::from_millis(500));
+/// let end: u64 = runtime.eval("Date.now()")?;
+/// assert_eq!(500, end - start);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DeterministicOptions {
+    /// Seed for all sources of randomness
+    /// Used for `crypto` unless [crate::ExtensionOptions] sets its own seed
+    pub seed: u64,
+}
+
+/// Stored in the OpState of deterministic runtimes
+pub(crate) struct DeterministicState {
+    /// State of the random number generator
+    rng: u64,
+}
+
+impl DeterministicState {
+    pub fn new(options: &DeterministicOptions) -> Self {
+        Self { rng: options.seed }
+    }
+
+    /// Returns a number in [0, 1), using splitmix64
+    pub fn random(&mut self) -> f64 {
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        // Keep the 53 bits that fit in an f64 mantissa
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
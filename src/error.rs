@@ -65,11 +65,43 @@ pub enum Error {
 
     /// Runtime error we successfully downcast
     #[error("{0}")]
-    JsError(#[from] deno_core::error::JsError),
+    JsError(deno_core::error::JsError),
 
     /// Triggers when a module times out before finishing
     #[error("Module timed out: {0}")]
     Timeout(String),
+
+    /// Triggers when a script exceeds the runtime's heap limit
+    /// The runtime is poisoned afterwards, and must be recreated
+    #[error("Heap limit exceeded. The runtime must be recreated")]
+    HeapExhausted,
+
+    /// Triggers when a call is cancelled using an [crate::InterruptHandle]
+    #[error("Execution was interrupted")]
+    Interrupted,
+
+    /// Triggers when V8 flags are invalid, or cannot be applied
+    /// See [crate::V8Flags::apply]
+    #[error("Invalid V8 flags: {0}")]
+    InvalidV8Flags(String),
+
+    /// Triggers when a script exceeds the maximum call stack size, usually through deep recursion
+    /// Contains the frames at the point of the overflow, innermost first
+    ///
+    /// The size of the stack can be limited per runtime with [crate::RuntimeOptions::max_stack_size],
+    /// or for the whole process with [crate::V8Flags::stack_size]
+    #[error("Maximum call stack size exceeded")]
+    StackOverflow(Vec<deno_core::error::JsStackFrame>),
+
+    /// Triggers when a module calls a registered function it was not granted
+    /// See [crate::FunctionCapabilities]
+    #[error("{0}")]
+    CapabilityDenied(String),
+
+    /// Triggers when a function registered with [crate::Runtime::register_typed_function]
+    /// is called with the wrong number or type of arguments
+    #[error("{0}")]
+    TypeMismatch(String),
 }
 
 impl Error {
@@ -166,6 +198,20 @@ impl Error {
     }
 }
 
+/// Returns the JS error class for errors returned by ops
+/// Denied calls use `rustyscript.CapabilityError`, so scripts can tell them apart
+///
+/// Stack overflows use `RangeError`, like V8's own, so they are still reported as [Error::StackOverflow]
+/// once they reach rust
+pub(crate) fn get_error_class(error: &deno_core::anyhow::Error) -> &'static str {
+    match error.downcast_ref::<Error>() {
+        Some(Error::CapabilityDenied(_)) => "CapabilityError",
+        Some(Error::TypeMismatch(_)) => "TypeError",
+        Some(Error::StackOverflow(_)) => "RangeError",
+        _ => deno_core::error::get_custom_error_class(error).unwrap_or("Error"),
+    }
+}
+
 #[macro_use]
 mod error_macro {
     /// Maps one error type to another
@@ -197,12 +243,33 @@ map_error!(deno_core::serde_v8::Error, |e| Error::JsonDecode(
     e.to_string()
 ));
 
+map_error!(deno_core::error::JsError, |mut e| {
+    // Modules reloaded after a reset are reported under their own specifier
+    let unversioned = crate::module_loader::unversioned;
+    e.exception_message = unversioned(&e.exception_message);
+    e.stack = e.stack.as_deref().map(unversioned);
+    for frame in &mut e.frames {
+        frame.file_name = frame.file_name.as_deref().map(unversioned);
+    }
+
+    // V8 reports stack overflows as a RangeError
+    let is_stack_overflow = e.name.as_deref() == Some("RangeError")
+        && e.message
+            .as_deref()
+            .is_some_and(|m| m.contains("Maximum call stack size exceeded"));
+    if is_stack_overflow {
+        Error::StackOverflow(e.frames)
+    } else {
+        Error::JsError(e)
+    }
+});
+
 map_error!(deno_core::anyhow::Error, |e| {
     // trydowncast to deno_core::error::JsError
     let s = e.to_string();
     match e.downcast::<deno_core::error::JsError>() {
-        Ok(js_error) => Error::JsError(js_error),
-        Err(_) => Error::Runtime(s),
+        Ok(js_error) => js_error.into(),
+        Err(_) => Error::Runtime(crate::module_loader::unversioned(&s)),
     }
 });
 
@@ -0,0 +1,38 @@
+// Captures the state of the global object once the runtime is initialized
+// Returns the function used by `Runtime::reset` to restore it,
+// along with one to capture the baseline again after a lockdown
+(() => {
+    'use strict';
+    const core = Deno.core;
+    let baseline;
+    const capture = () => {
+        baseline = Object.getOwnPropertyDescriptors(globalThis);
+    };
+    capture();
+
+    const reset = (timers) => {
+        // Stop any timers started since the baseline
+        for (const id of timers) {
+            core.cancelTimer(id);
+        }
+
+        // Remove globals created since the baseline
+        for (const key of Reflect.ownKeys(globalThis)) {
+            if (!(key in baseline) && !Reflect.deleteProperty(globalThis, key)) {
+                // Non-configurable, such as a top-level `var` - clear it instead
+                Reflect.set(globalThis, key, undefined);
+            }
+        }
+
+        // Restore globals that were replaced or removed
+        for (const key of Reflect.ownKeys(baseline)) {
+            try {
+                Object.defineProperty(globalThis, key, baseline[key]);
+            } catch {
+                // Frozen or non-configurable properties cannot have changed
+            }
+        }
+    };
+
+    return [reset, capture];
+})()
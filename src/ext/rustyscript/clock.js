@@ -0,0 +1,34 @@
+// Drives Date and performance.now from the virtual clock
+// Only run when `RuntimeOptions::clock` is set, or the runtime is deterministic
+(() => {
+    'use strict';
+    const ops = Deno.core.ops;
+    const now = () => ops.op_virtual_now();
+
+    // Date uses the virtual clock when called without a time
+    const NativeDate = globalThis.Date;
+    function Date(...args) {
+        if (new.target === undefined) {
+            return new NativeDate(now()).toString();
+        }
+
+        return Reflect.construct(NativeDate, args.length === 0 ? [now()] : args, new.target);
+    }
+    Object.setPrototypeOf(Date, NativeDate);
+    Date.prototype = NativeDate.prototype;
+    Date.now = now;
+    Object.defineProperty(NativeDate.prototype, 'constructor', {
+        value: Date, writable: true, enumerable: false, configurable: true,
+    });
+    Object.defineProperty(globalThis, 'Date', {
+        value: Date, writable: true, enumerable: false, configurable: true,
+    });
+
+    // deno_web's performance API reads the real clock, so it is replaced too
+    if (globalThis.performance) {
+        const origin = now();
+        Object.defineProperty(globalThis.performance, 'now', {
+            value: () => now() - origin, writable: true, enumerable: false, configurable: true,
+        });
+    }
+})();
@@ -0,0 +1,7 @@
+// Replaces Math.random with a seeded generator
+// Only run when `RuntimeOptions::deterministic` is set
+(() => {
+    'use strict';
+    const ops = Deno.core.ops;
+    Math.random = () => ops.op_deterministic_random();
+})();
@@ -0,0 +1,110 @@
+// Removes hidden globals, then freezes the remaining globals and the global object
+// Only run when `RuntimeOptions::lockdown` is set
+((options) => {
+    'use strict';
+
+    // Remove a global, or a property of one, given a path such as `Deno.core`
+    const hide = (path) => {
+        const keys = path.split('.');
+        const last = keys.pop();
+        let target = globalThis;
+        for (const key of keys) {
+            target = target?.[key];
+        }
+
+        if (target !== null && typeof target === 'object' || typeof target === 'function') {
+            if (!Reflect.deleteProperty(target, last)) {
+                throw new TypeError(`Could not hide ${path}`);
+            }
+        }
+    };
+
+    if (options.allowed !== null) {
+        const keep = new Set([...options.allowed, ...options.intrinsics]);
+        for (const key of Reflect.ownKeys(globalThis)) {
+            if (typeof key === 'string' && !keep.has(key)) {
+                hide(key);
+            }
+        }
+    }
+    for (const path of options.hidden) {
+        hide(path);
+    }
+
+    // Frozen prototypes would stop instances from assigning commonly overridden properties,
+    // such as `this.name` in error subclasses, so those become accessors that define an own property instead
+    const overridable = ['constructor', 'name', 'message', 'toString', 'valueOf'];
+    const makeOverridable = (proto) => {
+        for (const key of overridable) {
+            const descriptor = Reflect.getOwnPropertyDescriptor(proto, key);
+            if (!descriptor || !('value' in descriptor) || !descriptor.configurable) {
+                continue;
+            }
+
+            const value = descriptor.value;
+            Object.defineProperty(proto, key, {
+                get() { return value; },
+                set(newValue) {
+                    if (this === proto) {
+                        throw new TypeError(`Cannot assign to read only property '${key}'`);
+                    }
+                    Object.defineProperty(this, key, {
+                        value: newValue, writable: true, enumerable: true, configurable: true,
+                    });
+                },
+                enumerable: descriptor.enumerable,
+                configurable: false,
+            });
+        }
+    };
+
+    // Freeze everything reachable from a value
+    const seen = new Set();
+    const freeze = (value) => {
+        if ((typeof value !== 'object' || value === null) && typeof value !== 'function') {
+            return;
+        }
+        if (seen.has(value)) {
+            return;
+        }
+        seen.add(value);
+
+        if (Object.hasOwn(value, 'constructor')) {
+            makeOverridable(value);
+        }
+
+        // Typed arrays with elements cannot be frozen
+        if (!ArrayBuffer.isView(value)) {
+            Object.freeze(value);
+        }
+
+        freeze(Object.getPrototypeOf(value));
+        for (const key of Reflect.ownKeys(value)) {
+            const descriptor = Reflect.getOwnPropertyDescriptor(value, key);
+            freeze(descriptor.value);
+            freeze(descriptor.get);
+            freeze(descriptor.set);
+        }
+    };
+
+    // Globals the embedder opted out of are skipped, along with anything only reachable through them
+    // The global object is frozen last, so that walking the other globals does not reach them through it
+    seen.add(globalThis);
+    for (const key of options.unfrozen) {
+        const descriptor = Reflect.getOwnPropertyDescriptor(globalThis, key);
+        if (descriptor && 'value' in descriptor) {
+            seen.add(descriptor.value);
+        }
+    }
+    for (const key of Reflect.ownKeys(globalThis)) {
+        if (options.unfrozen.includes(key)) {
+            continue;
+        }
+
+        const descriptor = Reflect.getOwnPropertyDescriptor(globalThis, key);
+        freeze(descriptor.value);
+        freeze(descriptor.get);
+        freeze(descriptor.set);
+    }
+    Object.freeze(globalThis);
+})
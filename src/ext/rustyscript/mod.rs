@@ -1,10 +1,40 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Instant};
 
-use crate::{error::Error, RsAsyncFunction, RsFunction};
-use deno_core::{extension, op2, serde_json, v8, Extension, OpState};
+use crate::{
+    audit::{AuditEventKind, Auditor},
+    callbacks::AsyncFunctionContext,
+    capabilities::{calling_module, CapabilityState},
+    clock::ClockState,
+    deterministic::DeterministicState,
+    error::Error,
+    host_class::{ClassInfo, HostClassGeneration, HostClasses},
+    inner_runtime::{
+        TypedAsyncFunctionCallback, TypedFunctionCallback, TypedFunctionFuture,
+        UnhandledRejectionPolicy,
+    },
+    lockdown::LockedDown,
+    metrics::CallStats,
+    module_loader::unversioned,
+    RsAsyncFunction, RsFunction,
+};
+use deno_core::{
+    error::JsStackFrame, extension, op2, serde_json, serde_v8, v8, Extension, OpState,
+};
 
 type FnCache = HashMap<String, Box<dyn RsFunction>>;
 type AsyncFnCache = HashMap<String, Box<dyn RsAsyncFunction>>;
+type TypedFnCache = HashMap<String, TypedFunctionCallback>;
+type TypedAsyncFnCache = HashMap<String, TypedAsyncFunctionCallback>;
+
+/// Functions listed by each `host:` module, as `(name, is_async)` pairs, stored in the OpState
+#[derive(Default)]
+pub(crate) struct HostModules(pub HashMap<String, Vec<(String, bool)>>);
+
+/// Stored in the OpState to handle promise rejections that nothing handles
+pub struct UnhandledRejectionHandler {
+    pub callback: Option<Box<dyn Fn(Error)>>,
+    pub policy: UnhandledRejectionPolicy,
+}
 
 #[op2]
 /// Registers a JS function with the runtime as being the entrypoint for the module
@@ -21,42 +51,468 @@ fn op_register_entrypoint(
 }
 
 #[op2]
-#[serde]
-fn call_registered_function(
-    #[string] name: String,
-    #[serde] args: Vec<serde_json::Value>,
+/// Called for promise rejections that nothing handles
+/// Returns true if the rejection was handled, and should not be raised by the event loop
+fn op_unhandled_rejection(
+    state: &mut OpState,
+    scope: &mut v8::HandleScope,
+    reason: v8::Local<v8::Value>,
+) -> bool {
+    let Some(handler) = state.try_borrow::<UnhandledRejectionHandler>() else {
+        return false;
+    };
+
+    let error = deno_core::error::JsError::from_v8_exception(scope, reason);
+    if handler.policy == UnhandledRejectionPolicy::Log {
+        log::warn!("Unhandled promise rejection: {error}");
+    }
+
+    if let Some(callback) = &handler.callback {
+        callback(error.into());
+    }
+
+    handler.policy != UnhandledRejectionPolicy::FailNextCall
+}
+
+#[op2(fast)]
+/// Replaces Math.random in deterministic runtimes
+fn op_deterministic_random(state: &mut OpState) -> f64 {
+    match state.try_borrow_mut::<DeterministicState>() {
+        Some(deterministic) => deterministic.random(),
+        None => 0.0,
+    }
+}
+
+#[op2(fast)]
+/// Replaces Date.now in runtimes with a virtual clock
+/// Returns the time on the virtual clock, in milliseconds since the UNIX epoch
+fn op_virtual_now(state: &mut OpState) -> f64 {
+    match state.try_borrow::<ClockState>() {
+        Some(clock) => clock.now(),
+        None => 0.0,
+    }
+}
+
+#[op2]
+/// Records an access to `localStorage` or `sessionStorage` in runtimes with an audit sink
+fn op_audit_storage(
     state: &mut OpState,
-) -> Result<serde_json::Value, Error> {
-    if state.has::<FnCache>() {
-        let table = state.borrow_mut::<FnCache>();
-        if let Some(callback) = table.get(&name) {
-            return callback(&args);
+    scope: &mut v8::HandleScope,
+    #[string] storage: String,
+    #[string] operation: String,
+    #[serde] key: Option<String>,
+) {
+    if let Some(audit) = state.try_borrow::<Auditor>() {
+        audit.record(
+            calling_module(scope),
+            AuditEventKind::StorageAccess {
+                storage,
+                operation,
+                key,
+            },
+        );
+    }
+}
+
+#[op2]
+/// Marks the start and end of a call to a host API, such as `fetch`, in runtimes with an audit sink
+/// Permission checks made in between are attributed to the module that called it
+fn op_audit_caller(state: &mut OpState, scope: &mut v8::HandleScope, active: bool) {
+    if let Some(audit) = state.try_borrow::<Auditor>() {
+        audit.set_caller(if active { calling_module(scope) } else { None });
+    }
+}
+
+/// Default for [crate::RuntimeOptions::max_call_depth]
+pub(crate) const DEFAULT_MAX_CALL_DEPTH: usize = 128;
+
+/// Maximum number of JS frames reported by [Error::StackOverflow] when the call depth is exceeded
+const STACK_FRAME_LIMIT: usize = 32;
+
+/// Number of registered function calls in progress, and how many may be, stored in the OpState
+pub(crate) struct CallDepth {
+    depth: usize,
+    max: usize,
+}
+
+impl CallDepth {
+    pub(crate) fn new(max: usize) -> Self {
+        Self { depth: 0, max }
+    }
+}
+
+/// Counts a registered function call for as long as it is alive
+struct CallDepthGuard(Rc<RefCell<OpState>>);
+
+impl CallDepthGuard {
+    /// Enter a registered function call
+    /// Fails with [Error::StackOverflow] if too many calls are already in progress
+    fn enter(state: &Rc<RefCell<OpState>>, scope: &mut v8::HandleScope) -> Result<Self, Error> {
+        let mut op_state = state.borrow_mut();
+        if !op_state.has::<CallDepth>() {
+            op_state.put(CallDepth::new(DEFAULT_MAX_CALL_DEPTH));
+        }
+
+        let depth = op_state.borrow_mut::<CallDepth>();
+        if depth.depth >= depth.max {
+            return Err(Error::StackOverflow(current_frames(scope)));
         }
+        depth.depth += 1;
+        Ok(Self(state.clone()))
+    }
+}
+
+impl Drop for CallDepthGuard {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.0.try_borrow_mut() {
+            if let Some(depth) = state.try_borrow_mut::<CallDepth>() {
+                depth.depth = depth.depth.saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// Captures the JS frames currently on the stack, innermost first
+fn current_frames(scope: &mut v8::HandleScope) -> Vec<JsStackFrame> {
+    let Some(stack) = v8::StackTrace::current_stack_trace(scope, STACK_FRAME_LIMIT) else {
+        return Vec::new();
+    };
+
+    let mut frames = Vec::with_capacity(stack.get_frame_count());
+    for index in 0..stack.get_frame_count() {
+        let Some(frame) = stack.get_frame(scope, index) else {
+            continue;
+        };
+
+        let file_name = frame
+            .get_script_name_or_source_url(scope)
+            .map(|name| unversioned(&name.to_rust_string_lossy(scope)));
+        let mut js_frame = JsStackFrame::from_location(
+            file_name,
+            Some(frame.get_line_number() as i64),
+            Some(frame.get_column() as i64),
+        );
+        js_frame.function_name = frame
+            .get_function_name(scope)
+            .map(|name| name.to_rust_string_lossy(scope))
+            .filter(|name| !name.is_empty());
+        js_frame.is_eval = frame.is_eval();
+        js_frame.is_constructor = frame.is_constructor();
+        frames.push(js_frame);
+    }
+    frames
+}
+
+/// Checks that the code currently running may call a registered function,
+/// and records the call in runtimes with an audit sink
+fn authorize_call(
+    state: &OpState,
+    scope: &mut v8::HandleScope,
+    name: &str,
+    args: &[serde_json::Value],
+) -> Result<(), Error> {
+    let result = match state.try_borrow::<CapabilityState>() {
+        Some(capabilities) => capabilities.check(scope, name),
+        None => Ok(()),
+    };
+
+    if let Some(audit) = state.try_borrow::<Auditor>() {
+        audit.record(
+            calling_module(scope),
+            AuditEventKind::FunctionCall {
+                function: name.to_string(),
+                args: args.to_vec(),
+                allowed: result.is_ok(),
+            },
+        );
     }
 
-    Err(Error::ValueNotCallable(name.to_string()))
+    result
+}
+
+#[op2]
+fn call_registered_function<'s>(
+    #[string] name: String,
+    args: v8::Local<'s, v8::Value>,
+    state: Rc<RefCell<OpState>>,
+    scope: &mut v8::HandleScope<'s>,
+) -> Result<v8::Local<'s, v8::Value>, Error> {
+    let _depth = CallDepthGuard::enter(&state, scope)?;
+    let typed = state
+        .borrow()
+        .try_borrow::<TypedFnCache>()
+        .and_then(|table| table.get(&name))
+        .cloned();
+
+    // Typed functions take their arguments straight from v8, so they are only converted to JSON for auditing
+    let json_args: Vec<serde_json::Value> = if typed.is_none() || state.borrow().has::<Auditor>() {
+        serde_v8::from_v8(scope, args)?
+    } else {
+        Vec::new()
+    };
+    authorize_call(&state.borrow(), scope, &name, &json_args)?;
+
+    let start = Instant::now();
+    let result = if let Some(callback) = typed {
+        // The state is not borrowed during the call, so that JS functions called by the callback can call back into rust
+        let args = v8_array_elements(scope, args)?;
+        callback(scope, &name, &args)
+    } else {
+        let state = state.borrow();
+        match state
+            .try_borrow::<FnCache>()
+            .and_then(|table| table.get(&name))
+        {
+            Some(callback) => {
+                callback(&json_args).and_then(|value| Ok(serde_v8::to_v8(scope, value)?))
+            }
+            None => return Err(Error::ValueNotCallable(name)),
+        }
+    };
+
+    if let Some(stats) = state.borrow().try_borrow::<CallStats>() {
+        stats.record(&name, start.elapsed());
+    }
+    result
 }
 
 #[op2(async)]
 #[serde]
 fn call_registered_function_async(
     #[string] name: String,
-    #[serde] args: Vec<serde_json::Value>,
+    args: v8::Local<v8::Value>,
     state: &mut OpState,
+    scope: &mut v8::HandleScope,
 ) -> impl std::future::Future<Output = Result<serde_json::Value, Error>> {
-    if state.has::<AsyncFnCache>() {
-        let table = state.borrow_mut::<AsyncFnCache>();
-        if let Some(callback) = table.get(&name) {
-            return callback(args);
+    let future = start_registered_function_async(&name, args, state, scope);
+    let stats = state.try_borrow::<CallStats>().cloned();
+    let start = Instant::now();
+    async move {
+        let result = future?.await;
+        if let Some(stats) = stats {
+            stats.record(&name, start.elapsed());
         }
+        result
     }
+}
+
+/// Converts the arguments to a registered async function, and calls it
+fn start_registered_function_async(
+    name: &str,
+    args: v8::Local<v8::Value>,
+    state: &mut OpState,
+    scope: &mut v8::HandleScope,
+) -> Result<TypedFunctionFuture, Error> {
+    let typed = state
+        .try_borrow::<TypedAsyncFnCache>()
+        .and_then(|table| table.get(name))
+        .cloned();
+
+    // Typed functions take their arguments straight from v8, so they are only converted to JSON for auditing
+    let json_args: Vec<serde_json::Value> = if typed.is_none() || state.has::<Auditor>() {
+        serde_v8::from_v8(scope, args)?
+    } else {
+        Vec::new()
+    };
+    authorize_call(state, scope, name, &json_args)?;
+
+    if let Some(callback) = typed {
+        let context = state.borrow::<AsyncFunctionContext>().clone();
+        let args = v8_array_elements(scope, args)?;
+        return callback(scope, context, name, &args);
+    }
+
+    match state
+        .try_borrow::<AsyncFnCache>()
+        .and_then(|table| table.get(name))
+    {
+        Some(callback) => Ok(callback(json_args)),
+        None => Err(Error::ValueNotCallable(name.to_string())),
+    }
+}
+
+/// Returns the elements of an array of arguments
+fn v8_array_elements<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    args: v8::Local<'s, v8::Value>,
+) -> Result<Vec<v8::Local<'s, v8::Value>>, Error> {
+    let args = v8::Local::<v8::Array>::try_from(args)?;
+    Ok((0..args.length())
+        .filter_map(|index| args.get_index(scope, index))
+        .collect())
+}
+
+#[op2]
+#[serde]
+/// Returns the names of the members of a registered class, or `None` if there is no such class
+fn op_host_class_info(state: &mut OpState, #[string] name: String) -> Option<ClassInfo> {
+    state
+        .try_borrow::<HostClasses>()
+        .and_then(|classes| classes.get(&name))
+        .map(|class| class.info())
+}
+
+#[op2]
+/// Constructs an instance of a registered class, returning its resource id
+fn op_host_object_new(
+    state: &mut OpState,
+    scope: &mut v8::HandleScope,
+    #[string] name: String,
+    args: v8::Local<v8::Value>,
+) -> Result<u32, Error> {
+    let class = state
+        .try_borrow::<HostClasses>()
+        .and_then(|classes| classes.get(&name))
+        .cloned()
+        .ok_or_else(|| Error::ValueNotFound(name.clone()))?;
+
+    let json_args = audited_args(state, scope, args)?;
+    authorize_call(state, scope, &format!("{name}.constructor"), &json_args)?;
+    class.construct(state, scope, args)
+}
+
+#[op2]
+/// Calls a method or getter on an instance of a registered class
+fn op_host_object_call<'s>(
+    state: &mut OpState,
+    scope: &mut v8::HandleScope<'s>,
+    #[string] name: String,
+    rid: u32,
+    #[string] member: String,
+    args: v8::Local<'s, v8::Value>,
+) -> Result<v8::Local<'s, v8::Value>, Error> {
+    let class = state
+        .try_borrow::<HostClasses>()
+        .and_then(|classes| classes.get(&name))
+        .cloned()
+        .ok_or_else(|| Error::ValueNotFound(name.clone()))?;
+
+    let json_args = audited_args(state, scope, args)?;
+    authorize_call(state, scope, &format!("{name}.{member}"), &json_args)?;
+    let args = v8_array_elements(scope, args)?;
+    class.call(state, scope, rid, &member, &args)
+}
+
+/// Converts the arguments to a class member to JSON in runtimes with an audit sink, which records them
+fn audited_args(
+    state: &OpState,
+    scope: &mut v8::HandleScope,
+    args: v8::Local<v8::Value>,
+) -> Result<Vec<serde_json::Value>, Error> {
+    if state.has::<Auditor>() {
+        Ok(serde_v8::from_v8(scope, args)?)
+    } else {
+        Ok(Vec::new())
+    }
+}
 
-    Box::pin(std::future::ready(Err(Error::ValueNotCallable(name))))
+#[op2]
+/// Frees an instance of a registered class, once its JS object is garbage collected
+/// Resources that are not instances of the named class are left alone
+fn op_host_object_drop(state: &mut OpState, #[string] name: String, rid: u32) {
+    let class = state
+        .try_borrow::<HostClasses>()
+        .and_then(|classes| classes.get(&name))
+        .cloned();
+    if let Some(class) = class {
+        class.drop_instance(state, rid);
+    }
+}
+
+#[op2]
+#[serde]
+/// Returns the functions listed by a `host:` module, as `[name, isAsync]` pairs
+fn op_host_module_functions(
+    state: &mut OpState,
+    #[string] name: String,
+) -> Result<Vec<(String, bool)>, Error> {
+    state
+        .try_borrow::<HostModules>()
+        .and_then(|modules| modules.0.get(&name))
+        .cloned()
+        .ok_or_else(|| Error::ValueNotFound(name))
+}
+
+#[op2(fast)]
+/// Returns true once the runtime has been locked down, so that classes built afterwards are frozen
+fn op_locked_down(state: &mut OpState) -> bool {
+    state.has::<LockedDown>()
+}
+
+#[op2(fast)]
+/// Returns a number that changes each time a class is registered
+fn op_host_class_generation(state: &mut OpState) -> u32 {
+    state
+        .try_borrow::<HostClassGeneration>()
+        .map_or(0, |generation| generation.0)
+}
+
+#[op2(async)]
+/// Waits for an async function to queue a call to a JS function, and returns its id
+/// Polled by `rustyscript.js`, without keeping the event loop alive
+async fn op_next_callback(state: Rc<RefCell<OpState>>) -> u32 {
+    let context = state.borrow().try_borrow::<AsyncFunctionContext>().cloned();
+    match context {
+        Some(context) => std::future::poll_fn(|cx| context.poll_next(cx)).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[op2]
+/// Returns the function and arguments of a queued call, as `[function, args]`
+fn op_take_callback<'s>(
+    state: &mut OpState,
+    scope: &mut v8::HandleScope<'s>,
+    id: u32,
+) -> Result<v8::Local<'s, v8::Value>, Error> {
+    state.borrow::<AsyncFunctionContext>().take(scope, id)
+}
+
+#[op2]
+/// Completes a queued call with the value returned by the JS function
+fn op_resolve_callback(state: &mut OpState, id: u32, #[serde] value: serde_json::Value) {
+    if let Some(context) = state.try_borrow::<AsyncFunctionContext>() {
+        context.resolve(id, Ok(value));
+    }
+}
+
+#[op2]
+/// Completes a queued call with the error thrown by the JS function
+fn op_reject_callback(
+    state: &mut OpState,
+    scope: &mut v8::HandleScope,
+    id: u32,
+    error: v8::Local<v8::Value>,
+) {
+    if let Some(context) = state.try_borrow::<AsyncFunctionContext>() {
+        let error = deno_core::error::JsError::from_v8_exception(scope, error);
+        context.resolve(id, Err(error.into()));
+    }
 }
 
 extension!(
     rustyscript,
-    ops = [op_register_entrypoint, call_registered_function, call_registered_function_async],
+    ops = [
+        op_register_entrypoint,
+        op_unhandled_rejection,
+        op_deterministic_random,
+        op_virtual_now,
+        op_audit_storage,
+        op_audit_caller,
+        op_host_class_info,
+        op_host_class_generation,
+        op_locked_down,
+        op_host_module_functions,
+        op_host_object_new,
+        op_host_object_call,
+        op_host_object_drop,
+        op_next_callback,
+        op_take_callback,
+        op_resolve_callback,
+        op_reject_callback,
+        call_registered_function,
+        call_registered_function_async
+    ],
     esm_entry_point = "ext:rustyscript/rustyscript.js",
     esm = [ dir "src/ext/rustyscript", "rustyscript.js" ],
 );
@@ -0,0 +1,331 @@
+// Installs the extension globals in a realm created by `Runtime::create_realm`
+// Runs inside the realm, so that everything it defines belongs to the realm
+// `host` holds the wrapped functions collected by `realm_host.js`, which only exchange copies of plain data,
+// so the classes below are rebuilt here on top of them
+//
+// Returns the function used by `Runtime::load_module_in_realm` to collect the registered entrypoint
+((host) => {
+    'use strict';
+    const define = (name, value) => Object.defineProperty(globalThis, name, {
+        value, writable: true, enumerable: false, configurable: true,
+    });
+
+    let entrypoint;
+    const functions = new Proxy({}, {
+        get: (_target, name) => typeof name === 'string' ? host.function(name) : undefined,
+    });
+    const asyncFunctions = new Proxy({}, {
+        get: (_target, name) => typeof name === 'string' ? host.asyncFunction(name) : undefined,
+    });
+    Object.defineProperty(globalThis, 'rustyscript', {
+        value: Object.freeze({
+            'register_entrypoint': (f) => { entrypoint = f; },
+            'functions': functions,
+            'async_functions': asyncFunctions,
+            'bail': (msg) => { throw new Error(msg) },
+        }),
+    });
+
+    const console = {};
+    for (const level of ['log', 'info', 'warn', 'error', 'debug', 'trace']) {
+        console[level] = (...args) => host.console(level, ...args);
+    }
+    define('console', console);
+
+    for (const name of [
+        'setTimeout', 'setInterval', 'setImmediate', 'clearTimeout', 'clearInterval', 'clearImmediate',
+    ]) {
+        const timer = host[name];
+        define(name, (...args) => timer(...args));
+    }
+
+    // Errors from the host arrive as copies, with DOMExceptions reduced to a plain error with the same name
+    const makeError = (name, message) => {
+        if (host.DOMException) return new DOMException(message, name);
+        const error = new Error(message);
+        error.name = name;
+        return error;
+    };
+    const restoreErrors = (f) => (...args) => {
+        try {
+            return f(...args);
+        } catch (e) {
+            if (e instanceof Error && e.constructor === Error && e.name !== 'Error') {
+                throw makeError(e.name, e.message);
+            }
+            throw e;
+        }
+    };
+
+    if (host.DOMException) {
+        const codes = {
+            'IndexSizeError': 1, 'HierarchyRequestError': 3, 'WrongDocumentError': 4,
+            'InvalidCharacterError': 5, 'NoModificationAllowedError': 7, 'NotFoundError': 8,
+            'NotSupportedError': 9, 'InvalidStateError': 11, 'SyntaxError': 12,
+            'InvalidModificationError': 13, 'NamespaceError': 14, 'InvalidAccessError': 15,
+            'TypeMismatchError': 17, 'SecurityError': 18, 'NetworkError': 19, 'AbortError': 20,
+            'URLMismatchError': 21, 'QuotaExceededError': 22, 'TimeoutError': 23,
+            'InvalidNodeTypeError': 24, 'DataCloneError': 25,
+        };
+
+        class DOMException extends Error {
+            constructor(message = '', name = 'Error') {
+                super(String(message));
+                Object.defineProperties(this, {
+                    'name': { value: String(name), configurable: true, writable: true },
+                    'code': { value: codes[String(name)] ?? 0, configurable: true, writable: true },
+                });
+            }
+        }
+        define('DOMException', DOMException);
+    }
+
+    if (host.atob) {
+        define('atob', restoreErrors((data) => host.atob(String(data))));
+        define('btoa', restoreErrors((data) => host.btoa(String(data))));
+    }
+
+    if (host.encode) {
+        class TextEncoder {
+            get encoding() { return 'utf-8'; }
+
+            encode(input = '') {
+                return host.encode(String(input));
+            }
+
+            encodeInto(source, destination) {
+                source = String(source);
+                const bytes = host.encode(source);
+
+                // Only whole characters are written
+                let read = 0, written = 0;
+                for (const char of source) {
+                    const point = char.codePointAt(0);
+                    const size = point < 0x80 ? 1 : point < 0x800 ? 2 : point < 0x10000 ? 3 : 4;
+                    if (written + size > destination.length) break;
+                    read += char.length;
+                    written += size;
+                }
+
+                destination.set(bytes.subarray(0, written));
+                return { read, written };
+            }
+        }
+
+        class TextDecoder {
+            #encoding;
+            #fatal;
+            #ignoreBOM;
+
+            constructor(label = 'utf-8', options = {}) {
+                this.#encoding = host.decoderEncoding(String(label));
+                this.#fatal = Boolean(options.fatal);
+                this.#ignoreBOM = Boolean(options.ignoreBOM);
+            }
+
+            get encoding() { return this.#encoding; }
+            get fatal() { return this.#fatal; }
+            get ignoreBOM() { return this.#ignoreBOM; }
+
+            decode(input = new Uint8Array(), options = {}) {
+                if (options.stream) {
+                    throw new TypeError('Streaming decodes are not supported in realms');
+                }
+                const bytes = ArrayBuffer.isView(input)
+                    ? new Uint8Array(input.buffer, input.byteOffset, input.byteLength)
+                    : new Uint8Array(input);
+                return host.decode(bytes, this.#encoding, this.#fatal, this.#ignoreBOM);
+            }
+        }
+
+        define('TextEncoder', TextEncoder);
+        define('TextDecoder', TextDecoder);
+    }
+
+    if (host.parseUrl) {
+        // Links a URL and its searchParams, so that changing one updates the other
+        let attachParams, refreshParams, updateSearch;
+
+        class URLSearchParams {
+            #entries = [];
+            #url = null;
+
+            constructor(init = '') {
+                if (typeof init === 'object' && init !== null) {
+                    const pairs = typeof init[Symbol.iterator] === 'function'
+                        ? Array.from(init, (pair) => Array.from(pair))
+                        : Object.keys(init).map((key) => [key, init[key]]);
+                    for (const pair of pairs) {
+                        if (pair.length !== 2) {
+                            throw new TypeError('Each query pair must be an iterable [name, value] tuple');
+                        }
+                        this.#entries.push([String(pair[0]), String(pair[1])]);
+                    }
+                } else {
+                    this.#entries = host.parseSearch(String(init));
+                }
+            }
+
+            static {
+                attachParams = (params, url) => { params.#url = url; };
+                refreshParams = (params, search) => { params.#entries = host.parseSearch(search); };
+            }
+
+            #update() {
+                if (this.#url) updateSearch(this.#url, this.toString());
+            }
+
+            get size() { return this.#entries.length; }
+
+            append(name, value) {
+                this.#entries.push([String(name), String(value)]);
+                this.#update();
+            }
+
+            delete(name, value) {
+                name = String(name);
+                this.#entries = this.#entries.filter(([n, v]) =>
+                    n !== name || (value !== undefined && v !== String(value)));
+                this.#update();
+            }
+
+            get(name) {
+                name = String(name);
+                return this.#entries.find(([n]) => n === name)?.[1] ?? null;
+            }
+
+            getAll(name) {
+                name = String(name);
+                return this.#entries.filter(([n]) => n === name).map(([, v]) => v);
+            }
+
+            has(name, value) {
+                name = String(name);
+                return this.#entries.some(([n, v]) =>
+                    n === name && (value === undefined || v === String(value)));
+            }
+
+            set(name, value) {
+                name = String(name);
+                const index = this.#entries.findIndex(([n]) => n === name);
+                if (index === -1) {
+                    this.#entries.push([name, String(value)]);
+                } else {
+                    this.#entries[index] = [name, String(value)];
+                    this.#entries = this.#entries.filter(([n], i) => n !== name || i === index);
+                }
+                this.#update();
+            }
+
+            sort() {
+                this.#entries.sort(([a], [b]) => (a < b ? -1 : a > b ? 1 : 0));
+                this.#update();
+            }
+
+            forEach(callback, thisArg) {
+                for (const [name, value] of this.#entries) {
+                    callback.call(thisArg, value, name, this);
+                }
+            }
+
+            keys() { return this.#entries.map(([n]) => n)[Symbol.iterator](); }
+            values() { return this.#entries.map(([, v]) => v)[Symbol.iterator](); }
+            entries() { return this.#entries.map(([n, v]) => [n, v])[Symbol.iterator](); }
+            [Symbol.iterator]() { return this.entries(); }
+
+            toString() {
+                return host.serializeSearch(this.#entries);
+            }
+        }
+
+        class URL {
+            #parts;
+            #searchParams;
+
+            constructor(url, base) {
+                this.#parts = host.parseUrl(String(url), base === undefined ? undefined : String(base));
+                this.#searchParams = new URLSearchParams(this.#parts.search);
+                attachParams(this.#searchParams, this);
+            }
+
+            static {
+                updateSearch = (url, search) => {
+                    url.#parts = host.updateUrl(url.#parts.href, 'search', search);
+                };
+
+                // Each part is read from the parsed URL, and set by parsing it again with the change
+                for (const part of [
+                    'href', 'protocol', 'username', 'password', 'host', 'hostname', 'port', 'pathname', 'search', 'hash',
+                ]) {
+                    Object.defineProperty(URL.prototype, part, {
+                        get() { return this.#parts[part]; },
+                        set(value) {
+                            this.#parts = part === 'href'
+                                ? host.parseUrl(String(value))
+                                : host.updateUrl(this.#parts.href, part, String(value));
+                            if (part === 'href' || part === 'search') {
+                                refreshParams(this.#searchParams, this.#parts.search);
+                            }
+                        },
+                        enumerable: true, configurable: true,
+                    });
+                }
+            }
+
+            static canParse(url, base) {
+                try {
+                    new URL(url, base);
+                    return true;
+                } catch {
+                    return false;
+                }
+            }
+
+            static parse(url, base) {
+                try {
+                    return new URL(url, base);
+                } catch {
+                    return null;
+                }
+            }
+
+            get origin() { return this.#parts.origin; }
+            get searchParams() { return this.#searchParams; }
+            toString() { return this.#parts.href; }
+            toJSON() { return this.#parts.href; }
+        }
+
+        define('URL', URL);
+        define('URLSearchParams', URLSearchParams);
+    }
+
+    if (host.randomBytes) {
+        const integerArrays = [
+            Int8Array, Uint8Array, Uint8ClampedArray, Int16Array, Uint16Array,
+            Int32Array, Uint32Array, BigInt64Array, BigUint64Array,
+        ];
+        const getRandomValues = restoreErrors((array) => {
+            if (!integerArrays.some((type) => array instanceof type)) {
+                throw makeError('TypeMismatchError', 'The provided value is not an integer-type TypedArray');
+            }
+            new Uint8Array(array.buffer, array.byteOffset, array.byteLength)
+                .set(host.randomBytes(array.byteLength));
+            return array;
+        });
+
+        Object.defineProperty(globalThis, 'crypto', {
+            value: Object.freeze({
+                'getRandomValues': getRandomValues,
+                'randomUUID': () => host.randomUUID(),
+            }),
+            writable: false, enumerable: false, configurable: true,
+        });
+    }
+
+    // Taken after each module is loaded into the realm
+    return () => {
+        const f = entrypoint;
+        entrypoint = undefined;
+        return f;
+    };
+})
@@ -0,0 +1,81 @@
+// Collects the parts of the main realm that realms created by `Runtime::create_realm` can use
+// Realms never receive these functions directly - each one is wrapped in the realm,
+// and values passed to or returned from it are copied between the realms
+// Only plain data crosses over, so `realm.js` rebuilds classes such as `URL` inside the realm
+(() => {
+    'use strict';
+    const { functions, async_functions } = globalThis.rustyscript;
+    const {
+        console, setTimeout, setInterval, setImmediate, clearTimeout, clearInterval, clearImmediate,
+        DOMException, atob, btoa, TextEncoder, TextDecoder, URL, URLSearchParams, crypto,
+    } = globalThis;
+
+    // DOMExceptions are not native errors, so they would be copied as empty objects
+    // Rethrow them as errors with the same name and message, which `realm.js` turns back into DOMExceptions
+    const plainErrors = (f) => (...args) => {
+        try {
+            return f(...args);
+        } catch (e) {
+            if (DOMException && e instanceof DOMException) {
+                const error = new Error(e.message);
+                error.name = e.name;
+                throw error;
+            }
+            throw e;
+        }
+    };
+
+    const urlParts = (url) => ({
+        'href': url.href, 'origin': url.origin, 'protocol': url.protocol,
+        'username': url.username, 'password': url.password,
+        'host': url.host, 'hostname': url.hostname, 'port': url.port,
+        'pathname': url.pathname, 'search': url.search, 'hash': url.hash,
+    });
+
+    const host = {
+        'function': (name) => functions[name],
+        'asyncFunction': (name) => async_functions[name],
+        'console': (level, ...args) => console[level](...args),
+        'setTimeout': (callback, delay, ...args) => setTimeout(callback, delay, ...args),
+        'setInterval': (callback, delay, ...args) => setInterval(callback, delay, ...args),
+        'setImmediate': (callback, ...args) => setImmediate(callback, ...args),
+        'clearTimeout': (id) => clearTimeout(id),
+        'clearInterval': (id) => clearInterval(id),
+        'clearImmediate': (id) => clearImmediate(id),
+    };
+
+    if (DOMException) {
+        host['DOMException'] = true;
+    }
+
+    if (atob && btoa) {
+        host['atob'] = plainErrors((data) => atob(data));
+        host['btoa'] = plainErrors((data) => btoa(data));
+    }
+
+    if (TextEncoder && TextDecoder) {
+        const encoder = new TextEncoder();
+        host['encode'] = (input) => encoder.encode(input);
+        host['decoderEncoding'] = (label) => new TextDecoder(label).encoding;
+        host['decode'] = (bytes, label, fatal, ignoreBOM) =>
+            new TextDecoder(label, { fatal, ignoreBOM }).decode(bytes);
+    }
+
+    if (URL && URLSearchParams) {
+        host['parseUrl'] = (href, base) => urlParts(base === undefined ? new URL(href) : new URL(href, base));
+        host['updateUrl'] = (href, part, value) => {
+            const url = new URL(href);
+            url[part] = value;
+            return urlParts(url);
+        };
+        host['parseSearch'] = (search) => [...new URLSearchParams(search)];
+        host['serializeSearch'] = (entries) => new URLSearchParams(entries).toString();
+    }
+
+    if (crypto) {
+        host['randomBytes'] = plainErrors((length) => crypto.getRandomValues(new Uint8Array(length)));
+        host['randomUUID'] = () => crypto.randomUUID();
+    }
+
+    return host;
+})()
@@ -32,25 +32,163 @@ const getterOnly = (getter) => {
 }
 const applyToGlobal = (properties) => Object.defineProperties(globalThis, properties);
 
+// Captured now, so that `Deno.core` can be hidden by a lockdown
+const ops = Deno.core.ops;
+const unrefOpPromise = Deno.core.unrefOpPromise;
+
+// Runs the calls to JS functions queued by async rust functions
+// Started by the first async function call; the pending op never keeps the event loop alive by itself
+let pumpingCallbacks = false;
+const runCallback = async (id) => {
+    try {
+        const [callback, args] = ops.op_take_callback(id);
+        ops.op_resolve_callback(id, await callback(...args));
+    } catch (e) {
+        ops.op_reject_callback(id, e);
+    }
+};
+const pumpCallbacks = async () => {
+    if (pumpingCallbacks) return;
+    pumpingCallbacks = true;
+    try {
+        while (true) {
+            const next = ops.op_next_callback();
+            unrefOpPromise(next);
+            runCallback(await next);
+        }
+    } finally {
+        // Lets the next async function call start the pump again
+        pumpingCallbacks = false;
+    }
+};
+
+// Thrown when a module calls a registered function it was not granted
+class CapabilityError extends Error {
+    constructor(message) {
+        super(message);
+        this.name = 'CapabilityError';
+    }
+}
+Deno.core.registerErrorClass('CapabilityError', CapabilityError);
+
+// Builds the JS class for a rust type registered with `Runtime::register_class`
+// Instances hold the id of their rust object, which is freed once they are garbage collected
+// Classes are shared by every script, so they are frozen in locked down runtimes
+const hostObjects = new FinalizationRegistry(({ name, rid }) => ops.op_host_object_drop(name, rid));
+const hostClasses = new Map();
+let hostClassGeneration = 0;
+const makeHostClass = (name) => {
+    const info = ops.op_host_class_info(name);
+    if (!info) return undefined;
+
+    const rids = new WeakMap();
+    const ridOf = (object) => {
+        const rid = rids.get(object);
+        if (rid === undefined) throw new TypeError('Illegal invocation');
+        return rid;
+    };
+
+    const HostClass = class {
+        constructor(...args) {
+            const rid = ops.op_host_object_new(name, args);
+            rids.set(this, rid);
+            hostObjects.register(this, { name, rid });
+        }
+    };
+    Object.defineProperty(HostClass, 'name', { value: name });
+
+    for (const method of info.methods) {
+        Object.defineProperty(HostClass.prototype, method, {
+            value: function (...args) {
+                return ops.op_host_object_call(name, ridOf(this), method, args);
+            },
+            writable: true, configurable: true,
+        });
+    }
+    for (const getter of info.getters) {
+        Object.defineProperty(HostClass.prototype, getter, {
+            get() { return ops.op_host_object_call(name, ridOf(this), getter, []); },
+            configurable: true,
+        });
+    }
+
+    if (ops.op_locked_down()) {
+        for (const key of Reflect.ownKeys(HostClass.prototype)) {
+            const { value, get } = Reflect.getOwnPropertyDescriptor(HostClass.prototype, key);
+            Object.freeze(value ?? get);
+        }
+        Object.freeze(HostClass.prototype);
+        Object.freeze(HostClass);
+    }
+
+    return HostClass;
+};
+
 // Populate the global object
 globalThis.rustyscript = {
-    'register_entrypoint': (f) => Deno.core.ops.op_register_entrypoint(f),
+    'register_entrypoint': (f) => ops.op_register_entrypoint(f),
     'bail': (msg) => { throw new Error(msg) },
+    'CapabilityError': CapabilityError,
     
     'functions': new Proxy({}, {
         get: function(_target, name) {
-            return (...args) => Deno.core.ops.call_registered_function(name, args);
+            return (...args) => ops.call_registered_function(name, args);
         }
     }),
 
     'async_functions': new Proxy({}, {
         get: function(_target, name) {
-            return (...args) => Deno.core.ops.call_registered_function_async(name, args);
+            return (...args) => {
+                pumpCallbacks();
+                return ops.call_registered_function_async(name, args);
+            };
+        }
+    }),
+
+    'classes': new Proxy({}, {
+        get: function(_target, name) {
+            if (typeof name !== 'string') return undefined;
+
+            // Classes registered since the last lookup may replace ones built already
+            const generation = ops.op_host_class_generation();
+            if (generation !== hostClassGeneration) {
+                hostClasses.clear();
+                hostClassGeneration = generation;
+            }
+
+            if (!hostClasses.has(name)) {
+                const hostClass = makeHostClass(name);
+                if (!hostClass) return undefined;
+                hostClasses.set(name, hostClass);
+            }
+            return hostClasses.get(name);
         }
     })
 };
 Object.freeze(globalThis.rustyscript);
 
+// Builds the exports of a `host:` module registered with `Runtime::register_host_module`
+// Only the generated modules can import it, since they are loaded as extension modules,
+// and only the functions the named module lists are handed out
+const hostModule = (name) => {
+    const exports = {};
+    for (const [functionName, isAsync] of ops.op_host_module_functions(name)) {
+        exports[functionName] = isAsync
+            ? (...args) => {
+                pumpCallbacks();
+                return ops.call_registered_function_async(functionName, args);
+            }
+            : (...args) => ops.call_registered_function(functionName, args);
+    }
+    return Object.freeze(exports);
+};
+
+// Report promise rejections that nothing handles to the runtime
+// If the op returns false, the rejection is raised by the event loop as usual
+Deno.core.setUnhandledPromiseRejectionHandler(
+    (_promise, reason) => ops.op_unhandled_rejection(reason)
+);
+
 export {
-    nonEnumerable, readOnly, writeable, getterOnly, applyToGlobal
+    nonEnumerable, readOnly, writeable, getterOnly, applyToGlobal, hostModule
 };
\ No newline at end of file
@@ -0,0 +1,28 @@
+// Wraps the host APIs that make permission checks, so that each check is attributed to the module that called them
+// Only run when `RuntimeOptions::audit_sink` is set
+// Checks made once the call has returned, such as for redirects followed by `fetch`, are not attributed to a module
+(() => {
+    'use strict';
+    const ops = Deno.core.ops;
+
+    const audited = (f) => {
+        const wrapper = function (...args) {
+            ops.op_audit_caller(true);
+            try {
+                return Reflect.apply(f, this, args);
+            } finally {
+                ops.op_audit_caller(false);
+            }
+        };
+        Object.defineProperty(wrapper, 'name', { value: f.name });
+        Object.defineProperty(wrapper, 'length', { value: f.length });
+        return wrapper;
+    };
+
+    for (const name of ['fetch']) {
+        const descriptor = Reflect.getOwnPropertyDescriptor(globalThis, name);
+        if (typeof descriptor?.value === 'function') {
+            Object.defineProperty(globalThis, name, { ...descriptor, value: audited(descriptor.value) });
+        }
+    }
+})();
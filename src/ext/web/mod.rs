@@ -1,8 +1,42 @@
+use crate::audit::{AuditEventKind, Auditor};
 use deno_core::{extension, Extension, ModuleSpecifier};
-use std::{rc::Rc, sync::Arc};
+use std::{path::Path, rc::Rc, sync::Arc};
 
-#[derive(Clone)]
-pub struct Permissions;
+/// Allows all access, recording each check with the runtime's audit sink, if it has one
+#[derive(Clone, Default)]
+pub struct Permissions {
+    audit: Option<Auditor>,
+}
+
+impl Permissions {
+    /// Record checks with `auditor`
+    /// Checks are attributed to the module that called the API making them, if it was wrapped by `audit_web.js`
+    pub(crate) fn new(auditor: Auditor) -> Self {
+        Self {
+            audit: Some(auditor),
+        }
+    }
+
+    fn record(&self, kind: AuditEventKind) {
+        if let Some(audit) = &self.audit {
+            audit.record_from_caller(kind);
+        }
+    }
+
+    fn record_net(&self, target: String, api_name: &str) {
+        self.record(AuditEventKind::NetPermission {
+            target,
+            api_name: api_name.to_string(),
+        });
+    }
+
+    fn record_read(&self, path: &Path, api_name: &str) {
+        self.record(AuditEventKind::ReadPermission {
+            path: path.to_path_buf(),
+            api_name: api_name.to_string(),
+        });
+    }
+}
 
 impl deno_web::TimersPermission for Permissions {
     fn allow_hrtime(&mut self) -> bool {
@@ -13,17 +47,19 @@ impl deno_web::TimersPermission for Permissions {
 impl deno_fetch::FetchPermissions for Permissions {
     fn check_net_url(
         &mut self,
-        _url: &deno_core::url::Url,
-        _api_name: &str,
+        url: &deno_core::url::Url,
+        api_name: &str,
     ) -> Result<(), deno_core::error::AnyError> {
+        self.record_net(url.to_string(), api_name);
         Ok(())
     }
 
     fn check_read(
         &mut self,
-        _p: &std::path::Path,
-        _api_name: &str,
+        p: &std::path::Path,
+        api_name: &str,
     ) -> Result<(), deno_core::error::AnyError> {
+        self.record_read(p, api_name);
         Ok(())
     }
 }
@@ -31,25 +67,35 @@ impl deno_fetch::FetchPermissions for Permissions {
 impl deno_net::NetPermissions for Permissions {
     fn check_net<T: AsRef<str>>(
         &mut self,
-        _host: &(T, Option<u16>),
-        _api_name: &str,
+        host: &(T, Option<u16>),
+        api_name: &str,
     ) -> Result<(), deno_core::error::AnyError> {
+        let target = match host.1 {
+            Some(port) => format!("{}:{port}", host.0.as_ref()),
+            None => host.0.as_ref().to_string(),
+        };
+        self.record_net(target, api_name);
         Ok(())
     }
 
     fn check_read(
         &mut self,
-        _p: &std::path::Path,
-        _api_name: &str,
+        p: &std::path::Path,
+        api_name: &str,
     ) -> Result<(), deno_core::error::AnyError> {
+        self.record_read(p, api_name);
         Ok(())
     }
 
     fn check_write(
         &mut self,
-        _p: &std::path::Path,
-        _api_name: &str,
+        p: &std::path::Path,
+        api_name: &str,
     ) -> Result<(), deno_core::error::AnyError> {
+        self.record(AuditEventKind::WritePermission {
+            path: p.to_path_buf(),
+            api_name: api_name.to_string(),
+        });
         Ok(())
     }
 }
@@ -59,7 +105,7 @@ extension!(
     deps = [rustyscript],
     esm_entry_point = "ext:init_web/init_web.js",
     esm = [ dir "src/ext/web", "init_web.js" ],
-    state = |state| state.put(Permissions{})
+    state = |state| state.put(Permissions::default())
 );
 
 extension!(
@@ -67,7 +113,7 @@ extension!(
     deps = [rustyscript],
     esm_entry_point = "ext:init_fetch/init_fetch.js",
     esm = [ dir "src/ext/web", "init_fetch.js" ],
-    state = |state| state.put(Permissions{})
+    state = |state| state.put(Permissions::default())
 );
 
 extension!(
@@ -1,14 +1,23 @@
 // Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
 
 import { core, primordials } from "ext:core/mod.js";
-import { op_defer, op_now } from "ext:core/ops";
+import {
+  op_cancel_virtual_timer,
+  op_code_generation_allowed,
+  op_defer,
+  op_now,
+  op_queue_virtual_timer,
+  op_virtual_timers,
+} from "ext:core/ops";
 const {
   Uint8Array,
   Uint32Array,
   PromisePrototypeThen,
   TypedArrayPrototypeGetBuffer,
+  EvalError,
   TypeError,
   indirectEval,
+  MathMax,
   ReflectApply,
 } = primordials;
 
@@ -29,6 +38,40 @@ function checkThis(thisArg) {
   }
 }
 
+/**
+ * Fail early if the runtime will not allow a string-form timer to be compiled.
+ */
+function checkCodeGeneration(code) {
+  if (!op_code_generation_allowed(code)) {
+    throw new EvalError(
+      "Code generation from strings disallowed for this context",
+    );
+  }
+}
+
+/**
+ * Schedule a timer on the event loop, or against the runtime's virtual clock if it has one.
+ */
+function queueTimer(repeat, timeout, callback) {
+  if (op_virtual_timers()) {
+    return op_queue_virtual_timer(repeat, MathMax(timeout, 0), callback);
+  }
+  return core.queueUserTimer(
+    core.getTimerDepth() + 1,
+    repeat,
+    timeout,
+    callback,
+  );
+}
+
+function cancelTimer(id) {
+  if (op_virtual_timers()) {
+    op_cancel_virtual_timer(id);
+  } else {
+    core.cancelTimer(id);
+  }
+}
+
 /**
  * Call a callback function immediately.
  */
@@ -51,6 +94,7 @@ function setTimeout(callback, timeout = 0, ...args) {
   // If callback is a string, replace it with a function that evals the string on every timeout
   if (typeof callback !== "function") {
     const unboundCallback = webidl.converters.DOMString(callback);
+    checkCodeGeneration(unboundCallback);
     callback = () => indirectEval(unboundCallback);
   }
   if (args.length > 0) {
@@ -58,12 +102,7 @@ function setTimeout(callback, timeout = 0, ...args) {
     callback = () => ReflectApply(unboundCallback, window, args);
   }
   timeout = webidl.converters.long(timeout);
-  return core.queueUserTimer(
-    core.getTimerDepth() + 1,
-    false,
-    timeout,
-    callback,
-  );
+  return queueTimer(false, timeout, callback);
 }
 
 /**
@@ -73,6 +112,7 @@ function setInterval(callback, timeout = 0, ...args) {
   checkThis(this);
   if (typeof callback !== "function") {
     const unboundCallback = webidl.converters.DOMString(callback);
+    checkCodeGeneration(unboundCallback);
     callback = () => indirectEval(unboundCallback);
   }
   if (args.length > 0) {
@@ -80,12 +120,7 @@ function setInterval(callback, timeout = 0, ...args) {
     callback = () => ReflectApply(unboundCallback, window, args);
   }
   timeout = webidl.converters.long(timeout);
-  return core.queueUserTimer(
-    core.getTimerDepth() + 1,
-    true,
-    timeout,
-    callback,
-  );
+  return queueTimer(true, timeout, callback);
 }
 
 /**
@@ -94,7 +129,7 @@ function setInterval(callback, timeout = 0, ...args) {
 function clearTimeout(id = 0) {
   checkThis(this);
   id = webidl.converters.long(id);
-  core.cancelTimer(id);
+  cancelTimer(id);
 }
 
 /**
@@ -103,7 +138,7 @@ function clearTimeout(id = 0) {
 function clearInterval(id = 0) {
   checkThis(this);
   id = webidl.converters.long(id);
-  core.cancelTimer(id);
+  cancelTimer(id);
 }
 
 /**
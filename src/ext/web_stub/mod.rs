@@ -10,9 +10,12 @@ extension!(
     deno_web,
     ops = [
         timers::op_now, timers::op_defer,
+        timers::op_virtual_timers, timers::op_queue_virtual_timer, timers::op_cancel_virtual_timer,
+        timers::op_code_generation_allowed,
     ],
     esm_entry_point = "ext:deno_web/init_stub.js",
     esm = [ dir "src/ext/web_stub", "init_stub.js", "01_dom_exception.js", "02_timers.js" ],
+    state = |state| state.put(timers::StartTime::now())
 );
 
 pub fn extensions() -> Vec<Extension> {
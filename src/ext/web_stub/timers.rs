@@ -2,9 +2,12 @@
 
 //! This module helps deno implement timers and performance APIs.
 
+use crate::clock::{ClockState, VirtualTimers};
+use crate::code_generation::CodeGenerationPolicy;
 use deno_core::op2;
+use deno_core::v8;
 use deno_core::OpState;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 pub type StartTime = Instant;
 
@@ -12,18 +15,26 @@ pub type StartTime = Instant;
 // since the start time of the deno runtime.
 // If the High precision flag is not set, the
 // nanoseconds are rounded on 2ms.
+// Runtimes with a virtual clock use it instead, without rounding.
 #[op2(fast)]
 pub fn op_now(state: &mut OpState, #[buffer] buf: &mut [u8]) {
-    let start_time = state.borrow::<StartTime>();
-    let elapsed = start_time.elapsed();
-    let seconds = elapsed.as_secs();
-    let mut subsec_nanos = elapsed.subsec_nanos();
+    let (seconds, subsec_nanos) = match state.try_borrow::<ClockState>() {
+        Some(clock) => {
+            let elapsed = clock.elapsed();
+            (elapsed.as_secs(), elapsed.subsec_nanos())
+        }
+        None => {
+            let elapsed = state.borrow::<StartTime>().elapsed();
+            let mut subsec_nanos = elapsed.subsec_nanos();
 
-    // Never allow hrtime in the stub
-    // Round the nano result on 2 milliseconds
-    // see: https://developer.mozilla.org/en-US/docs/Web/API/DOMHighResTimeStamp#Reduced_time_precision
-    let reduced_time_precision = 2_000_000; // 2ms in nanoseconds
-    subsec_nanos -= subsec_nanos % reduced_time_precision;
+            // Never allow hrtime in the stub
+            // Round the nano result on 2 milliseconds
+            // see: https://developer.mozilla.org/en-US/docs/Web/API/DOMHighResTimeStamp#Reduced_time_precision
+            let reduced_time_precision = 2_000_000; // 2ms in nanoseconds
+            subsec_nanos -= subsec_nanos % reduced_time_precision;
+            (elapsed.as_secs(), subsec_nanos)
+        }
+    };
 
     if buf.len() < 8 {
         return;
@@ -35,6 +46,44 @@ pub fn op_now(state: &mut OpState, #[buffer] buf: &mut [u8]) {
     buf[1] = subsec_nanos;
 }
 
+// Returns true if timers are scheduled against a virtual clock,
+// instead of the event loop. See `Runtime::advance_time`.
+#[op2(fast)]
+pub fn op_virtual_timers(state: &mut OpState) -> bool {
+    state.has::<VirtualTimers>()
+}
+
+// Schedules a timer against the virtual clock, returning its id.
+#[op2]
+pub fn op_queue_virtual_timer(
+    state: &mut OpState,
+    repeat: bool,
+    timeout: u32,
+    #[global] callback: v8::Global<v8::Function>,
+) -> u32 {
+    let timeout = Duration::from_millis(timeout.into());
+    let due = state.borrow::<ClockState>().clock.now() + timeout;
+    state
+        .borrow_mut::<VirtualTimers>()
+        .queue(due, repeat.then_some(timeout), callback)
+}
+
+#[op2(fast)]
+pub fn op_cancel_virtual_timer(state: &mut OpState, id: u32) {
+    if let Some(timers) = state.try_borrow_mut::<VirtualTimers>() {
+        timers.cancel(id);
+    }
+}
+
+// Returns false if the runtime disallows compiling the given string.
+// Used by string-form timers, which would otherwise only fail once they fire.
+#[op2(fast)]
+pub fn op_code_generation_allowed(state: &mut OpState, #[string] source: &str) -> bool {
+    state
+        .try_borrow::<CodeGenerationPolicy>()
+        .map_or(true, |policy| policy.allows(source))
+}
+
 #[allow(clippy::unused_async)]
 #[op2(async(lazy), fast)]
 pub async fn op_defer() {}
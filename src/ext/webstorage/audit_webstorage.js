@@ -0,0 +1,69 @@
+// Wraps `localStorage` and `sessionStorage` so that each access is recorded by the runtime's audit sink
+// Only run when `RuntimeOptions::audit_sink` is set
+(() => {
+    'use strict';
+    const ops = Deno.core.ops;
+
+    // Methods are called on the underlying storage, which fails brand checks on proxies
+    const audited = (name) => {
+        const getStorage = Reflect.getOwnPropertyDescriptor(globalThis, name).get;
+        let proxy;
+        return () => {
+            proxy ??= new Proxy(getStorage(), {
+                get(target, key) {
+                    const value = Reflect.get(target, key);
+                    if (typeof key !== 'string') {
+                        return value;
+                    }
+                    if (typeof value === 'function') {
+                        return (...args) => {
+                            const itemKey = args.length > 0 ? String(args[0]) : null;
+                            ops.op_audit_storage(name, key, itemKey);
+                            return Reflect.apply(value, target, args);
+                        };
+                    }
+                    ops.op_audit_storage(name, key === 'length' ? key : 'getItem', key === 'length' ? null : key);
+                    return value;
+                },
+                set(target, key, value) {
+                    ops.op_audit_storage(name, 'setItem', String(key));
+                    return Reflect.set(target, key, value);
+                },
+                deleteProperty(target, key) {
+                    ops.op_audit_storage(name, 'removeItem', String(key));
+                    return Reflect.deleteProperty(target, key);
+                },
+                defineProperty(target, key, descriptor) {
+                    ops.op_audit_storage(name, 'setItem', String(key));
+                    return Reflect.defineProperty(target, key, descriptor);
+                },
+                has(target, key) {
+                    if (typeof key === 'string') {
+                        ops.op_audit_storage(name, 'has', key);
+                    }
+                    return Reflect.has(target, key);
+                },
+                ownKeys(target) {
+                    ops.op_audit_storage(name, 'keys', null);
+                    return Reflect.ownKeys(target);
+                },
+                getOwnPropertyDescriptor(target, key) {
+                    if (typeof key === 'string') {
+                        ops.op_audit_storage(name, 'getItem', key);
+                    }
+                    return Reflect.getOwnPropertyDescriptor(target, key);
+                },
+            });
+            return proxy;
+        };
+    };
+
+    for (const name of ['sessionStorage', 'localStorage']) {
+        Object.defineProperty(globalThis, name, {
+            get: audited(name),
+            set() {},
+            enumerable: true,
+            configurable: true,
+        });
+    }
+})();
@@ -0,0 +1,251 @@
+//! Exposes stateful rust objects to JS as classes
+//!
+//! See [crate::Runtime::register_class]
+use crate::Error;
+use deno_core::{
+    serde_v8,
+    v8::{self, HandleScope},
+    OpState, Resource, ResourceId,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{borrow::Cow, cell::RefCell, collections::HashMap, rc::Rc};
+
+/// A rust type that scripts can construct, and use through its methods and getters
+///
+/// Registered classes are available from `rustyscript.classes`. Each instance is stored in the
+/// runtime's resource table, and dropped once the JS object is garbage collected.
+///
+/// ```rust
+/// use rustyscript::{ ClassBuilder, HostClass, Runtime, Error };
+///
+/// struct Counter {
+///     count: i64,
+/// }
+///
+/// impl HostClass for Counter {
+///     const NAME: &'static str = "Counter";
+///     type Args = (i64,);
+///
+///     fn construct((start,): Self::Args) -> Result<Self, Error> {
+///         Ok(Self { count: start })
+///     }
+///
+///     fn members(class: &mut ClassBuilder<Self>) {
+///         class
+///             .method("add", |counter: &mut Self, amount: i64| {
+///                 counter.count += amount;
+///                 Ok(counter.count)
+///             })
+///             .getter("count", |counter| Ok(counter.count));
+///     }
+/// }
+///
+/// # fn main() -> Result<(), Error> {
+/// let mut runtime = Runtime::new(Default::default())?;
+/// runtime.register_class::<Counter>()?;
+///
+/// let count: i64 = runtime.eval("
+///     const counter = new rustyscript.classes.Counter(1);
+///     counter.add(2);
+///     counter.count
+/// ")?;
+/// assert_eq!(count, 3);
+/// # Ok(())
+/// # }
+/// ```
+pub trait HostClass: Sized + 'static {
+    /// Name of the class in JS
+    const NAME: &'static str;
+
+    /// Arguments taken by the constructor, as a tuple
+    type Args: DeserializeOwned;
+
+    /// Create a new instance, when `new` is called from JS
+    ///
+    /// # Errors
+    /// Errors are thrown in JS
+    fn construct(args: Self::Args) -> Result<Self, Error>;
+
+    /// Declare the methods and getters of the class
+    fn members(class: &mut ClassBuilder<Self>);
+}
+
+/// Represents a method of a [HostClass], with typed arguments
+/// Implemented for closures taking `&mut T`, followed by up to 8 `DeserializeOwned` arguments,
+/// and returning a `Result` of a `Serialize` type
+pub trait RsMethod<T, Args>: 'static {
+    /// Number of arguments the method takes from JS
+    const ARITY: usize;
+
+    /// Convert the arguments, call the method, and convert the result
+    fn call_v8<'s>(
+        &self,
+        this: &mut T,
+        scope: &mut HandleScope<'s>,
+        name: &str,
+        args: &[v8::Local<'s, v8::Value>],
+    ) -> Result<v8::Local<'s, v8::Value>, Error>;
+}
+
+/// A method or getter, with its argument types erased
+type Member<T> = Rc<
+    dyn for<'s> Fn(
+        &mut T,
+        &mut HandleScope<'s>,
+        &str,
+        &[v8::Local<'s, v8::Value>],
+    ) -> Result<v8::Local<'s, v8::Value>, Error>,
+>;
+
+/// Collects the methods and getters of a [HostClass]
+pub struct ClassBuilder<T> {
+    methods: HashMap<String, Member<T>>,
+    getters: HashMap<String, Member<T>>,
+}
+
+impl<T: HostClass> ClassBuilder<T> {
+    /// Add a method to the class
+    ///
+    /// # Arguments
+    /// * `name` - The name of the method in JS
+    /// * `method` - A closure taking `&mut T`, followed by the method's arguments
+    pub fn method<F, Args>(&mut self, name: &str, method: F) -> &mut Self
+    where
+        F: RsMethod<T, Args>,
+    {
+        let member: Member<T> =
+            Rc::new(move |this, scope, name, args| method.call_v8(this, scope, name, args));
+        self.methods.insert(name.to_string(), member);
+        self
+    }
+
+    /// Add a read-only property to the class
+    ///
+    /// # Arguments
+    /// * `name` - The name of the property in JS
+    /// * `getter` - A closure returning the property's value
+    pub fn getter<F, R>(&mut self, name: &str, getter: F) -> &mut Self
+    where
+        F: Fn(&T) -> Result<R, Error> + 'static,
+        R: Serialize,
+    {
+        let member: Member<T> = Rc::new(move |this, scope, _, _| {
+            let value = getter(this)?;
+            Ok(serde_v8::to_v8(scope, value)?)
+        });
+        self.getters.insert(name.to_string(), member);
+        self
+    }
+}
+
+/// The names of a class's members, used by `rustyscript.js` to build the JS class
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ClassInfo {
+    methods: Vec<String>,
+    getters: Vec<String>,
+}
+
+/// A registered class, with its type erased
+/// Stored in the OpState, by name
+pub(crate) trait ErasedHostClass {
+    /// Returns the names of the class's members
+    fn info(&self) -> ClassInfo;
+
+    /// Construct a new instance, and add it to the resource table
+    fn construct(
+        &self,
+        state: &mut OpState,
+        scope: &mut HandleScope,
+        args: v8::Local<v8::Value>,
+    ) -> Result<ResourceId, Error>;
+
+    /// Call a method or getter on an instance
+    fn call<'s>(
+        &self,
+        state: &mut OpState,
+        scope: &mut HandleScope<'s>,
+        rid: ResourceId,
+        member: &str,
+        args: &[v8::Local<'s, v8::Value>],
+    ) -> Result<v8::Local<'s, v8::Value>, Error>;
+
+    /// Free an instance
+    /// Does nothing if `rid` is not an instance of this class
+    fn drop_instance(&self, state: &mut OpState, rid: ResourceId);
+}
+
+/// Registered classes, by name
+pub(crate) type HostClasses = HashMap<String, Rc<dyn ErasedHostClass>>;
+
+/// Incremented each time a class is registered
+/// Lets `rustyscript.js` know that the JS classes it has built may be out of date
+#[derive(Default)]
+pub(crate) struct HostClassGeneration(pub u32);
+
+/// Create the type-erased version of a class, for storage in the OpState
+pub(crate) fn erase<T: HostClass>() -> Rc<dyn ErasedHostClass> {
+    let mut class = ClassBuilder::<T> {
+        methods: HashMap::new(),
+        getters: HashMap::new(),
+    };
+    T::members(&mut class);
+    Rc::new(class)
+}
+
+/// An instance of a host class, in the resource table
+struct HostObject<T>(RefCell<T>);
+
+impl<T: HostClass> Resource for HostObject<T> {
+    fn name(&self) -> Cow<str> {
+        T::NAME.into()
+    }
+}
+
+impl<T: HostClass> ErasedHostClass for ClassBuilder<T> {
+    fn info(&self) -> ClassInfo {
+        ClassInfo {
+            methods: self.methods.keys().cloned().collect(),
+            getters: self.getters.keys().cloned().collect(),
+        }
+    }
+
+    fn construct(
+        &self,
+        state: &mut OpState,
+        scope: &mut HandleScope,
+        args: v8::Local<v8::Value>,
+    ) -> Result<ResourceId, Error> {
+        let args: T::Args = serde_v8::from_v8(scope, args)
+            .map_err(|e| Error::TypeMismatch(format!("arguments to new {}: {e}", T::NAME)))?;
+        let object = T::construct(args)?;
+        Ok(state.resource_table.add(HostObject(RefCell::new(object))))
+    }
+
+    fn call<'s>(
+        &self,
+        state: &mut OpState,
+        scope: &mut HandleScope<'s>,
+        rid: ResourceId,
+        member: &str,
+        args: &[v8::Local<'s, v8::Value>],
+    ) -> Result<v8::Local<'s, v8::Value>, Error> {
+        let callback = self
+            .methods
+            .get(member)
+            .or_else(|| self.getters.get(member))
+            .ok_or_else(|| Error::ValueNotCallable(format!("{}.{member}", T::NAME)))?;
+
+        let object = state
+            .resource_table
+            .get::<HostObject<T>>(rid)
+            .map_err(|_| Error::Runtime(format!("{} instance was already freed", T::NAME)))?;
+        let mut this = object.0.try_borrow_mut()?;
+        callback(&mut this, scope, member, args)
+    }
+
+    fn drop_instance(&self, state: &mut OpState, rid: ResourceId) {
+        if state.resource_table.get::<HostObject<T>>(rid).is_ok() {
+            state.resource_table.close(rid).ok();
+        }
+    }
+}
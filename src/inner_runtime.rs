@@ -1,15 +1,30 @@
 use crate::{
+    audit::{AuditSink, Auditor},
     cache_provider::ModuleCacheProvider,
-    ext,
-    module_loader::RustyLoader,
-    traits::{ToDefinedValue, ToModuleSpecifier, ToV8String},
+    callbacks::{AsyncFunctionContext, FunctionContext, WithContext},
+    capabilities::{CapabilityDenial, CapabilityState, FunctionCapabilities},
+    clock::{ClockState, VirtualClock, VirtualTimers},
+    code_generation::CodeGenerationPolicy,
+    deterministic::{DeterministicOptions, DeterministicState},
+    error, ext,
+    host_class::{self, HostClass, HostClassGeneration, HostClasses, RsMethod},
+    lockdown::{self, LockdownOptions},
+    metrics::{CallStats, RuntimeMetrics},
+    module_loader::{versioned_code, RustyLoader},
+    realm::RealmHandle,
+    traits::{IntoArgs, ToDefinedValue, ToModuleSpecifier, ToV8String},
     transpiler::{self, transpile_extension},
+    v8_flags::{self, V8Flags},
+    watchdog::{TerminationReason, Watchdog},
     Error, Module, ModuleHandle,
 };
 use deno_core::{
-    serde_json, serde_v8::from_v8, v8, JsRuntime, PollEventLoopOptions, RuntimeOptions,
+    serde_json,
+    serde_v8::from_v8,
+    stats::{RuntimeActivity, RuntimeActivityStatsFilter},
+    v8, JsRuntime, PollEventLoopOptions, RuntimeOptions,
 };
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Serialize};
 use std::{collections::HashMap, pin::Pin, rc::Rc, time::Duration};
 
 /// Represents a function that can be registered with the runtime
@@ -38,6 +53,226 @@ impl<F> RsAsyncFunction for F where
 /// Type required to pass arguments to Functions
 pub type FunctionArguments = [serde_json::Value];
 
+/// A registered typed function, with its argument types erased
+/// Converts its arguments and return value directly with serde_v8
+pub(crate) type TypedFunctionCallback = Rc<
+    dyn for<'s> Fn(
+        &mut v8::HandleScope<'s>,
+        &str,
+        &[v8::Local<'s, v8::Value>],
+    ) -> Result<v8::Local<'s, v8::Value>, Error>,
+>;
+
+/// A registered typed async function, with its argument types erased
+/// Converts its arguments directly with serde_v8, and returns a future resolving to its result
+pub(crate) type TypedAsyncFunctionCallback = Rc<
+    dyn for<'s> Fn(
+        &mut v8::HandleScope<'s>,
+        AsyncFunctionContext,
+        &str,
+        &[v8::Local<'s, v8::Value>],
+    ) -> Result<TypedFunctionFuture, Error>,
+>;
+
+/// The future returned by a typed async function, with its result serialized
+pub type TypedFunctionFuture =
+    Pin<Box<dyn std::future::Future<Output = Result<serde_json::Value, Error>>>>;
+
+/// Represents a function with typed arguments that can be registered with the runtime
+/// Implemented for closures of up to 8 arguments, each `DeserializeOwned`, returning a `Result` of a `Serialize` type
+/// The closure may also take a `&mut` [FunctionContext] as its first argument, to call JS functions it is given
+///
+/// See [crate::Runtime::register_typed_function]
+pub trait RsTypedFunction<Args>: 'static {
+    /// Number of arguments the function takes from JS
+    const ARITY: usize;
+
+    /// Convert the arguments, call the function, and convert the result
+    fn call_v8<'s>(
+        &self,
+        scope: &mut v8::HandleScope<'s>,
+        name: &str,
+        args: &[v8::Local<'s, v8::Value>],
+    ) -> Result<v8::Local<'s, v8::Value>, Error>;
+}
+
+/// Represents an async function with typed arguments that can be registered with the runtime
+/// Implemented for closures taking an [AsyncFunctionContext], followed by up to 8 `DeserializeOwned` arguments,
+/// and returning a future that resolves to a `Result` of a `Serialize` type
+///
+/// See [crate::Runtime::register_typed_async_function]
+pub trait RsTypedAsyncFunction<Args>: 'static {
+    /// Number of arguments the function takes from JS
+    const ARITY: usize;
+
+    /// Convert the arguments, and call the function
+    fn call_v8<'s>(
+        &self,
+        scope: &mut v8::HandleScope<'s>,
+        context: AsyncFunctionContext,
+        name: &str,
+        args: &[v8::Local<'s, v8::Value>],
+    ) -> Result<TypedFunctionFuture, Error>;
+}
+
+/// Checks the number of arguments given to a typed function, and converts each one
+/// Missing trailing arguments are converted from `undefined`, so that `Option` arguments can be left out
+macro_rules! convert_typed_args {
+    ($scope:ident, $name:ident, $args:ident, $arity:expr $(, $arg:ident)*) => {
+        if $args.len() > $arity {
+            return Err(Error::TypeMismatch(format!(
+                "{} expects at most {} arguments, but got {}",
+                $name,
+                $arity,
+                $args.len()
+            )));
+        }
+
+        let undefined: v8::Local<v8::Value> = v8::undefined($scope).into();
+        let mut index = 0;
+        $(
+            let $arg: $arg = {
+                let value = $args.get(index).copied().unwrap_or(undefined);
+                index += 1;
+                from_v8($scope, value).map_err(|e| {
+                    Error::TypeMismatch(format!("argument {index} to {}: {e}", $name))
+                })?
+            };
+        )*
+    };
+}
+
+macro_rules! impl_typed_function {
+    ($arity:literal $(, $arg:ident)*) => {
+        impl<F, R, $($arg,)*> RsTypedFunction<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> Result<R, Error> + 'static,
+            R: Serialize,
+            $($arg: DeserializeOwned,)*
+        {
+            const ARITY: usize = $arity;
+
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn call_v8<'s>(
+                &self,
+                scope: &mut v8::HandleScope<'s>,
+                name: &str,
+                args: &[v8::Local<'s, v8::Value>],
+            ) -> Result<v8::Local<'s, v8::Value>, Error> {
+                convert_typed_args!(scope, name, args, Self::ARITY $(, $arg)*);
+                let result = self($($arg),*)?;
+                Ok(deno_core::serde_v8::to_v8(scope, result)?)
+            }
+        }
+
+        impl<F, R, $($arg,)*> RsTypedFunction<WithContext<($($arg,)*)>> for F
+        where
+            F: Fn(&mut FunctionContext, $($arg),*) -> Result<R, Error> + 'static,
+            R: Serialize,
+            $($arg: DeserializeOwned,)*
+        {
+            const ARITY: usize = $arity;
+
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn call_v8<'s>(
+                &self,
+                scope: &mut v8::HandleScope<'s>,
+                name: &str,
+                args: &[v8::Local<'s, v8::Value>],
+            ) -> Result<v8::Local<'s, v8::Value>, Error> {
+                convert_typed_args!(scope, name, args, Self::ARITY $(, $arg)*);
+                let result = self(&mut FunctionContext::new(scope), $($arg),*)?;
+                Ok(deno_core::serde_v8::to_v8(scope, result)?)
+            }
+        }
+
+        impl<F, T, R, $($arg,)*> RsMethod<T, ($($arg,)*)> for F
+        where
+            F: Fn(&mut T, $($arg),*) -> Result<R, Error> + 'static,
+            R: Serialize,
+            $($arg: DeserializeOwned,)*
+        {
+            const ARITY: usize = $arity;
+
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn call_v8<'s>(
+                &self,
+                this: &mut T,
+                scope: &mut v8::HandleScope<'s>,
+                name: &str,
+                args: &[v8::Local<'s, v8::Value>],
+            ) -> Result<v8::Local<'s, v8::Value>, Error> {
+                convert_typed_args!(scope, name, args, Self::ARITY $(, $arg)*);
+                let result = self(this, $($arg),*)?;
+                Ok(deno_core::serde_v8::to_v8(scope, result)?)
+            }
+        }
+
+        impl<F, Fut, R, $($arg,)*> RsTypedAsyncFunction<($($arg,)*)> for F
+        where
+            F: Fn(AsyncFunctionContext, $($arg),*) -> Fut + 'static,
+            Fut: std::future::Future<Output = Result<R, Error>> + 'static,
+            R: Serialize,
+            $($arg: DeserializeOwned,)*
+        {
+            const ARITY: usize = $arity;
+
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn call_v8<'s>(
+                &self,
+                scope: &mut v8::HandleScope<'s>,
+                context: AsyncFunctionContext,
+                name: &str,
+                args: &[v8::Local<'s, v8::Value>],
+            ) -> Result<TypedFunctionFuture, Error> {
+                convert_typed_args!(scope, name, args, Self::ARITY $(, $arg)*);
+                let future = self(context, $($arg),*);
+                Ok(Box::pin(async move { Ok(serde_json::to_value(future.await?)?) }))
+            }
+        }
+    };
+}
+
+impl_typed_function!(0);
+impl_typed_function!(1, A1);
+impl_typed_function!(2, A1, A2);
+impl_typed_function!(3, A1, A2, A3);
+impl_typed_function!(4, A1, A2, A3, A4);
+impl_typed_function!(5, A1, A2, A3, A4, A5);
+impl_typed_function!(6, A1, A2, A3, A4, A5, A6);
+impl_typed_function!(7, A1, A2, A3, A4, A5, A6, A7);
+impl_typed_function!(8, A1, A2, A3, A4, A5, A6, A7, A8);
+
+/// Determines how a value is defined on the global object
+/// Matches the property helpers in `rustyscript.js`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PropertyKind {
+    /// Writable, enumerable and configurable - the same as a plain assignment
+    #[default]
+    Writeable,
+
+    /// Writable and configurable, but hidden from enumeration
+    NonEnumerable,
+
+    /// Configurable, but cannot be reassigned, and hidden from enumeration
+    ReadOnly,
+}
+
+/// Determines what happens to a promise rejection that nothing handles
+/// See [InnerRuntimeOptions::unhandled_rejection_policy]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnhandledRejectionPolicy {
+    /// Report the rejection as a warning through the `log` crate, and otherwise ignore it
+    Log,
+
+    /// Silently ignore the rejection
+    Ignore,
+
+    /// Return the rejection as an error from the next call into the runtime
+    #[default]
+    FailNextCall,
+}
+
 /// Represents the set of options accepted by the runtime constructor
 pub struct InnerRuntimeOptions {
     /// A set of deno_core extensions to add to the runtime
@@ -50,6 +285,7 @@ pub struct InnerRuntimeOptions {
     pub default_entrypoint: Option<String>,
 
     /// Amount of time to run for before killing the thread
+    /// Synchronous JS that runs past this deadline is terminated, and the call returns [Error::Timeout]
     pub timeout: Duration,
 
     /// Optional cache provider for the module loader
@@ -66,9 +302,78 @@ pub struct InnerRuntimeOptions {
     /// See the rusty_v8 documentation for more information
     pub isolate_params: Option<v8::CreateParams>,
 
+    /// Optional maximum size of the heap, in bytes
+    /// Scripts exceeding it are terminated with [Error::HeapExhausted] instead of crashing the process
+    /// The runtime is then poisoned, and must be recreated - see [crate::Runtime::is_poisoned]
+    pub max_heap_size: Option<usize>,
+
+    /// Optional size of the stack scripts may use, in bytes, measured from the point on the creating thread's
+    /// stack where the runtime is created. Exceeding it returns [Error::StackOverflow].
+    ///
+    /// Unlike [V8Flags::stack_size], this only applies to this runtime. It must be smaller than the stack
+    /// the thread has left, and the runtime must be used from the thread that created it
+    pub max_stack_size: Option<usize>,
+
+    /// Maximum nesting of registered function calls, such as a function calling a JS callback that calls it again
+    /// Deeper calls fail with [Error::StackOverflow], listing the JS frames at that point, before the native
+    /// stack can run out. Defaults to 128
+    pub max_call_depth: usize,
+
     /// Optional shared array buffer store to use for the runtime
     /// Allows data-sharing between runtimes across threads
     pub shared_array_buffer_store: Option<deno_core::SharedArrayBufferStore>,
+
+    /// Optional callback for promise rejections that nothing handles
+    /// Receives the rejection as an [Error::JsError], including its stack
+    pub on_unhandled_rejection: Option<Box<dyn Fn(Error)>>,
+
+    /// What to do with unhandled promise rejections, after the callback has been called
+    /// By default, the rejection is returned as an error by the next call into the runtime
+    pub unhandled_rejection_policy: UnhandledRejectionPolicy,
+
+    /// Optional settings for running scripts reproducibly
+    /// Seeds all randomness from a single value, and drives time from a virtual clock
+    ///
+    /// Timers follow the virtual clock too, so they only fire when [crate::Runtime::advance_time] is called.
+    /// See [DeterministicOptions]
+    pub deterministic: Option<DeterministicOptions>,
+
+    /// Optional virtual clock, replacing the system clock for `Date`, `performance.now` and timers
+    /// Time only moves when the clock is changed, or [crate::Runtime::advance_time] is called
+    ///
+    /// Timers are only virtual when using the web stub, not the `web` feature
+    pub clock: Option<VirtualClock>,
+
+    /// Optional flags for the V8 engine
+    /// These are global to the process, so they must be set on the first runtime created,
+    /// and later runtimes must use the same flags, or none - see [V8Flags]
+    pub v8_flags: Option<V8Flags>,
+
+    /// Disallow generating code from strings, through `eval`, `new Function` or string-form timers
+    /// Such calls throw an `EvalError`, unless allowed by [InnerRuntimeOptions::code_generation_filter]
+    ///
+    /// Applies to the main realm and to every realm created with [crate::Runtime::create_realm]
+    pub disallow_code_generation: bool,
+
+    /// Optional callback that receives each string a script tries to compile while code generation is disallowed
+    /// Returning true allows that string to be compiled
+    pub code_generation_filter: Option<Box<dyn Fn(&str) -> bool>>,
+
+    /// Optional lockdown of the global environment, applied once extensions are initialized
+    /// Freezes the intrinsics, the extension globals and the global object, and can remove globals such as `Deno.core`
+    /// See [LockdownOptions]
+    pub lockdown: Option<LockdownOptions>,
+
+    /// Optional per-module grants for functions registered with the runtime
+    /// If set, modules can only call the functions they were granted - see [FunctionCapabilities]
+    pub function_capabilities: Option<FunctionCapabilities>,
+
+    /// Optional callback for calls denied by [InnerRuntimeOptions::function_capabilities]
+    pub on_capability_denied: Option<Box<dyn Fn(&CapabilityDenial)>>,
+
+    /// Optional sink that receives an event for each call to a registered function, module load,
+    /// network or file permission check, and storage access - see [AuditSink]
+    pub audit_sink: Option<Box<dyn AuditSink>>,
 }
 
 impl Default for InnerRuntimeOptions {
@@ -80,7 +385,21 @@ impl Default for InnerRuntimeOptions {
             module_cache: None,
             startup_snapshot: None,
             isolate_params: None,
+            max_heap_size: None,
+            max_stack_size: None,
+            max_call_depth: ext::rustyscript::DEFAULT_MAX_CALL_DEPTH,
             shared_array_buffer_store: None,
+            on_unhandled_rejection: None,
+            unhandled_rejection_policy: Default::default(),
+            deterministic: None,
+            clock: None,
+            v8_flags: None,
+            disallow_code_generation: false,
+            code_generation_filter: None,
+            lockdown: None,
+            function_capabilities: None,
+            on_capability_denied: None,
+            audit_sink: None,
 
             extension_options: Default::default(),
         }
@@ -97,11 +416,27 @@ pub struct InnerRuntime {
     pub module_loader: Rc<RustyLoader>,
     pub deno_runtime: JsRuntime,
     pub options: InnerRuntimeOptions,
+    pub watchdog: Watchdog,
+
+    /// Restores the global object to its state after initialization
+    reset_globals: v8::Global<v8::Function>,
+
+    /// The functions from the main realm that new realms are built from
+    realm_host: v8::Global<v8::Object>,
 }
 impl InnerRuntime {
-    pub fn new(options: InnerRuntimeOptions) -> Result<Self, Error> {
+    pub fn new(mut options: InnerRuntimeOptions) -> Result<Self, Error> {
         let loader = Rc::new(RustyLoader::new(options.module_cache));
 
+        // Deterministic runtimes seed crypto too, unless it was given its own seed
+        #[cfg(feature = "crypto")]
+        if let Some(deterministic) = &options.deterministic {
+            options
+                .extension_options
+                .crypto_seed
+                .get_or_insert(deterministic.seed);
+        }
+
         // If a snapshot is provided, do not reload ops
         let extensions = if options.startup_snapshot.is_some() {
             ext::all_snapshot_extensions(options.extensions, options.extension_options)
@@ -109,29 +444,210 @@ impl InnerRuntime {
             ext::all_extensions(options.extensions, options.extension_options)
         };
 
-        Ok(Self {
-            deno_runtime: JsRuntime::try_new(RuntimeOptions {
-                module_loader: Some(loader.clone()),
+        // Apply the heap limit on top of any user-supplied isolate parameters
+        let create_params = match options.max_heap_size {
+            Some(max_heap_size) => Some(
+                options
+                    .isolate_params
+                    .unwrap_or_default()
+                    .heap_limits(0, max_heap_size),
+            ),
+            None => options.isolate_params,
+        };
 
-                extension_transpiler: Some(Rc::new(|specifier, code| {
-                    transpile_extension(specifier, code)
-                })),
+        // Flags must be applied before the platform is started by the first runtime
+        if let Some(flags) = &options.v8_flags {
+            flags.apply()?;
+        }
+        v8_flags::platform_initialized();
 
-                source_map_getter: Some(loader.clone()),
-                create_params: options.isolate_params,
-                shared_array_buffer_store: options.shared_array_buffer_store,
+        let mut deno_runtime = JsRuntime::try_new(RuntimeOptions {
+            module_loader: Some(loader.clone()),
 
-                startup_snapshot: options.startup_snapshot,
-                extensions,
+            extension_transpiler: Some(Rc::new(|specifier, code| {
+                transpile_extension(specifier, code)
+            })),
 
-                ..Default::default()
-            })?,
+            source_map_getter: Some(loader.clone()),
+            get_error_class_fn: Some(&error::get_error_class),
+            create_params,
+            shared_array_buffer_store: options.shared_array_buffer_store,
+
+            startup_snapshot: options.startup_snapshot,
+            extensions,
+
+            ..Default::default()
+        })?;
+
+        // Used to interrupt synchronous JS that runs past the timeout
+        // Also kept in the isolate, so that rust functions calling back into JS can report why it was terminated
+        let watchdog = Watchdog::new(deno_runtime.v8_isolate().thread_safe_handle());
+        deno_runtime.v8_isolate().set_slot(watchdog.handle());
+
+        if let Some(size) = options.max_stack_size {
+            set_stack_limit(deno_runtime.v8_isolate(), size);
+        }
+
+        // Terminate the script instead of letting v8 abort the process
+        // The limit is raised so that v8 has room to unwind the terminated script
+        if options.max_heap_size.is_some() {
+            let watchdog = watchdog.handle();
+            deno_runtime.add_near_heap_limit_callback(move |current_limit, _| {
+                watchdog.terminate(TerminationReason::HeapExhausted);
+                current_limit * 2
+            });
+        }
+
+        // Tracks calls to registered rust functions
+        deno_runtime
+            .op_state()
+            .borrow_mut()
+            .put(CallStats::default());
+        deno_runtime
+            .op_state()
+            .borrow_mut()
+            .put(ext::rustyscript::CallDepth::new(options.max_call_depth));
+
+        // Used by the rustyscript extension to report unhandled promise rejections
+        deno_runtime
+            .op_state()
+            .borrow_mut()
+            .put(ext::rustyscript::UnhandledRejectionHandler {
+                callback: options.on_unhandled_rejection,
+                policy: options.unhandled_rejection_policy,
+            });
+
+        // Deterministic runtimes cannot use the system clock
+        if options.deterministic.is_some() && options.clock.is_none() {
+            options.clock = Some(VirtualClock::default());
+        }
+
+        // Replace the system clock
+        if let Some(clock) = &options.clock {
+            {
+                let state = deno_runtime.op_state();
+                let mut state = state.borrow_mut();
+                state.put(ClockState::new(clock.clone()));
+                state.put(VirtualTimers::default());
+            }
+
+            deno_runtime.execute_script(
+                "ext:rustyscript/clock.js",
+                include_str!("ext/rustyscript/clock.js"),
+            )?;
+        }
+
+        // Replace the sources of randomness
+        if let Some(deterministic) = &options.deterministic {
+            deno_runtime
+                .op_state()
+                .borrow_mut()
+                .put(DeterministicState::new(deterministic));
+            deno_runtime.execute_script(
+                "ext:rustyscript/deterministic.js",
+                include_str!("ext/rustyscript/deterministic.js"),
+            )?;
+        }
+
+        // Restrict which modules can call registered functions
+        if let Some(function_capabilities) = options.function_capabilities {
+            deno_runtime.op_state().borrow_mut().put(CapabilityState {
+                capabilities: function_capabilities,
+                on_denied: options.on_capability_denied,
+            });
+        }
+
+        // Record interactions with the host
+        if let Some(sink) = options.audit_sink {
+            let audit = Auditor::new(sink);
+            loader.set_audit(audit.clone());
+
+            #[cfg(feature = "web")]
+            deno_runtime
+                .op_state()
+                .borrow_mut()
+                .put(ext::web::Permissions::new(audit.clone()));
+            deno_runtime.op_state().borrow_mut().put(audit);
+
+            #[cfg(feature = "web")]
+            deno_runtime.execute_script(
+                "ext:init_web/audit_web.js",
+                include_str!("ext/web/audit_web.js"),
+            )?;
+
+            #[cfg(feature = "webstorage")]
+            deno_runtime.execute_script(
+                "ext:init_webstorage/audit_webstorage.js",
+                include_str!("ext/webstorage/audit_webstorage.js"),
+            )?;
+        }
 
+        // Stop scripts from compiling strings
+        if options.disallow_code_generation {
+            let policy = CodeGenerationPolicy::install(
+                &mut deno_runtime,
+                options.code_generation_filter.map(Rc::from),
+            );
+            deno_runtime.op_state().borrow_mut().put(policy);
+        }
+
+        let realm_host = RealmHandle::host(&mut deno_runtime)?;
+
+        // Must come last, so that the baseline includes everything set up above
+        let baseline = deno_runtime.execute_script(
+            "ext:rustyscript/baseline.js",
+            include_str!("ext/rustyscript/baseline.js"),
+        )?;
+        let (reset_globals, capture_globals) = {
+            let mut scope = deno_runtime.handle_scope();
+            let baseline = v8::Local::new(&mut scope, baseline);
+            let baseline = v8::Local::<v8::Array>::try_from(baseline)?;
+            let mut get_function = |index| -> Result<v8::Global<v8::Function>, Error> {
+                let value = baseline.get_index(&mut scope, index).ok_or_else(|| {
+                    Error::Runtime("Could not capture global baseline".to_string())
+                })?;
+                let value = v8::Local::<v8::Function>::try_from(value)?;
+                Ok(v8::Global::new(&mut scope, value))
+            };
+            (get_function(0)?, get_function(1)?)
+        };
+
+        // Freeze the environment, then capture the baseline again, without the hidden globals
+        if let Some(options) = &options.lockdown {
+            lockdown::lockdown(&mut deno_runtime, options)?;
+
+            let mut scope = deno_runtime.handle_scope();
+            let capture_globals = v8::Local::new(&mut scope, capture_globals);
+            let undefined = v8::undefined(&mut scope).into();
+
+            let mut scope = v8::TryCatch::new(&mut scope);
+            if capture_globals.call(&mut scope, undefined, &[]).is_none() {
+                let error = match scope.exception() {
+                    Some(e) => e.to_rust_string_lossy(&mut scope),
+                    None => "Unknown error".to_string(),
+                };
+                return Err(Error::Runtime(format!(
+                    "Could not capture global baseline: {error}"
+                )));
+            }
+        }
+
+        Ok(Self {
+            deno_runtime,
             module_loader: loader,
+            watchdog,
+            reset_globals,
+            realm_host,
 
             options: InnerRuntimeOptions {
                 timeout: options.timeout,
                 default_entrypoint: options.default_entrypoint,
+                unhandled_rejection_policy: options.unhandled_rejection_policy,
+                deterministic: options.deterministic,
+                clock: options.clock,
+                v8_flags: options.v8_flags,
+                disallow_code_generation: options.disallow_code_generation,
+                lockdown: options.lockdown,
                 ..Default::default()
             },
         })
@@ -142,6 +658,188 @@ impl InnerRuntime {
         &mut self.deno_runtime
     }
 
+    /// Return the runtime to the state it was in after initialization
+    /// - Globals created or replaced since then are removed or restored
+    /// - Pending timers are cancelled
+    /// - The registered entrypoint is dropped
+    /// - Modules loaded afterwards will be instantiated again, instead of reusing earlier instances
+    ///
+    /// Extensions, registered functions and the isolate itself are kept
+    ///
+    /// Pending async ops cannot be cancelled, and would resume the previous user's code
+    /// against the next user's globals, so the reset is refused until they settle
+    pub fn reset(&mut self) -> Result<(), Error> {
+        let filter = RuntimeActivityStatsFilter::default().with_ops();
+        let pending_ops: Vec<&str> = self
+            .deno_runtime
+            .runtime_activity_stats_factory()
+            .capture(&filter)
+            .dump()
+            .active
+            .iter()
+            .filter_map(|a| match a {
+                // Waits for queued callbacks on behalf of the runtime itself
+                RuntimeActivity::AsyncOp(_, _, "op_next_callback") => None,
+                RuntimeActivity::AsyncOp(_, _, name) => Some(*name),
+                _ => None,
+            })
+            .collect();
+        if !pending_ops.is_empty() {
+            return Err(Error::Runtime(format!(
+                "Cannot reset a runtime with pending async operations ({}); run the event loop until it is idle first",
+                pending_ops.join(", ")
+            )));
+        }
+
+        self.deno_runtime
+            .op_state()
+            .try_borrow_mut()?
+            .try_take::<v8::Global<v8::Function>>();
+
+        // Calls queued by async functions belong to the previous user of the runtime
+        if let Some(context) = self
+            .deno_runtime
+            .op_state()
+            .borrow()
+            .try_borrow::<AsyncFunctionContext>()
+        {
+            context.clear();
+        }
+
+        // Find the timers that need to be cancelled
+        let filter = RuntimeActivityStatsFilter::default().with_timers();
+        let timers: Vec<usize> = self
+            .deno_runtime
+            .runtime_activity_stats_factory()
+            .capture(&filter)
+            .dump()
+            .active
+            .iter()
+            .filter_map(|a| match a {
+                RuntimeActivity::Timer(id, ..) | RuntimeActivity::Interval(id, ..) => Some(*id),
+                _ => None,
+            })
+            .collect();
+        if let Some(timers) = self
+            .deno_runtime
+            .op_state()
+            .borrow_mut()
+            .try_borrow_mut::<VirtualTimers>()
+        {
+            timers.clear();
+        }
+
+        let mut scope = self.deno_runtime.handle_scope();
+        let reset_globals = v8::Local::new(&mut scope, &self.reset_globals);
+        let timers = deno_core::serde_v8::to_v8(&mut scope, timers)?;
+        let undefined = v8::undefined(&mut scope).into();
+
+        let mut scope = v8::TryCatch::new(&mut scope);
+        if reset_globals
+            .call(&mut scope, undefined, &[timers])
+            .is_none()
+        {
+            let error = match scope.exception() {
+                Some(e) => e.to_rust_string_lossy(&mut scope),
+                None => "Unknown error".to_string(),
+            };
+            return Err(Error::Runtime(format!("Could not reset runtime: {error}")));
+        }
+
+        self.module_loader.reset();
+        Ok(())
+    }
+
+    /// Create a new realm sharing this runtime's isolate
+    /// The new realm gets its own copies of the globals installed by extensions
+    pub fn create_realm(&mut self) -> Result<RealmHandle, Error> {
+        RealmHandle::new(&mut self.deno_runtime, &self.realm_host)
+    }
+
+    /// Run a full, blocking garbage collection
+    /// Precise if `--expose-gc` was applied, otherwise uses V8's low-memory collection
+    pub fn collect_garbage(&mut self) {
+        let isolate = self.deno_runtime.v8_isolate();
+        if v8_flags::expose_gc() {
+            isolate.request_garbage_collection_for_testing(v8::GarbageCollectionType::Full);
+        } else {
+            isolate.low_memory_notification();
+        }
+    }
+
+    /// Tell V8 that the system is low on memory, so that it frees as much as it can
+    pub fn notify_low_memory(&mut self) {
+        self.deno_runtime.v8_isolate().low_memory_notification();
+    }
+
+    /// Let V8 use up to `idle_time` for idle tasks, such as incremental garbage collection
+    /// Only has an effect if the platform was initialized with idle task support
+    pub fn idle_notification(&mut self, idle_time: Duration) {
+        let platform = v8::V8::get_current_platform();
+        v8::Platform::run_idle_tasks(
+            &platform,
+            self.deno_runtime.v8_isolate(),
+            idle_time.as_secs_f64(),
+        );
+    }
+
+    /// Collect a snapshot of the runtime's memory usage and activity
+    pub fn metrics(&mut self) -> RuntimeMetrics {
+        let mut heap = v8::HeapStatistics::default();
+        self.deno_runtime
+            .v8_isolate()
+            .get_heap_statistics(&mut heap);
+
+        // Count pending work in the event loop
+        let filter = RuntimeActivityStatsFilter::default()
+            .with_ops()
+            .with_timers();
+        let activity = self
+            .deno_runtime
+            .runtime_activity_stats_factory()
+            .capture(&filter)
+            .dump();
+        let pending_ops = activity
+            .active
+            .iter()
+            .filter(|a| matches!(a, RuntimeActivity::AsyncOp(..)))
+            .count();
+        let pending_timers = activity
+            .active
+            .iter()
+            .filter(|a| {
+                matches!(
+                    a,
+                    RuntimeActivity::Timer(..) | RuntimeActivity::Interval(..)
+                )
+            })
+            .count()
+            + self
+                .deno_runtime
+                .op_state()
+                .borrow()
+                .try_borrow::<VirtualTimers>()
+                .map_or(0, VirtualTimers::len);
+
+        let functions = self
+            .deno_runtime
+            .op_state()
+            .borrow()
+            .try_borrow::<CallStats>()
+            .map(CallStats::snapshot)
+            .unwrap_or_default();
+
+        RuntimeMetrics {
+            used_heap_size: heap.used_heap_size(),
+            total_heap_size: heap.total_heap_size(),
+            external_memory: heap.external_memory(),
+            loaded_modules: self.module_loader.loaded_module_count(),
+            functions,
+            pending_ops,
+            pending_timers,
+        }
+    }
+
     /// Remove and return a value from the state
     pub fn take<T>(&mut self) -> Option<T>
     where
@@ -189,6 +887,10 @@ impl InnerRuntime {
             .borrow_mut::<HashMap<String, Box<dyn RsAsyncFunction>>>()
             .insert(name.to_string(), Box::new(callback));
 
+        if let Some(stats) = state.try_borrow::<CallStats>() {
+            stats.register(name);
+        }
+
         Ok(())
     }
 
@@ -211,6 +913,152 @@ impl InnerRuntime {
             .borrow_mut::<HashMap<String, Box<dyn RsFunction>>>()
             .insert(name.to_string(), Box::new(callback));
 
+        if let Some(stats) = state.try_borrow::<CallStats>() {
+            stats.register(name);
+        }
+
+        Ok(())
+    }
+
+    /// Register a rust function with typed arguments
+    /// Arguments and the return value are converted directly with serde_v8
+    pub fn register_typed_function<F, Args>(&mut self, name: &str, callback: F) -> Result<(), Error>
+    where
+        F: RsTypedFunction<Args>,
+    {
+        let state = self.deno_runtime().op_state();
+        let mut state = state.try_borrow_mut()?;
+
+        if !state.has::<HashMap<String, TypedFunctionCallback>>() {
+            state.put(HashMap::<String, TypedFunctionCallback>::new());
+        }
+
+        // Insert the callback into the state, with its argument types erased
+        let callback: TypedFunctionCallback =
+            Rc::new(move |scope, name, args| callback.call_v8(scope, name, args));
+        state
+            .borrow_mut::<HashMap<String, TypedFunctionCallback>>()
+            .insert(name.to_string(), callback);
+
+        if let Some(stats) = state.try_borrow::<CallStats>() {
+            stats.register(name);
+        }
+
+        Ok(())
+    }
+
+    /// Group registered functions into a module importable as `host:<name>`
+    /// Each function must already be registered, and is exported under its registered name
+    pub fn register_host_module(&mut self, name: &str, functions: &[&str]) -> Result<(), Error> {
+        let state = self.deno_runtime().op_state();
+        let mut state = state.try_borrow_mut()?;
+        let is_registered = |function: &str, sync: bool| {
+            if sync {
+                state
+                    .try_borrow::<HashMap<String, Box<dyn RsFunction>>>()
+                    .is_some_and(|table| table.contains_key(function))
+                    || state
+                        .try_borrow::<HashMap<String, TypedFunctionCallback>>()
+                        .is_some_and(|table| table.contains_key(function))
+            } else {
+                state
+                    .try_borrow::<HashMap<String, Box<dyn RsAsyncFunction>>>()
+                    .is_some_and(|table| table.contains_key(function))
+                    || state
+                        .try_borrow::<HashMap<String, TypedAsyncFunctionCallback>>()
+                        .is_some_and(|table| table.contains_key(function))
+            }
+        };
+
+        let mut listed = Vec::with_capacity(functions.len());
+        for &function in functions {
+            let is_async = if is_registered(function, true) {
+                false
+            } else if is_registered(function, false) {
+                true
+            } else {
+                return Err(Error::ValueNotFound(function.to_string()));
+            };
+            listed.push((function.to_string(), is_async));
+        }
+
+        // Generate the module, exporting the functions built for it by the rustyscript extension
+        let mut code = format!(
+            "import {{ hostModule }} from 'ext:rustyscript/rustyscript.js';\nconst host = hostModule({});\n",
+            serde_json::to_string(name)?
+        );
+        for (i, &function) in functions.iter().enumerate() {
+            let function = serde_json::to_string(function)?;
+            code.push_str(&format!(
+                "const f{i} = host[{function}];\nexport {{ f{i} as {function} }};\n"
+            ));
+        }
+
+        if !state.has::<ext::rustyscript::HostModules>() {
+            state.put(ext::rustyscript::HostModules::default());
+        }
+        state
+            .borrow_mut::<ext::rustyscript::HostModules>()
+            .0
+            .insert(name.to_string(), listed);
+
+        self.module_loader.add_host_module(name, code);
+        Ok(())
+    }
+
+    /// Register a rust type as a class that scripts can construct
+    /// See [HostClass]
+    pub fn register_class<T: HostClass>(&mut self) -> Result<(), Error> {
+        let state = self.deno_runtime().op_state();
+        let mut state = state.try_borrow_mut()?;
+
+        if !state.has::<HostClasses>() {
+            state.put(HostClasses::new());
+        }
+        state
+            .borrow_mut::<HostClasses>()
+            .insert(T::NAME.to_string(), host_class::erase::<T>());
+
+        if !state.has::<HostClassGeneration>() {
+            state.put(HostClassGeneration::default());
+        }
+        let generation = state.borrow_mut::<HostClassGeneration>();
+        generation.0 = generation.0.wrapping_add(1);
+
+        Ok(())
+    }
+
+    /// Register an async rust function with typed arguments
+    /// Arguments are converted directly with serde_v8, and the function is given an [AsyncFunctionContext]
+    pub fn register_typed_async_function<F, Args>(
+        &mut self,
+        name: &str,
+        callback: F,
+    ) -> Result<(), Error>
+    where
+        F: RsTypedAsyncFunction<Args>,
+    {
+        let state = self.deno_runtime().op_state();
+        let mut state = state.try_borrow_mut()?;
+
+        if !state.has::<HashMap<String, TypedAsyncFunctionCallback>>() {
+            state.put(HashMap::<String, TypedAsyncFunctionCallback>::new());
+        }
+        if !state.has::<AsyncFunctionContext>() {
+            state.put(AsyncFunctionContext::default());
+        }
+
+        // Insert the callback into the state, with its argument types erased
+        let callback: TypedAsyncFunctionCallback =
+            Rc::new(move |scope, context, name, args| callback.call_v8(scope, context, name, args));
+        state
+            .borrow_mut::<HashMap<String, TypedAsyncFunctionCallback>>()
+            .insert(name.to_string(), callback);
+
+        if let Some(stats) = state.try_borrow::<CallStats>() {
+            stats.register(name);
+        }
+
         Ok(())
     }
 
@@ -219,6 +1067,62 @@ impl InnerRuntime {
         Ok(self.deno_runtime.run_event_loop(options).await?)
     }
 
+    /// Runs a single iteration of the JS event loop
+    /// Returns true if work remains in the event loop
+    pub async fn poll_event_loop_once(&mut self) -> Result<bool, Error> {
+        // Let tokio update its timers and IO before polling
+        tokio::task::yield_now().await;
+
+        let result = std::future::poll_fn(|cx| {
+            std::task::Poll::Ready(self.deno_runtime.poll_event_loop(cx, Default::default()))
+        })
+        .await;
+
+        match result {
+            std::task::Poll::Ready(result) => result.map(|_| false).map_err(Error::from),
+            std::task::Poll::Pending => Ok(true),
+        }
+    }
+
+    /// Runs the JS event loop for at most `duration`
+    /// Returns true if work remains in the event loop
+    pub async fn run_event_loop_for(&mut self, duration: Duration) -> Result<bool, Error> {
+        let event_loop = self.deno_runtime.run_event_loop(Default::default());
+        match tokio::time::timeout(duration, event_loop).await {
+            Ok(result) => result.map(|_| false).map_err(Error::from),
+            Err(_) => Ok(true),
+        }
+    }
+
+    /// Move the virtual clock forward by `duration`
+    /// Timers that come due are fired in order, with the clock set to their due time,
+    /// and the event loop is polled after each one
+    pub async fn advance_time(&mut self, duration: Duration) -> Result<(), Error> {
+        let clock = self.options.clock.clone().ok_or_else(|| {
+            Error::Runtime("advance_time requires RuntimeOptions::clock to be set".to_string())
+        })?;
+
+        let target = clock.now() + duration;
+        loop {
+            let next = self
+                .deno_runtime
+                .op_state()
+                .borrow_mut()
+                .try_borrow_mut::<VirtualTimers>()
+                .and_then(|timers| timers.pop_due(target));
+            let Some((due, callback)) = next else {
+                break;
+            };
+
+            clock.set(due.max(clock.now()));
+            self.call_function_by_ref(None, callback, &()).await?;
+            self.poll_event_loop_once().await?;
+        }
+
+        clock.set(target);
+        Ok(())
+    }
+
     /// Evaluate a piece of non-ECMAScript-module JavaScript code
     /// The expression is evaluated in the global context, so changes persist
     ///
@@ -233,15 +1137,81 @@ impl InnerRuntime {
     where
         T: DeserializeOwned,
     {
-        // Update source map cache
-        self.module_loader
-            .insert_source_map("", expr.to_string(), None);
+        self.eval_named("", expr)
+    }
+
+    /// Evaluate a piece of non-ECMAScript-module JavaScript code under the given file name
+    /// The name is used in stack traces and error messages
+    ///
+    /// # Arguments
+    /// * `name` - The file name to give the code
+    /// * `expr` - A string representing the JavaScript expression to evaluate
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the expression (`T`)
+    /// or an error (`Error`) if the expression cannot be evaluated or if the
+    /// result cannot be deserialized.
+    pub fn eval_named<T>(&mut self, name: &str, expr: &str) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let result = self.execute_named(name, expr.to_string(), expr)?;
+        self.decode_value(result)
+    }
 
-        let result = self.deno_runtime().execute_script("", expr.to_string())?;
+    /// Evaluate a piece of JavaScript code that may use `await`
+    /// The result is resolved using the event loop
+    ///
+    /// The code runs inside an async function, so declarations do not persist,
+    /// but changes to `globalThis` do
+    ///
+    /// # Arguments
+    /// * `name` - The file name to give the code
+    /// * `expr` - A string representing the JavaScript expression to evaluate
+    ///
+    /// # Returns
+    /// A `Result` containing the resolved value of the expression,
+    /// or an error (`Error`) if the expression cannot be evaluated
+    pub async fn eval_async(
+        &mut self,
+        name: &str,
+        expr: &str,
+    ) -> Result<v8::Global<v8::Value>, Error> {
+        // Try it as an expression first, so that its value is returned
+        // Statements only compile in the body of the function, so they fall back to that
+        let expression = expr.trim_end().trim_end_matches(';');
+        let mut wrapped = format!("(async () => ({expression}\n))()");
+        if !self.compiles(&wrapped) {
+            wrapped = format!("(async () => {{ {expr}\n}})()");
+        }
 
+        let promise = self.execute_named(name, wrapped, expr)?;
+        self.resolve_with_event_loop(promise).await
+    }
+
+    /// Returns true if the code compiles as a script, without running it
+    fn compiles(&mut self, code: &str) -> bool {
         let mut scope = self.deno_runtime.handle_scope();
-        let result = v8::Local::new(&mut scope, result);
-        Ok(from_v8(&mut scope, result)?)
+        let mut scope = v8::TryCatch::new(&mut scope);
+        let Some(source) = v8::String::new(&mut scope, code) else {
+            return false;
+        };
+        v8::Script::compile(&mut scope, source, None).is_some()
+    }
+
+    /// Execute a script under the given file name
+    /// `source` is the original code, used to generate error messages
+    fn execute_named(
+        &mut self,
+        name: &str,
+        code: String,
+        source: &str,
+    ) -> Result<v8::Global<v8::Value>, Error> {
+        // Update source map cache
+        self.module_loader
+            .insert_source_map(name, source.to_string(), None);
+
+        Ok(self.deno_runtime().execute_script(name.to_string(), code)?)
     }
 
     /// Attempt to get a value out of the global context (globalThis.name)
@@ -265,6 +1235,65 @@ impl InnerRuntime {
         }
     }
 
+    /// Define a value on the global context (globalThis.name)
+    /// The value is serialized directly into v8, and replaces any existing value
+    ///
+    /// # Arguments
+    /// * `name` - Name of the property to define
+    /// * `value` - The value to serialize
+    /// * `kind` - How the property is defined
+    pub fn set_global_value<T>(
+        &mut self,
+        name: &str,
+        value: &T,
+        kind: PropertyKind,
+    ) -> Result<(), Error>
+    where
+        T: Serialize,
+    {
+        let context = self.deno_runtime.main_context();
+        let mut scope = self.deno_runtime.handle_scope();
+        let global = context.open(&mut scope).global(&mut scope);
+
+        let key = name.to_v8_string(&mut scope)?;
+        let value = deno_core::serde_v8::to_v8(&mut scope, value)?;
+        let attributes = match kind {
+            PropertyKind::Writeable => v8::PropertyAttribute::NONE,
+            PropertyKind::NonEnumerable => v8::PropertyAttribute::DONT_ENUM,
+            PropertyKind::ReadOnly => {
+                v8::PropertyAttribute::DONT_ENUM | v8::PropertyAttribute::READ_ONLY
+            }
+        };
+
+        let mut scope = v8::TryCatch::new(&mut scope);
+        match global.define_own_property(&mut scope, key.into(), value, attributes) {
+            Some(true) => Ok(()),
+            _ => Err(Error::Runtime(format!(
+                "{name} could not be defined on the global object"
+            ))),
+        }
+    }
+
+    /// Remove a value from the global context (globalThis.name)
+    /// Removing a value that does not exist is not an error
+    ///
+    /// # Arguments
+    /// * `name` - Name of the property to remove
+    pub fn delete_global_value(&mut self, name: &str) -> Result<(), Error> {
+        let context = self.deno_runtime.main_context();
+        let mut scope = self.deno_runtime.handle_scope();
+        let global = context.open(&mut scope).global(&mut scope);
+
+        let key = name.to_v8_string(&mut scope)?;
+        let mut scope = v8::TryCatch::new(&mut scope);
+        match global.delete(&mut scope, key.into()) {
+            Some(true) => Ok(()),
+            _ => Err(Error::Runtime(format!(
+                "{name} could not be deleted from the global object"
+            ))),
+        }
+    }
+
     /// Attempt to get a value out of a module context (export ``...)
     ///
     /// # Arguments
@@ -362,12 +1391,31 @@ impl InnerRuntime {
         Ok(v8::Global::<v8::Function>::new(&mut scope, f))
     }
 
-    pub async fn call_function_by_ref(
+    pub async fn call_function_by_ref<A>(
         &mut self,
         module_context: Option<&ModuleHandle>,
         function: v8::Global<v8::Function>,
-        args: &FunctionArguments,
-    ) -> Result<v8::Global<v8::Value>, Error> {
+        args: &A,
+    ) -> Result<v8::Global<v8::Value>, Error>
+    where
+        A: IntoArgs + ?Sized,
+    {
+        self.call_function_by_ref_with_this(module_context, None, function, args)
+            .await
+    }
+
+    /// Call a function using an explicit value for `this`
+    /// If `this` is `None`, the module namespace is used, if provided
+    pub async fn call_function_by_ref_with_this<A>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        this: Option<v8::Global<v8::Value>>,
+        function: v8::Global<v8::Function>,
+        args: &A,
+    ) -> Result<v8::Global<v8::Value>, Error>
+    where
+        A: IntoArgs + ?Sized,
+    {
         // Namespace, if provided
         let module_namespace = if let Some(module_context) = module_context {
             Some(
@@ -383,9 +1431,11 @@ impl InnerRuntime {
 
         // Get the namespace
         // Module-level if supplied, none otherwise
-        let namespace: v8::Local<v8::Value> = match module_namespace {
-            Some(namespace) => v8::Local::<v8::Object>::new(&mut scope, namespace).into(),
-            None => {
+        // An explicit `this` takes precedence over the namespace
+        let namespace: v8::Local<v8::Value> = match (this, module_namespace) {
+            (Some(this), _) => v8::Local::<v8::Value>::new(&mut scope, this),
+            (None, Some(namespace)) => v8::Local::<v8::Object>::new(&mut scope, namespace).into(),
+            (None, None) => {
                 // Create a new object to use as the namespace if none is provided
                 //let obj: v8::Local<v8::Value> = v8::Object::new(&mut scope).into();
                 let obj: v8::Local<v8::Value> = v8::undefined(&mut scope).into();
@@ -396,11 +1446,7 @@ impl InnerRuntime {
         let function_instance = function.open(&mut scope);
 
         // Prep argument
-        let f_args: Result<Vec<v8::Local<v8::Value>>, deno_core::serde_v8::Error> = args
-            .iter()
-            .map(|f| deno_core::serde_v8::to_v8(&mut scope, f))
-            .collect();
-        let final_args = f_args?;
+        let final_args = args.to_v8_args(&mut scope)?;
 
         // Call the function
         let result = function_instance.call(&mut scope, namespace, &final_args);
@@ -410,6 +1456,15 @@ impl InnerRuntime {
                 Ok(value)
             }
             None if scope.has_caught() => {
+                // Stack overflows keep the frames that caused them
+                if let Some(exception) = scope.exception() {
+                    let error: Error =
+                        deno_core::error::JsError::from_v8_exception(&mut scope, exception).into();
+                    if matches!(error, Error::StackOverflow(_)) {
+                        return Err(error);
+                    }
+                }
+
                 let e = match scope.message() {
                     Some(e) => e,
                     None => return Err(Error::Runtime("Unknown error".to_string())),
@@ -494,12 +1549,16 @@ impl InnerRuntime {
 
         let mut module_handle_stub = Default::default();
 
+        // Imports left over from a load that failed are not counted
+        self.module_loader.discard_pending_modules();
+
         // Get additional modules first
         for side_module in side_modules {
             let module_specifier = side_module.filename().to_module_specifier()?;
+            let module_specifier = self.module_loader.versioned(module_specifier);
             let (code, sourcemap) =
                 transpiler::transpile(&module_specifier, side_module.contents())?;
-            let fast_code = deno_core::FastString::from(code.clone());
+            let fast_code = deno_core::FastString::from(versioned_code(&module_specifier, &code));
 
             let s_modid = self
                 .deno_runtime
@@ -512,20 +1571,23 @@ impl InnerRuntime {
                 code,
                 sourcemap.map(|s| s.to_vec()),
             );
+            self.module_loader.record_module(&module_specifier);
 
             let result = self.deno_runtime.mod_evaluate(s_modid);
             self.deno_runtime
                 .run_event_loop(PollEventLoopOptions::default())
                 .await?;
             result.await?;
+            self.module_loader.track_module(&module_specifier);
             module_handle_stub = ModuleHandle::new(side_module, s_modid, None);
         }
 
         // Load main module
         if let Some(module) = main_module {
             let module_specifier = module.filename().to_module_specifier()?;
+            let module_specifier = self.module_loader.versioned(module_specifier);
             let (code, sourcemap) = transpiler::transpile(&module_specifier, module.contents())?;
-            let fast_code = deno_core::FastString::from(code.clone());
+            let fast_code = deno_core::FastString::from(versioned_code(&module_specifier, &code));
 
             let module_id = self
                 .deno_runtime
@@ -538,6 +1600,7 @@ impl InnerRuntime {
                 code,
                 sourcemap.map(|s| s.to_vec()),
             );
+            self.module_loader.record_module(&module_specifier);
 
             // Finish execution
             let result = self.deno_runtime.mod_evaluate(module_id);
@@ -548,6 +1611,7 @@ impl InnerRuntime {
                 })
                 .await?;
             result.await?;
+            self.module_loader.track_module(&module_specifier);
             module_handle_stub = ModuleHandle::new(module, module_id, None);
         }
 
@@ -563,6 +1627,29 @@ impl InnerRuntime {
     }
 }
 
+extern "C" {
+    /// `v8::Isolate::SetStackLimit`, which rusty_v8 does not expose
+    #[cfg_attr(
+        target_env = "msvc",
+        link_name = "?SetStackLimit@Isolate@v8@@QEAAX_K@Z"
+    )]
+    #[cfg_attr(
+        not(target_env = "msvc"),
+        link_name = "_ZN2v87Isolate13SetStackLimitEm"
+    )]
+    fn v8_isolate_set_stack_limit(isolate: *mut v8::Isolate, stack_limit: usize);
+}
+
+/// Lets scripts on the isolate use `size` bytes of stack below the current position on this thread's stack
+fn set_stack_limit(isolate: &mut v8::Isolate, size: usize) {
+    let position = 0u8;
+    let limit = std::ptr::addr_of!(position) as usize;
+    let limit = limit.saturating_sub(size);
+
+    // SAFETY: the isolate is alive, and v8 only compares the limit against the stack pointer
+    unsafe { v8_isolate_set_stack_limit(isolate, limit) };
+}
+
 #[cfg(test)]
 mod test_inner_runtime {
     use serde::Deserialize;
@@ -120,6 +120,21 @@
 //! # }
 //! ```
 //!
+//! The preferred way to reach them is to group them into a module with [Runtime::register_host_module].
+//! Importing a name the module does not export then fails before any code runs, instead of when it is called:
+//! ```rust
+//! use rustyscript::{ Runtime, Module };
+//!
+//! # fn main() -> Result<(), rustyscript::Error> {
+//! let module = Module::new("test.js", " import { foo } from 'host:utils'; foo(); ");
+//! let mut runtime = Runtime::new(Default::default())?;
+//! runtime.register_typed_function("foo", || Ok(()))?;
+//! runtime.register_host_module("utils", &["foo"])?;
+//! runtime.load_module(&module)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
 //! ----
 //!
 //! Asynchronous JS can be called in 2 ways;
@@ -259,16 +274,29 @@ pub mod cache_provider;
 pub mod error;
 pub mod js_value;
 
+mod audit;
+mod call_options;
+mod callbacks;
+mod capabilities;
+mod clock;
+mod code_generation;
+mod deterministic;
 mod ext;
+mod host_class;
 mod inner_runtime;
+mod lockdown;
+mod metrics;
 mod module;
 mod module_handle;
 mod module_loader;
 mod module_wrapper;
+mod realm;
 mod runtime;
 mod traits;
 mod transpiler;
 mod utilities;
+mod v8_flags;
+mod watchdog;
 
 #[cfg(feature = "worker")]
 pub mod worker;
@@ -286,13 +314,29 @@ pub use ext::web::WebOptions;
 pub use ext::ExtensionOptions;
 
 // Expose some important stuff from us
+pub use audit::{AuditEvent, AuditEventKind, AuditSink};
+pub use call_options::{CallOptions, EventLoopPolicy};
+pub use callbacks::{AsyncFunctionContext, FunctionContext, WithContext};
+pub use capabilities::{CapabilityDenial, FunctionCapabilities};
+pub use clock::VirtualClock;
+pub use deterministic::DeterministicOptions;
 pub use error::Error;
-pub use inner_runtime::{FunctionArguments, RsAsyncFunction, RsFunction};
+pub use host_class::{ClassBuilder, HostClass, RsMethod};
+pub use inner_runtime::{
+    FunctionArguments, PropertyKind, RsAsyncFunction, RsFunction, RsTypedAsyncFunction,
+    RsTypedFunction, UnhandledRejectionPolicy,
+};
+pub use lockdown::LockdownOptions;
+pub use metrics::{FunctionMetrics, RuntimeMetrics};
 pub use module::{Module, StaticModule};
 pub use module_handle::ModuleHandle;
 pub use module_wrapper::ModuleWrapper;
+pub use realm::{RealmHandle, RealmModuleHandle};
 pub use runtime::{Runtime, RuntimeOptions, Undefined};
+pub use traits::IntoArgs;
 pub use utilities::{evaluate, import, init_platform, resolve_path, validate};
+pub use v8_flags::V8Flags;
+pub use watchdog::InterruptHandle;
 
 #[cfg(test)]
 mod test {
@@ -0,0 +1,95 @@
+//! Freezes the global environment once extensions are initialized
+//!
+//! See [crate::RuntimeOptions::lockdown]
+use crate::{traits::ToV8String, Error};
+use deno_core::{serde_json, serde_v8, v8, JsRuntime};
+
+/// Options for locking down a runtime's global environment
+///
+/// Once extensions are initialized, the ECMAScript intrinsics (`Array.prototype`, `JSON`, ...),
+/// the globals added by extensions (`rustyscript`, `Deno`, `console`, `URL.prototype`, ...),
+/// and the global object itself are frozen, so that one script cannot tamper with the environment seen by another.
+///
+/// Use [LockdownOptions::hidden_globals] to keep scripts away from globals entirely,
+/// or [LockdownOptions::unfrozen_globals] to leave some of them open to patching.
+/// Values returned by getters, such as `localStorage`, are not frozen.
+///
+/// Assigning to properties such as `name`, `message` or `toString` on objects that inherit them
+/// still works, by defining the property on the object itself.
+///
+/// Since the global object is frozen, top-level `var` and function declarations in scripts will fail,
+/// as will [crate::Runtime::set_global_value]; modules, and `let` or `const` declarations are unaffected.
+/// Realms created with [crate::Runtime::create_realm] have their own, unfrozen intrinsics.
+///
+/// ```rust
+/// use rustyscript::{ LockdownOptions, Runtime, RuntimeOptions, Error };
+///
+/// # fn main() -> Result<(), Error> {
+/// let mut runtime = Runtime::new(RuntimeOptions {
+///     lockdown: Some(LockdownOptions {
+///         hidden_globals: vec!["Deno.core".to_string()],
+///         ..Default::default()
+///     }),
+///     ..Default::default()
+/// })?;
+///
+/// let frozen: bool = runtime.eval("Object.isFrozen(Array.prototype)")?;
+/// assert!(frozen);
+///
+/// let hidden: bool = runtime.eval("Deno.core === undefined")?;
+/// assert!(hidden);
+///
+/// let frozen: bool = runtime.eval("Object.isFrozen(Deno)")?;
+/// assert!(frozen);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LockdownOptions {
+    /// Globals to remove before freezing, as paths such as `Deno.core`
+    pub hidden_globals: Vec<String>,
+
+    /// If set, only these globals are kept, along with the ECMAScript built-ins
+    /// Any other global added by extensions, such as `Deno` or `rustyscript`, is removed
+    pub allowed_globals: Option<Vec<String>>,
+
+    /// Globals left unfrozen, such as `Deno`, for code that still needs to patch them
+    /// Objects only reachable through these globals are not frozen either
+    pub unfrozen_globals: Vec<String>,
+}
+
+/// Stored in the OpState of locked down runtimes, so that classes built later are frozen as well
+pub(crate) struct LockedDown;
+
+/// Remove hidden globals, then freeze the remaining globals and the global object
+pub(crate) fn lockdown(runtime: &mut JsRuntime, options: &LockdownOptions) -> Result<(), Error> {
+    runtime.op_state().borrow_mut().put(LockedDown);
+
+    let config = serde_json::json!({
+        "hidden": options.hidden_globals,
+        "allowed": options.allowed_globals,
+        "unfrozen": options.unfrozen_globals,
+        "intrinsics": intrinsic_globals(runtime)?,
+    });
+
+    let code = format!(
+        "{}({config})",
+        include_str!("ext/rustyscript/lockdown.js").trim_end()
+    );
+    runtime.execute_script("ext:rustyscript/lockdown.js", code)?;
+    Ok(())
+}
+
+/// Returns the names of the globals defined by ECMAScript, by listing the globals of a fresh context
+fn intrinsic_globals(runtime: &mut JsRuntime) -> Result<Vec<String>, Error> {
+    let mut scope = runtime.handle_scope();
+    let context = v8::Context::new(&mut scope);
+    let mut scope = v8::ContextScope::new(&mut scope, context);
+
+    let source = "Object.getOwnPropertyNames(globalThis)".to_v8_string(&mut scope)?;
+    let names = v8::Script::compile(&mut scope, source, None)
+        .and_then(|script| script.run(&mut scope))
+        .ok_or_else(|| Error::Runtime("Could not list ECMAScript globals".to_string()))?;
+
+    Ok(serde_v8::from_v8(&mut scope, names)?)
+}
@@ -0,0 +1,63 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Duration};
+
+/// A snapshot of the state of a [crate::Runtime], returned by [crate::Runtime::metrics]
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeMetrics {
+    /// Bytes of the v8 heap currently in use
+    pub used_heap_size: usize,
+
+    /// Total bytes allocated for the v8 heap
+    pub total_heap_size: usize,
+
+    /// Bytes of memory allocated outside of the heap, but retained by JS objects
+    pub external_memory: usize,
+
+    /// Number of modules loaded successfully into the runtime, including imported modules
+    pub loaded_modules: usize,
+
+    /// Usage of each function registered with [crate::Runtime::register_function]
+    /// or [crate::Runtime::register_async_function], by name
+    pub functions: HashMap<String, FunctionMetrics>,
+
+    /// Number of async ops waiting to complete in the event loop
+    pub pending_ops: usize,
+
+    /// Number of timers and intervals waiting to fire in the event loop
+    pub pending_timers: usize,
+}
+
+/// Usage of a single registered rust function
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FunctionMetrics {
+    /// Number of times the function was called from JS
+    pub calls: u64,
+
+    /// Total time spent in the function across all calls
+    /// For async functions this includes the time until the returned future resolved
+    pub total_time: Duration,
+}
+
+/// Records calls to registered functions
+/// Stored in the OpState, and shared with pending async calls
+#[derive(Clone, Default)]
+pub(crate) struct CallStats(Rc<RefCell<HashMap<String, FunctionMetrics>>>);
+
+impl CallStats {
+    /// Start tracking a function, so that it is listed before its first call
+    pub fn register(&self, name: &str) {
+        self.0.borrow_mut().entry(name.to_string()).or_default();
+    }
+
+    /// Record a single call to a function
+    pub fn record(&self, name: &str, elapsed: Duration) {
+        let mut table = self.0.borrow_mut();
+        let metrics = table.entry(name.to_string()).or_default();
+        metrics.calls += 1;
+        metrics.total_time += elapsed;
+    }
+
+    /// Get a copy of the metrics for all functions called so far
+    pub fn snapshot(&self) -> HashMap<String, FunctionMetrics> {
+        self.0.borrow().clone()
+    }
+}
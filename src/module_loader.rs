@@ -1,4 +1,5 @@
 use crate::{
+    audit::{AuditEventKind, Auditor},
     cache_provider::{ClonableSource, ModuleCacheProvider},
     transpiler,
 };
@@ -9,7 +10,7 @@ use deno_core::{
     SourceMapGetter,
 };
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::{HashMap, HashSet},
     rc::Rc,
 };
@@ -17,12 +18,77 @@ use std::{
 /// Stores the source code and source map for loaded modules
 type SourceMapCache = HashMap<String, (String, Option<Vec<u8>>)>;
 
+/// Prefix of the fragment that tags the specifiers of modules loaded after a reset
+const VERSION_FRAGMENT: &str = "rustyscript-";
+
+/// Path of the `ext:` specifiers that `host:` modules are loaded under
+/// As extension modules, they can import the helper that builds their exports, which scripts cannot
+const HOST_MODULE_PATH: &str = "rustyscript/host/";
+
+/// Removes the generation tag added to a module specifier after a reset,
+/// and reports `host:` modules under the specifier they are imported as
+pub(crate) fn unversioned_specifier(specifier: &ModuleSpecifier) -> ModuleSpecifier {
+    let mut specifier = specifier.clone();
+    if specifier
+        .fragment()
+        .is_some_and(|f| f.starts_with(VERSION_FRAGMENT))
+    {
+        specifier.set_fragment(None);
+    }
+
+    match host_module_name(&specifier).map(|name| ModuleSpecifier::parse(&format!("host:{name}"))) {
+        Some(Ok(host_specifier)) => host_specifier,
+        _ => specifier,
+    }
+}
+
+/// Returns the name of the `host:` module loaded under `specifier`, if it is one
+fn host_module_name(specifier: &ModuleSpecifier) -> Option<&str> {
+    if specifier.scheme() == "ext" {
+        specifier.path().strip_prefix(HOST_MODULE_PATH)
+    } else {
+        None
+    }
+}
+
+/// Removes the generation tags from any module specifiers mentioned in `text`
+/// Used for error messages and stack traces, so reloaded modules are reported under their own specifier
+pub(crate) fn unversioned(text: &str) -> String {
+    let tag = format!("#{VERSION_FRAGMENT}");
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find(&tag) {
+        result.push_str(&rest[..index]);
+        rest = rest[index + tag.len()..].trim_start_matches(|c: char| c.is_ascii_digit());
+    }
+    result.push_str(rest);
+    result.replace(&format!("ext:{HOST_MODULE_PATH}"), "host:")
+}
+
+/// Returns the code to evaluate for a JS module registered under `specifier`
+/// v8 sets `import.meta.url` to the versioned specifier, so it is restored on the first line,
+/// leaving the line numbers in errors and source maps unchanged
+pub(crate) fn versioned_code(specifier: &ModuleSpecifier, code: &str) -> String {
+    let unversioned = unversioned_specifier(specifier);
+    if unversioned == *specifier {
+        return code.to_string();
+    }
+
+    let url = deno_core::serde_json::Value::String(unversioned.to_string());
+    format!("import.meta.url = {url}; {code}")
+}
+
 /// Internal implementation ModuleLoader
 #[derive(Clone)]
 struct InnerRustyLoader {
     cache_provider: Rc<Option<Box<dyn ModuleCacheProvider>>>,
     fs_whlist: Rc<RefCell<HashSet<String>>>,
     source_map_cache: Rc<RefCell<SourceMapCache>>,
+    loaded_modules: Rc<RefCell<HashSet<String>>>,
+    pending_modules: Rc<RefCell<HashSet<String>>>,
+    generation: Rc<Cell<usize>>,
+    audit: Rc<RefCell<Option<Auditor>>>,
+    host_modules: Rc<RefCell<HashMap<String, String>>>,
 }
 
 impl InnerRustyLoader {
@@ -33,9 +99,54 @@ impl InnerRustyLoader {
             cache_provider: Rc::new(cache_provider),
             fs_whlist: Rc::new(RefCell::new(HashSet::new())),
             source_map_cache: Rc::new(RefCell::new(SourceMapCache::new())),
+            loaded_modules: Rc::new(RefCell::new(HashSet::new())),
+            pending_modules: Rc::new(RefCell::new(HashSet::new())),
+            generation: Rc::new(Cell::new(0)),
+            audit: Rc::new(RefCell::new(None)),
+            host_modules: Rc::new(RefCell::new(HashMap::new())),
         }
     }
 
+    /// Records a module load with the audit sink, if there is one
+    fn record_load(&self, specifier: &ModuleSpecifier, referrer: Option<&ModuleSpecifier>) {
+        if let Some(audit) = self.audit.borrow().as_ref() {
+            audit.record(
+                referrer.map(|r| unversioned_specifier(r).to_string()),
+                AuditEventKind::ModuleLoad {
+                    specifier: unversioned_specifier(specifier).to_string(),
+                },
+            );
+        }
+    }
+
+    /// Tags a module specifier with the current generation
+    /// v8 never unloads modules, so this makes modules loaded after a reset distinct from earlier ones
+    fn versioned(&self, mut specifier: ModuleSpecifier) -> ModuleSpecifier {
+        let generation = self.generation.get();
+        if generation > 0 && matches!(specifier.scheme(), "file" | "http" | "https" | "host") {
+            specifier.set_fragment(Some(&format!("{VERSION_FRAGMENT}{generation}")));
+        }
+        specifier
+    }
+
+    /// Forget all loaded modules, so that they are loaded again on the next import
+    fn reset(&self) {
+        self.generation.set(self.generation.get() + 1);
+        self.loaded_modules.borrow_mut().clear();
+        self.pending_modules.borrow_mut().clear();
+    }
+
+    /// Records a module fetched by the loader
+    /// Static imports only count as loaded once the module loaded from rust that imports them succeeds
+    fn track_module(&self, specifier: &str, is_dyn_import: bool) {
+        let modules = if is_dyn_import {
+            &self.loaded_modules
+        } else {
+            &self.pending_modules
+        };
+        modules.borrow_mut().insert(specifier.to_string());
+    }
+
     /// Adds a module specifier to the whitelist
     /// This allows the module to be loaded from the filesystem
     /// If they are included from rust first
@@ -50,10 +161,59 @@ impl InnerRustyLoader {
         self.fs_whlist.borrow_mut().contains(specifier)
     }
 
+    /// Returns the generated source of a `host:` module
+    /// Modules are found by name, so that versioned specifiers load the current source
+    fn load_host_module(
+        &self,
+        module_specifier: &ModuleSpecifier,
+        is_dyn_import: bool,
+    ) -> Result<ModuleSource, anyhow::Error> {
+        let host_modules = self.host_modules.borrow();
+        let code = host_module_name(module_specifier)
+            .and_then(|name| host_modules.get(name))
+            .ok_or_else(|| {
+                anyhow!(
+                    "no host module named {}",
+                    unversioned_specifier(module_specifier)
+                )
+            })?;
+
+        self.track_module(module_specifier.as_str(), is_dyn_import);
+        Ok(ModuleSource::new(
+            ModuleType::JavaScript,
+            ModuleSourceCode::String(code.clone().into()),
+            module_specifier,
+            None,
+        ))
+    }
+
+    /// Returns a copy of `source` to be loaded under the versioned `specifier`
+    fn versioned_source(specifier: &ModuleSpecifier, source: &ModuleSource) -> ModuleSource {
+        if unversioned_specifier(specifier) == *specifier {
+            return source.clone(specifier);
+        }
+
+        let code = match &source.code {
+            ModuleSourceCode::String(s) if source.module_type == ModuleType::JavaScript => {
+                s.to_string()
+            }
+            _ => return source.clone(specifier),
+        };
+
+        ModuleSource::new(
+            ModuleType::JavaScript,
+            ModuleSourceCode::String(versioned_code(specifier, &code).into()),
+            specifier,
+            None,
+        )
+    }
+
     /// Loads a module's source code from the cache or from the provided handler
+    /// The cache is keyed by the unversioned specifier, so that modules are reused across resets
     async fn load<F, Fut>(
         &self,
         module_specifier: ModuleSpecifier,
+        is_dyn_import: bool,
         handler: F,
     ) -> Result<ModuleSource, deno_core::error::AnyError>
     where
@@ -63,8 +223,12 @@ impl InnerRustyLoader {
         // Check if the module is in the cache first
         let cache_provider = self.cache_provider.clone();
         let cache_provider = cache_provider.as_ref().as_ref().map(|p| p.as_ref());
-        match cache_provider.map(|p| p.get(&module_specifier)) {
-            Some(Some(source)) => Ok(source),
+        let cache_key = unversioned_specifier(&module_specifier);
+        match cache_provider.map(|p| p.get(&cache_key)) {
+            Some(Some(source)) => {
+                self.track_module(module_specifier.as_str(), is_dyn_import);
+                Ok(Self::versioned_source(&module_specifier, &source))
+            }
             _ => {
                 // Not in the cache, load the module from the handler
 
@@ -83,7 +247,7 @@ impl InnerRustyLoader {
                 let source = ModuleSource::new(
                     module_type,
                     ModuleSourceCode::String(tcode.into()),
-                    &module_specifier,
+                    &cache_key,
                     None,
                 );
 
@@ -96,9 +260,10 @@ impl InnerRustyLoader {
                 // Cache the source if a cache provider is available
                 // Could speed up loads on some future runtime
                 if let Some(p) = cache_provider {
-                    p.set(&module_specifier, source.clone(&module_specifier));
+                    p.set(&cache_key, source.clone(&cache_key));
                 }
-                Ok(source)
+                self.track_module(module_specifier.as_str(), is_dyn_import);
+                Ok(Self::versioned_source(&module_specifier, &source))
             }
         }
     }
@@ -150,6 +315,19 @@ impl ModuleLoader for RustyLoader {
                 }
             }
 
+            // Registered host modules, loaded as extension modules
+            "host" => {
+                if !self.inner.host_modules.borrow().contains_key(url.path()) {
+                    return Err(anyhow!("no host module named {specifier}"));
+                }
+
+                let versioned = self.inner.versioned(url);
+                let mut host_specifier =
+                    ModuleSpecifier::parse(&format!("ext:{HOST_MODULE_PATH}{}", versioned.path()))?;
+                host_specifier.set_fragment(versioned.fragment());
+                return Ok(host_specifier);
+            }
+
             _ if specifier.starts_with("ext:") => {
                 // Extension import - allow
             }
@@ -161,17 +339,19 @@ impl ModuleLoader for RustyLoader {
             }
         }
 
-        Ok(url)
+        Ok(self.inner.versioned(url))
     }
 
     /// Load a module by it's name
     fn load(
         &self,
         module_specifier: &ModuleSpecifier,
-        _maybe_referrer: Option<&ModuleSpecifier>,
-        _is_dyn_import: bool,
+        maybe_referrer: Option<&ModuleSpecifier>,
+        is_dyn_import: bool,
         _requested_module_type: deno_core::RequestedModuleType,
     ) -> deno_core::ModuleLoadResponse {
+        self.inner.record_load(module_specifier, maybe_referrer);
+
         let inner = self.inner.clone();
         let module_specifier = module_specifier.clone();
         // We check permissions first
@@ -181,7 +361,7 @@ impl ModuleLoader for RustyLoader {
             "https" | "http" => ModuleLoadResponse::Async(
                 async move {
                     inner
-                        .load(module_specifier, |specifier| async move {
+                        .load(module_specifier, is_dyn_import, |specifier| async move {
                             let response = reqwest::get(specifier).await?;
                             Ok(response.text().await?)
                         })
@@ -194,7 +374,7 @@ impl ModuleLoader for RustyLoader {
             "file" => ModuleLoadResponse::Async(
                 async move {
                     inner
-                        .load(module_specifier, |specifier| async move {
+                        .load(module_specifier, is_dyn_import, |specifier| async move {
                             let path = specifier
                                 .to_file_path()
                                 .map_err(|_| anyhow!("`{specifier}` is not a valid file URL."))?;
@@ -205,6 +385,11 @@ impl ModuleLoader for RustyLoader {
                 .boxed_local(),
             ),
 
+            // Registered host modules
+            "ext" if host_module_name(&module_specifier).is_some() => {
+                ModuleLoadResponse::Sync(inner.load_host_module(&module_specifier, is_dyn_import))
+            }
+
             // Unknown scheme - deny
             _ => ModuleLoadResponse::Sync(Err(anyhow!(
                 "{} imports are not allowed here: {}",
@@ -239,6 +424,55 @@ impl RustyLoader {
         self.inner.whitelist_has(specifier)
     }
 
+    /// Records a module loaded directly from rust, rather than through the loader
+    pub fn record_module(&self, specifier: &ModuleSpecifier) {
+        self.inner.record_load(specifier, None);
+    }
+
+    /// Counts a module loaded directly from rust as loaded, once it has been evaluated successfully,
+    /// along with the modules it imported
+    pub fn track_module(&self, specifier: &ModuleSpecifier) {
+        let mut loaded = self.inner.loaded_modules.borrow_mut();
+        loaded.insert(specifier.to_string());
+        loaded.extend(self.inner.pending_modules.borrow_mut().drain());
+    }
+
+    /// Forget modules imported by a module loaded from rust that failed
+    pub fn discard_pending_modules(&self) {
+        self.inner.pending_modules.borrow_mut().clear();
+    }
+
+    /// Adds a `host:` module, importable as `host:<name>`
+    /// Replaces any earlier module with the same name - modules already imported keep the old source until a reset
+    pub(crate) fn add_host_module(&self, name: &str, code: String) {
+        self.inner
+            .host_modules
+            .borrow_mut()
+            .insert(name.to_string(), code);
+    }
+
+    /// Sets the audit sink that module loads are recorded with
+    pub(crate) fn set_audit(&self, audit: Auditor) {
+        *self.inner.audit.borrow_mut() = Some(audit);
+    }
+
+    /// Returns the number of distinct modules loaded successfully so far
+    pub fn loaded_module_count(&self) -> usize {
+        self.inner.loaded_modules.borrow().len()
+    }
+
+    /// Returns the specifier under which a module loaded from rust should be registered
+    /// Modules loaded after a reset are given a distinct specifier
+    pub fn versioned(&self, specifier: ModuleSpecifier) -> ModuleSpecifier {
+        self.inner.versioned(specifier)
+    }
+
+    /// Forget all loaded modules, so that they are loaded again on the next import
+    /// Used by [crate::Runtime::reset]
+    pub fn reset(&self) {
+        self.inner.reset();
+    }
+
     /// Inserts a source map into the source map cache
     /// This is used to provide source maps for loaded modules
     /// for error message generation
@@ -323,4 +557,47 @@ mod test {
             _ => panic!("Unexpected response"),
         }
     }
+
+    #[tokio::test]
+    async fn test_versioned_cache() {
+        let cache_provider = MemoryModuleCacheProvider::default();
+        let specifier = "file:///test.js".to_module_specifier().unwrap();
+        let source = ModuleSource::new(
+            ModuleType::JavaScript,
+            ModuleSourceCode::String("export const url = import.meta.url;".to_string().into()),
+            &specifier,
+            None,
+        );
+        cache_provider.set(&specifier, source.clone(&specifier));
+
+        // Modules loaded after a reset still hit the cache, under their unversioned specifier
+        let loader = RustyLoader::new(Some(Box::new(cache_provider)));
+        loader.reset();
+        let versioned = loader.versioned(specifier.clone());
+        assert_ne!(versioned, specifier);
+        assert_eq!(unversioned_specifier(&versioned), specifier);
+
+        let response = loader.load(
+            &versioned,
+            None,
+            false,
+            deno_core::RequestedModuleType::None,
+        );
+        let ModuleLoadResponse::Async(future) = response else {
+            panic!("Unexpected response");
+        };
+        let source = future.await.expect("Expected to get source");
+        let ModuleSourceCode::String(code) = source.code else {
+            panic!("Unexpected source code type");
+        };
+        assert_eq!(
+            code.to_string(),
+            "import.meta.url = \"file:///test.js\"; export const url = import.meta.url;"
+        );
+
+        assert_eq!(
+            unversioned("at file:///test.js#rustyscript-12:1:5"),
+            "at file:///test.js:1:5"
+        );
+    }
 }
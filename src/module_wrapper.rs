@@ -1,4 +1,6 @@
-use crate::{js_value::Function, Error, Module, ModuleHandle, Runtime, RuntimeOptions};
+use crate::{
+    js_value::Function, CallOptions, Error, Module, ModuleHandle, Runtime, RuntimeOptions,
+};
 use deno_core::{serde_json, v8::GetPropertyNamesArgs};
 
 /// A wrapper type representing a runtime instance loaded with a single module
@@ -133,6 +135,30 @@ impl ModuleWrapper {
             .call_function(Some(&self.module_context), name, args)
     }
 
+    /// Calls a function in the module with the given name and arguments and deserializes the result.
+    /// Uses the given per-call settings, such as a timeout or an explicit `this`
+    /// See [Runtime::call_function_with]
+    ///
+    /// # Arguments
+    /// * `name` - The name of the function to call.
+    /// * `args` - The arguments to pass to the function.
+    /// * `options` - Settings for this call.
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of type `T` on success or an `Error` on failure.
+    pub fn call_with<T>(
+        &mut self,
+        name: &str,
+        args: &[serde_json::Value],
+        options: &CallOptions,
+    ) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.runtime
+            .call_function_with(Some(&self.module_context), name, args, options)
+    }
+
     /// Calls a function in the module with the given name and arguments and deserializes the result.
     /// See [Runtime::call_function_async]
     ///
@@ -286,6 +312,31 @@ mod test_runtime {
         assert_eq!(4, value);
     }
 
+    #[test]
+    fn test_call_with() {
+        let module = Module::new(
+            "test.js",
+            "
+            export function func() { return this.value; }
+        ",
+        );
+
+        let mut module = ModuleWrapper::new_from_module(&module, RuntimeOptions::default())
+            .expect("Could not create wrapper");
+        let this = module
+            .get_runtime()
+            .eval("({ value: 5 })")
+            .expect("Could not create object");
+        let options = CallOptions {
+            this: Some(this),
+            ..Default::default()
+        };
+        let value: usize = module
+            .call_with("func", json_args!(), &options)
+            .expect("Could not call function");
+        assert_eq!(5, value);
+    }
+
     #[test]
     fn test_get() {
         let module = Module::new(
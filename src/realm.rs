@@ -0,0 +1,825 @@
+//! Provides realms - separate global environments sharing a single isolate
+//!
+//! Each realm is a distinct v8 context, with its own global object, security token and set of modules.
+//! Every realm gets its own copies of the extension globals - see [crate::Runtime::create_realm].
+//! These call into the main realm through wrapper functions that belong to the realm,
+//! and copy every value passed across, so that a realm never holds an object from another realm.
+//!
+//! Modules loaded into a realm are not managed by deno_core, so they can only import
+//! modules previously loaded into the same realm, and dynamic imports are not supported.
+use crate::{
+    code_generation::CodeGenerationPolicy,
+    traits::{ToModuleSpecifier, ToV8String},
+    transpiler, Error, FunctionArguments, Module,
+};
+use deno_core::{serde_v8, v8, JsRuntime};
+use std::{cell::RefCell, rc::Rc};
+
+/// Modules loaded into a realm, by specifier
+/// Stored in a slot on the realm's context, for use by the resolve callback
+#[derive(Clone, Default)]
+struct RealmModules {
+    /// Modules that were evaluated successfully, and can be imported
+    loaded: Rc<RefCell<Vec<(String, v8::Global<v8::Module>)>>>,
+
+    /// The module being linked, which its imports are resolved relative to
+    linking: Rc<RefCell<Option<(String, v8::Global<v8::Module>)>>>,
+}
+
+/// A handle to a realm created with [crate::Runtime::create_realm]
+///
+/// Realms have their own global object, so code running in one cannot see the globals of another.
+/// The handle can be cloned, and remains valid for the lifetime of the runtime that created it.
+#[derive(Clone)]
+pub struct RealmHandle {
+    context: v8::Global<v8::Context>,
+    modules: RealmModules,
+    take_entrypoint: v8::Global<v8::Function>,
+}
+
+/// A handle to a module loaded into a realm with [crate::Runtime::load_module_in_realm]
+pub struct RealmModuleHandle {
+    module: Module,
+    specifier: String,
+    compiled: v8::Global<v8::Module>,
+    namespace: v8::Global<v8::Object>,
+    entrypoint: Option<v8::Global<v8::Function>>,
+}
+
+impl RealmModuleHandle {
+    /// Returns the module this handle refers to
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+}
+
+impl RealmHandle {
+    /// Collect the functions from the main realm that new realms are built from
+    /// Must be called once the extensions are initialized
+    pub(crate) fn host(runtime: &mut JsRuntime) -> Result<v8::Global<v8::Object>, Error> {
+        let host = runtime.execute_script(
+            "ext:rustyscript/realm_host.js",
+            include_str!("ext/rustyscript/realm_host.js"),
+        )?;
+        let mut scope = runtime.handle_scope();
+        let host = v8::Local::new(&mut scope, host);
+        let host = v8::Local::<v8::Object>::try_from(host)?;
+        Ok(v8::Global::new(&mut scope, host))
+    }
+
+    /// Create a new realm on the given runtime's isolate
+    /// `host` is the object returned by [RealmHandle::host]
+    ///
+    /// The context keeps the unique security token v8 gives it,
+    /// so that it cannot access the global object of any other realm
+    pub(crate) fn new(
+        runtime: &mut JsRuntime,
+        host: &v8::Global<v8::Object>,
+    ) -> Result<Self, Error> {
+        let modules = RealmModules::default();
+        let restrict_code_generation = runtime.op_state().borrow().has::<CodeGenerationPolicy>();
+
+        let mut scope = runtime.handle_scope();
+        let host = v8::Local::new(&mut scope, host);
+        let context = v8::Context::new(&mut scope);
+        if restrict_code_generation {
+            CodeGenerationPolicy::apply(&context);
+        }
+        let mut scope = v8::ContextScope::new(&mut scope, context);
+        context.set_slot(&mut scope, modules.clone());
+        let mut scope = v8::TryCatch::new(&mut scope);
+
+        // Wrap each host function in the new realm
+        let realm_host = v8::Object::new(&mut scope);
+        let keys = host
+            .get_own_property_names(&mut scope, Default::default())
+            .ok_or_else(|| Error::Runtime("Could not read the realm host".to_string()))?;
+        for i in 0..keys.length() {
+            let wrapped = keys.get_index(&mut scope, i).and_then(|key| {
+                let value = host.get(&mut scope, key)?;
+                let value = copy_value(&mut scope, value)?;
+                realm_host.set(&mut scope, key, value)
+            });
+            if wrapped.is_none() {
+                return Err(caught_error(&mut scope));
+            }
+        }
+
+        let source = include_str!("ext/rustyscript/realm.js").to_v8_string(&mut scope)?;
+        let origin = script_origin(&mut scope, "ext:rustyscript/realm.js", false)?;
+        let take_entrypoint = v8::Script::compile(&mut scope, source, Some(&origin))
+            .and_then(|script| script.run(&mut scope))
+            .and_then(|install| v8::Local::<v8::Function>::try_from(install).ok())
+            .and_then(|install| {
+                let undefined = v8::undefined(&mut scope).into();
+                install.call(&mut scope, undefined, &[realm_host.into()])
+            })
+            .and_then(|take| v8::Local::<v8::Function>::try_from(take).ok());
+        let Some(take_entrypoint) = take_entrypoint else {
+            return Err(caught_error(&mut scope));
+        };
+
+        Ok(Self {
+            context: v8::Global::new(&mut scope, context),
+            modules,
+            take_entrypoint: v8::Global::new(&mut scope, take_entrypoint),
+        })
+    }
+
+    /// Evaluate a script in the realm's global context
+    pub(crate) fn eval(
+        &self,
+        runtime: &mut JsRuntime,
+        name: &str,
+        code: &str,
+    ) -> Result<v8::Global<v8::Value>, Error> {
+        let mut scope = runtime.handle_scope();
+        let context = v8::Local::new(&mut scope, &self.context);
+        let mut scope = v8::ContextScope::new(&mut scope, context);
+        let mut scope = v8::TryCatch::new(&mut scope);
+
+        let source = code.to_v8_string(&mut scope)?;
+        let origin = script_origin(&mut scope, name, false)?;
+        let result = v8::Script::compile(&mut scope, source, Some(&origin))
+            .and_then(|script| script.run(&mut scope));
+
+        match result {
+            Some(value) => Ok(v8::Global::new(&mut scope, value)),
+            None => Err(caught_error(&mut scope)),
+        }
+    }
+
+    /// Compile, link and evaluate a module in the realm
+    /// Returns a handle to the module, and the promise returned by its evaluation
+    ///
+    /// The module can only be imported once [RealmHandle::add_module] is called,
+    /// after the evaluation has completed successfully
+    pub(crate) fn load_module(
+        &self,
+        runtime: &mut JsRuntime,
+        module: &Module,
+    ) -> Result<(RealmModuleHandle, v8::Global<v8::Value>), Error> {
+        let specifier = module.filename().to_module_specifier()?;
+        let (code, _) = transpiler::transpile(&specifier, module.contents())?;
+
+        let mut scope = runtime.handle_scope();
+        let context = v8::Local::new(&mut scope, &self.context);
+        let mut scope = v8::ContextScope::new(&mut scope, context);
+        let mut scope = v8::TryCatch::new(&mut scope);
+
+        let source = code.to_v8_string(&mut scope)?;
+        let origin = script_origin(&mut scope, specifier.as_str(), true)?;
+        let mut source = v8::script_compiler::Source::new(source, Some(&origin));
+        let Some(compiled) = v8::script_compiler::compile_module(&mut scope, &mut source) else {
+            return Err(caught_error(&mut scope));
+        };
+
+        let compiled_global = v8::Global::new(&mut scope, compiled);
+        *self.modules.linking.borrow_mut() = Some((specifier.to_string(), compiled_global.clone()));
+        let instantiated = compiled.instantiate_module(&mut scope, resolve_callback);
+        self.modules.linking.borrow_mut().take();
+        if instantiated.is_none() {
+            return Err(caught_error(&mut scope));
+        }
+
+        let Some(result) = compiled.evaluate(&mut scope) else {
+            return Err(caught_error(&mut scope));
+        };
+
+        let namespace = v8::Local::<v8::Object>::try_from(compiled.get_module_namespace())?;
+        let handle = RealmModuleHandle {
+            module: module.clone(),
+            specifier: specifier.to_string(),
+            compiled: compiled_global,
+            namespace: v8::Global::new(&mut scope, namespace),
+            entrypoint: None,
+        };
+
+        Ok((handle, v8::Global::new(&mut scope, result)))
+    }
+
+    /// Takes the function registered by the last call to `rustyscript.register_entrypoint` in the realm
+    pub(crate) fn take_entrypoint(
+        &self,
+        runtime: &mut JsRuntime,
+    ) -> Result<Option<v8::Global<v8::Function>>, Error> {
+        let mut scope = runtime.handle_scope();
+        let context = v8::Local::new(&mut scope, &self.context);
+        let mut scope = v8::ContextScope::new(&mut scope, context);
+        let mut scope = v8::TryCatch::new(&mut scope);
+
+        let take = v8::Local::new(&mut scope, &self.take_entrypoint);
+        let undefined = v8::undefined(&mut scope).into();
+        let Some(entrypoint) = take.call(&mut scope, undefined, &[]) else {
+            return Err(caught_error(&mut scope));
+        };
+
+        Ok(v8::Local::<v8::Function>::try_from(entrypoint)
+            .ok()
+            .map(|f| v8::Global::new(&mut scope, f)))
+    }
+
+    /// Adds a module that was evaluated successfully to the realm, so that later modules can import it
+    ///
+    /// Its entrypoint is the function it registered, if any, then its default export,
+    /// then the export named `default_entrypoint`
+    pub(crate) fn add_module(
+        &self,
+        runtime: &mut JsRuntime,
+        handle: &mut RealmModuleHandle,
+        registered: Option<v8::Global<v8::Function>>,
+        default_entrypoint: Option<&str>,
+    ) -> Result<(), Error> {
+        self.modules
+            .loaded
+            .borrow_mut()
+            .push((handle.specifier.clone(), handle.compiled.clone()));
+
+        let mut scope = runtime.handle_scope();
+        let context = v8::Local::new(&mut scope, &self.context);
+        let mut scope = v8::ContextScope::new(&mut scope, context);
+
+        let namespace = v8::Local::new(&mut scope, &handle.namespace);
+        let mut entrypoint = registered;
+        for name in std::iter::once("default").chain(default_entrypoint) {
+            if entrypoint.is_some() {
+                break;
+            }
+
+            let key = name.to_v8_string(&mut scope)?;
+            entrypoint = namespace
+                .get(&mut scope, key.into())
+                .and_then(|value| v8::Local::<v8::Function>::try_from(value).ok())
+                .map(|f| v8::Global::new(&mut scope, f));
+        }
+
+        handle.entrypoint = entrypoint;
+        Ok(())
+    }
+
+    /// Call the entrypoint of a module loaded into the realm
+    pub(crate) fn call_entrypoint(
+        &self,
+        runtime: &mut JsRuntime,
+        module_context: &RealmModuleHandle,
+        args: &FunctionArguments,
+    ) -> Result<v8::Global<v8::Value>, Error> {
+        let entrypoint = module_context
+            .entrypoint
+            .as_ref()
+            .ok_or_else(|| Error::MissingEntrypoint(module_context.module.clone()))?;
+
+        let mut scope = runtime.handle_scope();
+        let context = v8::Local::new(&mut scope, &self.context);
+        let mut scope = v8::ContextScope::new(&mut scope, context);
+        let mut scope = v8::TryCatch::new(&mut scope);
+
+        let function = v8::Local::new(&mut scope, entrypoint);
+        let this = v8::undefined(&mut scope).into();
+        call_with_args(&mut scope, function, this, args)
+    }
+
+    /// Call a function in the realm by name
+    /// The module's exports are searched first if provided, then the realm's global object
+    pub(crate) fn call_function(
+        &self,
+        runtime: &mut JsRuntime,
+        module_context: Option<&RealmModuleHandle>,
+        name: &str,
+        args: &FunctionArguments,
+    ) -> Result<v8::Global<v8::Value>, Error> {
+        let mut scope = runtime.handle_scope();
+        let context = v8::Local::new(&mut scope, &self.context);
+        let mut scope = v8::ContextScope::new(&mut scope, context);
+        let mut scope = v8::TryCatch::new(&mut scope);
+
+        let key = name.to_v8_string(&mut scope)?;
+        let namespace = module_context.map(|m| v8::Local::new(&mut scope, &m.namespace));
+
+        // Search the module first, then the global object
+        let mut value = namespace.and_then(|namespace| namespace.get(&mut scope, key.into()));
+        if value.map_or(true, |v| v.is_undefined()) {
+            let global = context.global(&mut scope);
+            value = global.get(&mut scope, key.into());
+        }
+
+        let function = value
+            .and_then(|v| v8::Local::<v8::Function>::try_from(v).ok())
+            .ok_or_else(|| Error::ValueNotCallable(name.to_string()))?;
+
+        let this = match namespace {
+            Some(namespace) => namespace.into(),
+            None => v8::undefined(&mut scope).into(),
+        };
+        call_with_args(&mut scope, function, this, args)
+    }
+}
+
+/// Call a function with arguments converted from JSON values
+fn call_with_args<'s>(
+    scope: &mut v8::TryCatch<v8::HandleScope<'s>>,
+    function: v8::Local<'s, v8::Function>,
+    this: v8::Local<'s, v8::Value>,
+    args: &FunctionArguments,
+) -> Result<v8::Global<v8::Value>, Error> {
+    let args = args
+        .iter()
+        .map(|arg| serde_v8::to_v8(scope, arg))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match function.call(scope, this, &args) {
+        Some(value) => Ok(v8::Global::new(scope, value)),
+        None => Err(caught_error(scope)),
+    }
+}
+
+/// Copy a value into the current context of `scope`
+///
+/// Functions are replaced by a wrapper belonging to the current context, which calls the original
+/// in its own context, and copies its arguments, return value and exceptions across.
+/// Promises are replaced by a promise that settles with a copy of the original's result,
+/// errors by a new error with the same type, name and message, typed arrays by a `Uint8Array`
+/// with a copy of their bytes, array buffers by a copy, and other objects by a JSON copy.
+///
+/// Returns None if an exception was thrown
+fn copy_value<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    value: v8::Local<'s, v8::Value>,
+) -> Option<v8::Local<'s, v8::Value>> {
+    if let Ok(function) = v8::Local::<v8::Function>::try_from(value) {
+        let wrapper = v8::Function::builder(forward_call)
+            .data(function.into())
+            .build(scope)?;
+        return Some(wrapper.into());
+    }
+
+    if let Ok(promise) = v8::Local::<v8::Promise>::try_from(value) {
+        let resolver = v8::PromiseResolver::new(scope)?;
+        let on_fulfilled = v8::Function::builder(forward_fulfilled)
+            .data(resolver.into())
+            .build(scope)?;
+        let on_rejected = v8::Function::builder(forward_rejected)
+            .data(resolver.into())
+            .build(scope)?;
+        promise.then2(scope, on_fulfilled, on_rejected)?;
+        return Some(resolver.get_promise(scope).into());
+    }
+
+    if let Ok(view) = v8::Local::<v8::ArrayBufferView>::try_from(value) {
+        let mut bytes = vec![0; view.byte_length()];
+        view.copy_contents(&mut bytes);
+        return copy_bytes(scope, bytes, true);
+    }
+
+    if let Ok(buffer) = v8::Local::<v8::ArrayBuffer>::try_from(value) {
+        let length = buffer.byte_length();
+        let view = v8::Uint8Array::new(scope, buffer, 0, length)?;
+        let mut bytes = vec![0; length];
+        view.copy_contents(&mut bytes);
+        return copy_bytes(scope, bytes, false);
+    }
+
+    if value.is_native_error() {
+        let error = value.to_object(scope)?;
+        let message_key = v8::String::new(scope, "message")?;
+        let message = error.get(scope, message_key.into())?.to_string(scope)?;
+        let name_key = v8::String::new(scope, "name")?;
+        let name = error.get(scope, name_key.into())?.to_string(scope)?;
+
+        let copy = match name.to_rust_string_lossy(scope).as_str() {
+            "TypeError" => v8::Exception::type_error(scope, message),
+            "RangeError" => v8::Exception::range_error(scope, message),
+            "SyntaxError" => v8::Exception::syntax_error(scope, message),
+            "ReferenceError" => v8::Exception::reference_error(scope, message),
+            "Error" => v8::Exception::error(scope, message),
+            _ => {
+                let copy = v8::Exception::error(scope, message);
+                copy.to_object(scope)?
+                    .set(scope, name_key.into(), name.into())?;
+                copy
+            }
+        };
+        return Some(copy);
+    }
+
+    if value.is_object() {
+        return match serde_v8::from_v8::<serde_json::Value>(scope, value) {
+            Ok(json) => serde_v8::to_v8(scope, json).ok(),
+            Err(_) => value.to_string(scope).map(Into::into),
+        };
+    }
+
+    Some(value)
+}
+
+/// Copy bytes into a new `ArrayBuffer` in the current context, viewed as a `Uint8Array` if `as_view` is set
+fn copy_bytes<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    bytes: Vec<u8>,
+    as_view: bool,
+) -> Option<v8::Local<'s, v8::Value>> {
+    let length = bytes.len();
+    let store = v8::ArrayBuffer::new_backing_store_from_vec(bytes).make_shared();
+    let buffer = v8::ArrayBuffer::with_backing_store(scope, &store);
+    if as_view {
+        v8::Uint8Array::new(scope, buffer, 0, length).map(Into::into)
+    } else {
+        Some(buffer.into())
+    }
+}
+
+/// Calls the function wrapped by [copy_value] in the context it was created in
+fn forward_call(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let Ok(function) = v8::Local::<v8::Function>::try_from(args.data()) else {
+        return;
+    };
+    let Some(context) = function.get_creation_context(scope) else {
+        return;
+    };
+    let arguments: Vec<_> = (0..args.length()).map(|i| args.get(i)).collect();
+
+    let result = {
+        let scope = &mut v8::ContextScope::new(scope, context);
+        let scope = &mut v8::TryCatch::new(scope);
+        let undefined = v8::undefined(scope).into();
+        let result = arguments
+            .into_iter()
+            .map(|arg| copy_value(scope, arg))
+            .collect::<Option<Vec<_>>>()
+            .and_then(|arguments| function.call(scope, undefined, &arguments));
+        result.ok_or_else(|| scope.exception())
+    };
+
+    match result {
+        Ok(value) => {
+            if let Some(value) = copy_value(scope, value) {
+                rv.set(value);
+            }
+        }
+
+        Err(Some(exception)) => {
+            if let Some(exception) = copy_value(scope, exception) {
+                scope.throw_exception(exception);
+            }
+        }
+
+        // Execution was terminated
+        Err(None) => {}
+    }
+}
+
+/// Resolves the promise created by [copy_value] with a copy of the original's value
+fn forward_fulfilled(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    // SAFETY: the data is always the resolver given by copy_value
+    let resolver = unsafe { v8::Local::<v8::PromiseResolver>::cast(args.data()) };
+    if let Some(value) = copy_value(scope, args.get(0)) {
+        resolver.resolve(scope, value);
+    }
+}
+
+/// Rejects the promise created by [copy_value] with a copy of the original's reason
+fn forward_rejected(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    // SAFETY: the data is always the resolver given by copy_value
+    let resolver = unsafe { v8::Local::<v8::PromiseResolver>::cast(args.data()) };
+    if let Some(reason) = copy_value(scope, args.get(0)) {
+        resolver.reject(scope, reason);
+    }
+}
+
+/// Build the origin used to name scripts and modules in stack traces
+fn script_origin<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    name: &str,
+    is_module: bool,
+) -> Result<v8::ScriptOrigin<'s>, Error> {
+    let name = name.to_v8_string(scope)?;
+    let source_map_url = v8::undefined(scope).into();
+    Ok(v8::ScriptOrigin::new(
+        scope,
+        name.into(),
+        0,
+        0,
+        false,
+        0,
+        source_map_url,
+        false,
+        false,
+        is_module,
+    ))
+}
+
+/// Convert the exception caught by a TryCatch into an error
+fn caught_error(scope: &mut v8::TryCatch<v8::HandleScope>) -> Error {
+    match scope.exception() {
+        Some(exception) => deno_core::error::JsError::from_v8_exception(scope, exception).into(),
+        None => Error::Runtime("execution terminated".to_string()),
+    }
+}
+
+/// Resolves imports between modules loaded into the same realm
+fn resolve_callback<'s>(
+    context: v8::Local<'s, v8::Context>,
+    specifier: v8::Local<'s, v8::String>,
+    _import_attributes: v8::Local<'s, v8::FixedArray>,
+    referrer: v8::Local<'s, v8::Module>,
+) -> Option<v8::Local<'s, v8::Module>> {
+    // SAFETY: v8 calls this from within the context being linked
+    let scope = &mut unsafe { v8::CallbackScope::new(context) };
+    let modules = context.get_slot::<RealmModules>(scope)?.clone();
+    let linking = modules.linking.borrow();
+    let loaded = modules.loaded.borrow();
+    let specifier = specifier.to_rust_string_lossy(scope);
+
+    // Imports are relative to the importing module, found by identity since identity hashes can collide
+    let referrer = linking
+        .iter()
+        .chain(loaded.iter())
+        .find(|(_, module)| v8::Local::new(scope, module) == referrer)
+        .map(|(name, _)| name.as_str())
+        .unwrap_or(".");
+
+    let resolved = deno_core::resolve_import(&specifier, referrer)
+        .map(|url| url.to_string())
+        .unwrap_or(specifier.clone());
+    match loaded.iter().find(|(name, _)| *name == resolved) {
+        Some((_, module)) => Some(v8::Local::new(scope, module)),
+        None => {
+            let message = format!("{specifier} has not been loaded into this realm");
+            let message = v8::String::new(scope, &message)?;
+            let exception = v8::Exception::error(scope, message);
+            scope.throw_exception(exception);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{json_args, Error, Module, Runtime, Undefined};
+
+    #[test]
+    fn test_realm_isolation() {
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        let realm_a = runtime.create_realm().expect("Could not create realm");
+        let realm_b = runtime.create_realm().expect("Could not create realm");
+
+        runtime
+            .eval_in_realm::<usize>(&realm_a, "globalThis.secret = 5")
+            .expect("Could not eval");
+        let visible: bool = runtime
+            .eval_in_realm(&realm_b, "'secret' in globalThis")
+            .expect("Could not eval");
+        assert!(!visible);
+        let visible: bool = runtime
+            .eval("'secret' in globalThis")
+            .expect("Could not eval");
+        assert!(!visible);
+
+        // Extension globals are available in every realm
+        let installed: bool = runtime
+            .eval_in_realm(&realm_b, "typeof rustyscript.functions === 'object'")
+            .expect("Could not eval");
+        assert!(installed);
+    }
+
+    #[test]
+    fn test_realm_extension_globals() {
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        let realm = runtime.create_realm().expect("Could not create realm");
+
+        #[cfg(feature = "url")]
+        {
+            let url: Vec<String> = runtime
+                .eval_in_realm(
+                    &realm,
+                    "
+                    const url = new URL('../b?x=1#top', 'https://example.com/a/c');
+                    url.searchParams.append('y', '2');
+                    url.hash = '';
+                    [url.href, url.pathname, url.searchParams.get('x'), String(url instanceof URL)]
+                ",
+                )
+                .expect("Could not eval");
+            assert_eq!(
+                vec!["https://example.com/b?x=1&y=2", "/b", "1", "true"],
+                url
+            );
+
+            let invalid: bool = runtime
+                .eval_in_realm(
+                    &realm,
+                    "try { new URL('not a url'); false } catch (e) { e instanceof TypeError }",
+                )
+                .expect("Could not eval");
+            assert!(invalid);
+        }
+
+        #[cfg(feature = "crypto")]
+        {
+            let random: bool = runtime
+                .eval_in_realm(
+                    &realm,
+                    "
+                    const array = crypto.getRandomValues(new Uint32Array(4));
+                    array instanceof Uint32Array && crypto.randomUUID().length === 36
+                ",
+                )
+                .expect("Could not eval");
+            assert!(random);
+        }
+
+        #[cfg(any(feature = "web", feature = "web_stub"))]
+        {
+            let error: Vec<String> = runtime
+                .eval_in_realm(
+                    &realm,
+                    "
+                    const e = new DOMException('oops', 'AbortError');
+                    [e.name, e.message, String(e.code), String(e instanceof Error)]
+                ",
+                )
+                .expect("Could not eval");
+            assert_eq!(vec!["AbortError", "oops", "20", "true"], error);
+
+            let immediate: bool = runtime
+                .eval_in_realm(&realm, "typeof setImmediate === 'function'")
+                .expect("Could not eval");
+            assert!(immediate);
+        }
+
+        #[cfg(feature = "web")]
+        {
+            let text: Vec<String> = runtime
+                .eval_in_realm(
+                    &realm,
+                    "
+                    const bytes = new TextEncoder().encode('héllo');
+                    const into = new Uint8Array(2);
+                    const { read, written } = new TextEncoder().encodeInto('héllo', into);
+                    [
+                        new TextDecoder().decode(bytes),
+                        String(bytes instanceof Uint8Array),
+                        `${read}:${written}`,
+                        atob(btoa('hi')),
+                    ]
+                ",
+                )
+                .expect("Could not eval");
+            assert_eq!(vec!["héllo", "true", "1:1", "hi"], text);
+
+            let error: bool = runtime
+                .eval_in_realm(
+                    &realm,
+                    "try { atob('*'); false } catch (e) { e instanceof DOMException && e.name === 'InvalidCharacterError' }",
+                )
+                .expect("Could not eval");
+            assert!(error);
+        }
+
+        // Modules can register an entrypoint
+        let module = Module::new(
+            "entry.js",
+            "rustyscript.register_entrypoint((a, b) => a + b);",
+        );
+        let handle = runtime
+            .load_module_in_realm(&realm, &module)
+            .expect("Could not load module");
+        let value: usize = runtime
+            .call_entrypoint_in_realm(&realm, &handle, json_args!(1, 2))
+            .expect("Could not call entrypoint");
+        assert_eq!(3, value);
+    }
+
+    #[test]
+    fn test_realm_escape() {
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        runtime
+            .register_function("echo", |args| Ok(args[0].clone()))
+            .expect("Could not register function");
+        runtime
+            .eval::<usize>("globalThis.secret = 5")
+            .expect("Could not eval");
+        let realm = runtime.create_realm().expect("Could not create realm");
+
+        // The extension globals belong to the realm, so their constructors cannot reach the main realm
+        let escaped: Vec<bool> = runtime
+            .eval_in_realm(
+                &realm,
+                "
+                [
+                    setTimeout,
+                    console.log,
+                    rustyscript.bail,
+                    rustyscript.functions.echo,
+                    rustyscript.async_functions.echo,
+                ].map(f => 'secret' in f.constructor('return globalThis')())
+            ",
+            )
+            .expect("Could not eval");
+        assert_eq!(vec![false; 5], escaped);
+
+        // Values are copied in both directions
+        let escaped: bool = runtime
+            .eval_in_realm(
+                &realm,
+                "
+                const value = rustyscript.functions.echo({ inner: {} });
+                'secret' in value.inner.constructor.constructor('return globalThis')()
+            ",
+            )
+            .expect("Could not eval");
+        assert!(!escaped);
+
+        // Replacing the realm's globals does not affect the main realm
+        runtime
+            .eval_in_realm::<Undefined>(&realm, "console.log = null; console.warn = null")
+            .expect("Could not eval");
+        let intact: bool = runtime
+            .eval("typeof console.log === 'function' && typeof console.warn === 'function'")
+            .expect("Could not eval");
+        assert!(intact);
+
+        // Exceptions thrown by the main realm are copied too
+        let escaped: bool = runtime
+            .eval_in_realm(
+                &realm,
+                "
+                try {
+                    rustyscript.functions.missing();
+                    false
+                } catch (e) {
+                    'secret' in e.constructor.constructor('return globalThis')()
+                }
+            ",
+            )
+            .expect("Could not eval");
+        assert!(!escaped);
+
+        let e = runtime
+            .eval_in_realm_named::<Undefined>(&realm, "plugin.js", "1;\nthrow new Error('oops')")
+            .expect_err("Did not throw");
+        match e {
+            Error::JsError(e) => {
+                assert!(e.stack.unwrap_or_default().contains("plugin.js:2"));
+            }
+            e => panic!("Unexpected error: {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_realm_modules() {
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        let realm = runtime.create_realm().expect("Could not create realm");
+
+        let dependency = Module::new("dependency.js", "export const value = 2;");
+        let main = Module::new(
+            "main.js",
+            "
+            import { value } from './dependency.js';
+            export function double() { return value * 2; }
+        ",
+        );
+
+        runtime
+            .load_module_in_realm(&realm, &dependency)
+            .expect("Could not load module");
+        let handle = runtime
+            .load_module_in_realm(&realm, &main)
+            .expect("Could not load module");
+        let value: usize = runtime
+            .call_function_in_realm(&realm, Some(&handle), "double", json_args!())
+            .expect("Could not call function");
+        assert_eq!(4, value);
+
+        // Modules from other realms are not visible
+        let other = runtime.create_realm().expect("Could not create realm");
+        runtime
+            .load_module_in_realm(&other, &main)
+            .expect_err("Imported a module from another realm");
+
+        // Modules that fail to evaluate cannot be imported
+        let broken = Module::new(
+            "broken.js",
+            "export const value = 1; throw new Error('oops');",
+        );
+        let importer = Module::new("importer.js", "import { value } from './broken.js';");
+        runtime
+            .load_module_in_realm(&other, &broken)
+            .expect_err("Module did not fail");
+        runtime
+            .load_module_in_realm(&other, &importer)
+            .expect_err("Imported a module that failed");
+    }
+}
@@ -1,7 +1,12 @@
 use crate::{
-    inner_runtime::{InnerRuntime, InnerRuntimeOptions, RsAsyncFunction, RsFunction},
+    host_class::HostClass,
+    inner_runtime::{
+        InnerRuntime, InnerRuntimeOptions, RsAsyncFunction, RsFunction, RsTypedAsyncFunction,
+        RsTypedFunction,
+    },
     js_value::Function,
-    Error, FunctionArguments, Module, ModuleHandle,
+    traits::IntoArgs,
+    CallOptions, Error, EventLoopPolicy, FunctionArguments, Module, ModuleHandle,
 };
 use deno_core::serde_json;
 use std::rc::Rc;
@@ -109,13 +114,118 @@ impl Runtime {
         &self.inner.options
     }
 
+    /// Returns a snapshot of the runtime's heap usage, loaded modules,
+    /// registered function usage, and pending work in the event loop
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let metrics = runtime.metrics();
+    /// println!("Heap usage: {} bytes", metrics.used_heap_size);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn metrics(&mut self) -> crate::RuntimeMetrics {
+        self.inner.metrics()
+    }
+
+    /// Run a full, blocking garbage collection
+    ///
+    /// Collection is precise if [crate::V8Flags::expose_gc] was applied,
+    /// and otherwise falls back to V8's low-memory collection
+    ///
+    /// Note that values held from rust, such as [crate::js_value::Value], are kept alive until dropped
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, Error, Undefined };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.eval::<Undefined>("globalThis.data = new Array(100000).fill('x'); undefined")?;
+    /// runtime.eval::<Undefined>("globalThis.data = null; undefined")?;
+    /// runtime.collect_garbage();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn collect_garbage(&mut self) {
+        self.inner.collect_garbage();
+    }
+
+    /// Tell V8 that the system is running low on memory
+    /// It will run full collections and release as much memory as it can, including caches
+    pub fn notify_low_memory(&mut self) {
+        self.inner.notify_low_memory();
+    }
+
+    /// Hint that the runtime will be idle for `idle_time`, such as between requests
+    /// V8 uses the time for idle tasks, such as incremental garbage collection
+    ///
+    /// Only has an effect if the platform was initialized with idle task support - see [crate::init_platform]
+    pub fn idle_notification(&mut self, idle_time: std::time::Duration) {
+        self.inner.idle_notification(idle_time);
+    }
+
+    /// Return the runtime to the state it was in right after it was created,
+    /// so that it can be reused without leaking state from earlier work
+    ///
+    /// - Globals created since then are removed, and replaced built-in globals are restored
+    /// - Pending timers are cancelled
+    /// - The registered entrypoint is dropped
+    /// - Previously loaded modules are forgotten - loading them again runs them again
+    ///
+    /// The isolate, extensions, startup snapshot and registered rust functions are kept.
+    ///
+    /// Note that module instances are never freed by v8, and that changes made to built-in
+    /// objects (such as `Array.prototype`) or top-level `let` and `const` declarations made
+    /// with [Runtime::eval] cannot be undone
+    ///
+    /// Pending async operations cannot be cancelled, so the reset fails with [Error::Runtime]
+    /// while any remain - run the event loop until it is idle first
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.eval::<usize>("globalThis.secret = 5")?;
+    /// runtime.reset()?;
+    ///
+    /// let defined: bool = runtime.eval("'secret' in globalThis")?;
+    /// assert!(!defined);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reset(&mut self) -> Result<(), Error> {
+        let watchdog = self.inner.watchdog.arm(self.options().timeout)?;
+        let result = self.inner.reset();
+        watchdog.finish(result)
+    }
+
+    /// Returns true if the runtime exceeded its heap limit (see [RuntimeOptions::max_heap_size])
+    /// A poisoned runtime will refuse any further calls with [Error::HeapExhausted], and must be recreated
+    pub fn is_poisoned(&self) -> bool {
+        self.inner.watchdog.is_poisoned()
+    }
+
+    /// Returns a handle that can be used from any thread to cancel the call
+    /// currently running on this runtime, which will return [Error::Interrupted]
+    ///
+    /// The handle remains valid for the lifetime of the runtime, across any number of calls
+    pub fn interrupt_handle(&self) -> crate::InterruptHandle {
+        crate::InterruptHandle::new(self.inner.watchdog.handle())
+    }
+
     /// Run the JS event loop to completion
     /// Required when using the `_immediate` variants of functions
     pub async fn await_event_loop(
         &mut self,
         options: deno_core::PollEventLoopOptions,
     ) -> Result<(), Error> {
-        self.inner.await_event_loop(options).await
+        self.with_timeout(|runtime| async move { runtime.inner.await_event_loop(options).await })
+            .await
     }
 
     /// Run the JS event loop to completion
@@ -127,6 +237,118 @@ impl Runtime {
         self.run_async_task(|runtime| async move { runtime.await_event_loop(options).await })
     }
 
+    /// Advance the JS event loop by a single iteration, without waiting
+    /// Timers that are due and completed ops will have their callbacks run
+    ///
+    /// Returns true if work remains in the event loop
+    pub async fn poll_event_loop_once_async(&mut self) -> Result<bool, Error> {
+        self.with_timeout(|runtime| async move { runtime.inner.poll_event_loop_once().await })
+            .await
+    }
+
+    /// Advance the JS event loop by a single iteration, without waiting
+    /// Timers that are due and completed ops will have their callbacks run
+    ///
+    /// Returns true if work remains in the event loop
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let pending = runtime.poll_event_loop_once()?;
+    /// assert!(!pending);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn poll_event_loop_once(&mut self) -> Result<bool, Error> {
+        self.run_async_task(|runtime| async move { runtime.poll_event_loop_once_async().await })
+    }
+
+    /// Run the JS event loop for at most `duration`, or until it completes
+    /// Useful to tick JS timers and ops from an external scheduler, such as a game loop
+    ///
+    /// Returns true if work remains in the event loop
+    ///
+    /// The slice is capped at the runtime's timeout, so a long slice returns instead of failing
+    /// with [Error::Timeout]; synchronous JS that outlives the timeout is still terminated
+    pub async fn run_event_loop_for_async(
+        &mut self,
+        duration: std::time::Duration,
+    ) -> Result<bool, Error> {
+        let timeout = self.options().timeout;
+        let slice = duration.min(timeout);
+        self.with_deadline(timeout, |runtime| async move {
+            runtime.inner.run_event_loop_for(slice).await
+        })
+        .await
+    }
+
+    /// Run the JS event loop for at most `duration`, or until it completes
+    /// Useful to tick JS timers and ops from an external scheduler, such as a game loop
+    ///
+    /// Returns true if work remains in the event loop
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, Error };
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// while runtime.run_event_loop_for(Duration::from_millis(16))? {
+    ///     // Render a frame
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn run_event_loop_for(&mut self, duration: std::time::Duration) -> Result<bool, Error> {
+        self.run_async_task(
+            |runtime| async move { runtime.run_event_loop_for_async(duration).await },
+        )
+    }
+
+    /// Move the runtime's virtual clock forward, without waiting
+    /// Timers that come due are fired instantly, in order, and the event loop is polled after each one
+    ///
+    /// Requires [RuntimeOptions::clock] to be set
+    pub async fn advance_time_async(&mut self, duration: std::time::Duration) -> Result<(), Error> {
+        self.with_timeout(|runtime| async move { runtime.inner.advance_time(duration).await })
+            .await
+    }
+
+    /// Move the runtime's virtual clock forward, without waiting
+    /// Timers that come due are fired instantly, in order, and the event loop is polled after each one
+    ///
+    /// Requires [RuntimeOptions::clock] to be set
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, RuntimeOptions, VirtualClock, Error, Undefined };
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(RuntimeOptions {
+    ///     clock: Some(VirtualClock::default()),
+    ///     ..Default::default()
+    /// })?;
+    ///
+    /// runtime.eval::<Undefined>("globalThis.ticks = 0; setInterval(() => ticks++, 1000); undefined")?;
+    /// runtime.advance_time(Duration::from_secs(60))?;
+    ///
+    /// let ticks: usize = runtime.eval("ticks")?;
+    /// assert_eq!(60, ticks);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn advance_time(&mut self, duration: std::time::Duration) -> Result<(), Error> {
+        self.run_async_task(|runtime| async move { runtime.advance_time_async(duration).await })
+    }
+
     /// Encode an argument as a json value for use as a function argument
     /// ```rust
     /// use rustyscript::{ Runtime, RuntimeOptions, Module };
@@ -284,6 +506,182 @@ impl Runtime {
         self.inner.register_async_function(name, callback)
     }
 
+    /// Register a rust function with typed arguments to be callable from JS
+    /// Arguments are converted directly from v8 to the closure's argument types, and the result back,
+    /// without going through `serde_json::Value`
+    ///
+    /// Missing trailing arguments are converted from `undefined`, so `Option` arguments can be left out.
+    /// Calls with too many arguments, or arguments that cannot be converted, throw a `TypeError` in JS
+    ///
+    /// Arguments can be JS functions, received as [crate::js_value::Function]
+    /// To call them before returning, take a `&mut` [crate::FunctionContext] as the first argument
+    ///
+    /// # Arguments
+    /// * `name` - The name of the function, as called from `rustyscript.functions`
+    /// * `callback` - A closure of up to 8 `DeserializeOwned` arguments, returning a `Result` of a `Serialize` type
+    ///
+    /// # Returns
+    /// A `Result` containing an error if the function could not be registered
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, Error };
+    ///
+    /// #[derive(serde::Serialize)]
+    /// struct Greeting {
+    ///     text: String,
+    /// }
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.register_typed_function("greet", |name: String, times: u32| {
+    ///     Ok(Greeting { text: format!("Hello {name}! ").repeat(times as usize) })
+    /// })?;
+    ///
+    /// let text: String = runtime.eval("rustyscript.functions.greet('world', 2).text")?;
+    /// assert_eq!(text, "Hello world! Hello world! ");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_typed_function<F, Args>(&mut self, name: &str, callback: F) -> Result<(), Error>
+    where
+        F: RsTypedFunction<Args>,
+    {
+        self.inner.register_typed_function(name, callback)
+    }
+
+    /// Register a rust type as a class that scripts can construct, from `rustyscript.classes`
+    /// Each instance is backed by the runtime's resource table, and dropped once its JS object is garbage collected
+    ///
+    /// Constructors and members are checked by [crate::FunctionCapabilities], and recorded by the audit sink,
+    /// as functions named `Class.constructor` and `Class.member`
+    ///
+    /// # Returns
+    /// A `Result` containing an error if the class could not be registered
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ ClassBuilder, HostClass, Runtime, Error };
+    ///
+    /// struct Document {
+    ///     text: String,
+    /// }
+    ///
+    /// impl HostClass for Document {
+    ///     const NAME: &'static str = "Document";
+    ///     type Args = (String,);
+    ///
+    ///     fn construct((text,): Self::Args) -> Result<Self, Error> {
+    ///         Ok(Self { text })
+    ///     }
+    ///
+    ///     fn members(class: &mut ClassBuilder<Self>) {
+    ///         class
+    ///             .method("append", |document: &mut Self, text: String| {
+    ///                 document.text.push_str(&text);
+    ///                 Ok(())
+    ///             })
+    ///             .getter("length", |document| Ok(document.text.len()));
+    ///     }
+    /// }
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.register_class::<Document>()?;
+    ///
+    /// let length: usize = runtime.eval("
+    ///     const document = new rustyscript.classes.Document('abc');
+    ///     document.append('def');
+    ///     document.length
+    /// ")?;
+    /// assert_eq!(length, 6);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_class<T: HostClass>(&mut self) -> Result<(), Error> {
+        self.inner.register_class::<T>()
+    }
+
+    /// Group registered functions into a virtual module, importable as `host:<name>`
+    ///
+    /// This is the preferred way for modules to reach registered functions.
+    /// Unlike `rustyscript.functions`, importing a function that is not part of the module
+    /// fails when the importing module is linked, rather than when the function is called.
+    /// The module does not rely on any global, so it keeps working when a lockdown removes `rustyscript`
+    ///
+    /// Once a module has been imported, registering it again only has an effect after [Runtime::reset]
+    ///
+    /// # Arguments
+    /// * `name` - The name of the module, imported as `host:<name>`
+    /// * `functions` - Names of functions already registered with the runtime, sync or async
+    ///
+    /// # Returns
+    /// A `Result` containing an error if one of the functions is not registered
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.register_typed_function("readConfig", |key: String| Ok(format!("value of {key}")))?;
+    /// runtime.register_host_module("config", &["readConfig"])?;
+    ///
+    /// let module = Module::new("test.js", "
+    ///     import { readConfig } from 'host:config';
+    ///     export const value = readConfig('port');
+    /// ");
+    /// let handle = runtime.load_module(&module)?;
+    /// let value: String = runtime.get_value(Some(&handle), "value")?;
+    /// assert_eq!(value, "value of port");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_host_module(&mut self, name: &str, functions: &[&str]) -> Result<(), Error> {
+        self.inner.register_host_module(name, functions)
+    }
+
+    /// Register a non-blocking rust function with typed arguments to be callable from JS
+    /// Arguments are converted directly from v8 to the closure's argument types
+    ///
+    /// The closure is given an [crate::AsyncFunctionContext] as its first argument,
+    /// which can call JS functions it receives as [crate::js_value::Function] until its future resolves
+    ///
+    /// # Arguments
+    /// * `name` - The name of the function, as called from `rustyscript.async_functions`
+    /// * `callback` - A closure taking an [crate::AsyncFunctionContext], and up to 8 `DeserializeOwned` arguments,
+    ///   returning a future that resolves to a `Result` of a `Serialize` type
+    ///
+    /// # Returns
+    /// A `Result` containing an error if the function could not be registered
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ AsyncFunctionContext, Runtime, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.register_typed_async_function("add", |_: AsyncFunctionContext, a: i64, b: i64| async move {
+    ///     Ok(a + b)
+    /// })?;
+    ///
+    /// let tokio_runtime = runtime.tokio_runtime();
+    /// let value: i64 = tokio_runtime.block_on(runtime.eval_async("await rustyscript.async_functions.add(1, 2)"))?;
+    /// assert_eq!(value, 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_typed_async_function<F, Args>(
+        &mut self,
+        name: &str,
+        callback: F,
+    ) -> Result<(), Error>
+    where
+        F: RsTypedAsyncFunction<Args>,
+    {
+        self.inner.register_typed_async_function(name, callback)
+    }
+
     /// Evaluate a piece of non-ECMAScript-module JavaScript code
     /// The expression is evaluated in the global context, so changes persist
     ///
@@ -324,73 +722,257 @@ impl Runtime {
     where
         T: serde::de::DeserializeOwned,
     {
-        self.inner.eval(expr)
+        let watchdog = self.inner.watchdog.arm(self.options().timeout)?;
+        let result = self.inner.eval(expr);
+        watchdog.finish(result)
     }
 
-    /// Calls a stored javascript function and deserializes its return value.
-    /// Returns a future that resolves when:
-    /// - The event loop is resolved, and
-    /// - If the value is a promise, the promise is resolved
+    /// Evaluate a piece of non-ECMAScript-module JavaScript code under the given file name
+    /// The name appears in stack traces, and in [Error::as_highlighted]
     ///
-    /// Note that synchronous functions are run synchronously. Returned promises will be run asynchronously, however.
+    /// See [Runtime::eval] for details
     ///
     /// # Arguments
-    /// * `module_context` - Optional handle to a module providing global context for the function
-    /// * `function` - A The function object
-    /// * `args` - The arguments to pass to the function
+    /// * `name` - The file name to give the code
+    /// * `expr` - A string representing the JavaScript expression to evaluate
     ///
     /// # Returns
-    /// A `Result` containing the deserialized result of the function call (`T`)
-    /// or an error (`Error`) if the function cannot be found, if there are issues with
-    /// calling the function, or if the result cannot be deserialized.
-    pub async fn call_stored_function_async<T>(
-        &mut self,
-        module_context: Option<&ModuleHandle>,
-        function: &Function,
-        args: &FunctionArguments,
-    ) -> Result<T, Error>
+    /// A `Result` containing the deserialized result of the expression (`T`)
+    /// or an error (`Error`) if the expression cannot be evaluated or if the
+    /// result cannot be deserialized.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let value: usize = runtime.eval_named("repl_1.js", "2 + 2")?;
+    /// assert_eq!(4, value);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn eval_named<T>(&mut self, name: &str, expr: &str) -> Result<T, Error>
     where
         T: serde::de::DeserializeOwned,
     {
-        let function = function.as_global(&mut self.deno_runtime().handle_scope());
-        let result = self
-            .inner
-            .call_function_by_ref(module_context, function, args)
-            .await?;
-        let result = self.inner.resolve_with_event_loop(result).await?;
-        self.inner.decode_value(result)
+        let watchdog = self.inner.watchdog.arm(self.options().timeout)?;
+        let result = self.inner.eval_named(name, expr);
+        watchdog.finish(result)
     }
 
-    /// Calls a stored javascript function and deserializes its return value.
-    /// Blocks until:
+    /// Evaluate a piece of JavaScript code that may use top-level `await`
+    /// Returns a future that resolves when:
     /// - The event loop is resolved, and
-    /// - If the value is a promise, the promise is resolved
+    /// - The value of the expression is resolved
+    ///
+    /// The code runs inside an async function, so `let`, `const` and function declarations
+    /// will not persist between calls. Assign to `globalThis` instead.
+    ///
+    /// The code is given an empty file name - use [Runtime::eval_async_named] to name it
     ///
     /// # Arguments
-    /// * `module_context` - Optional handle to a module providing global context for the function
-    /// * `function` - A The function object
-    /// * `args` - The arguments to pass to the function
+    /// * `expr` - A string representing the JavaScript expression to evaluate
     ///
     /// # Returns
-    /// A `Result` containing the deserialized result of the function call (`T`)
-    /// or an error (`Error`) if the function cannot be found, if there are issues with
-    /// calling the function, or if the result cannot be deserialized.
-    pub fn call_stored_function<T>(
-        &mut self,
-        module_context: Option<&ModuleHandle>,
-        function: &Function,
-        args: &FunctionArguments,
-    ) -> Result<T, Error>
-    where
-        T: deno_core::serde::de::DeserializeOwned,
-    {
-        self.run_async_task(|runtime| async move {
-            runtime
+    /// A `Result` containing the deserialized result of the expression (`T`)
+    /// or an error (`Error`) if the expression cannot be evaluated or if the
+    /// result cannot be deserialized.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let tokio_runtime = runtime.tokio_runtime();
+    /// let value: usize = tokio_runtime.block_on(runtime.eval_async("await Promise.resolve(4)"))?;
+    /// assert_eq!(4, value);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn eval_async<T>(&mut self, expr: &str) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.eval_async_named("", expr).await
+    }
+
+    /// Evaluate a piece of JavaScript code that may use top-level `await`, under the given file name
+    /// The name appears in stack traces, and in [Error::as_highlighted]
+    ///
+    /// See [Runtime::eval_async] for details
+    ///
+    /// # Arguments
+    /// * `name` - The file name to give the code
+    /// * `expr` - A string representing the JavaScript expression to evaluate
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the expression (`T`)
+    /// or an error (`Error`) if the expression cannot be evaluated or if the
+    /// result cannot be deserialized.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let tokio_runtime = runtime.tokio_runtime();
+    /// let value: usize = tokio_runtime.block_on(
+    ///     runtime.eval_async_named("repl_1.js", "await Promise.resolve(4)")
+    /// )?;
+    /// assert_eq!(4, value);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn eval_async_named<T>(&mut self, name: &str, expr: &str) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.with_timeout(|runtime| async move {
+            let result = runtime.inner.eval_async(name, expr).await?;
+            runtime.inner.decode_value(result)
+        })
+        .await
+    }
+
+    /// Calls a stored javascript function and deserializes its return value.
+    /// Returns a future that resolves when:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// Note that synchronous functions are run synchronously. Returned promises will be run asynchronously, however.
+    ///
+    /// # Arguments
+    /// * `module_context` - Optional handle to a module providing global context for the function
+    /// * `function` - A The function object
+    /// * `args` - The arguments to pass to the function
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the function call (`T`)
+    /// or an error (`Error`) if the function cannot be found, if there are issues with
+    /// calling the function, or if the result cannot be deserialized.
+    pub async fn call_stored_function_async<T>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        function: &Function,
+        args: &FunctionArguments,
+    ) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let function = function.as_global(&mut self.deno_runtime().handle_scope());
+        self.with_timeout(|runtime| async move {
+            let result = runtime
+                .inner
+                .call_function_by_ref(module_context, function, args)
+                .await?;
+            let result = runtime.inner.resolve_with_event_loop(result).await?;
+            runtime.inner.decode_value(result)
+        })
+        .await
+    }
+
+    /// Calls a stored javascript function and deserializes its return value.
+    /// Blocks until:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// # Arguments
+    /// * `module_context` - Optional handle to a module providing global context for the function
+    /// * `function` - A The function object
+    /// * `args` - The arguments to pass to the function
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the function call (`T`)
+    /// or an error (`Error`) if the function cannot be found, if there are issues with
+    /// calling the function, or if the result cannot be deserialized.
+    pub fn call_stored_function<T>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        function: &Function,
+        args: &FunctionArguments,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        self.run_async_task(|runtime| async move {
+            runtime
                 .call_stored_function_async(module_context, function, args)
                 .await
         })
     }
 
+    /// Calls a stored javascript function and deserializes its return value.
+    /// Arguments are converted straight to v8, without going through `serde_json::Value`
+    /// Returns a future that resolves when:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// # Arguments
+    /// * `module_context` - Optional handle to a module providing global context for the function
+    /// * `function` - A The function object
+    /// * `args` - The arguments to pass to the function - a tuple of `Serialize` values, or any [IntoArgs]
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the function call (`T`)
+    /// or an error (`Error`) if the function cannot be found, if there are issues with
+    /// calling the function, or if the result cannot be deserialized.
+    pub async fn call_stored_function_typed_async<T, A>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        function: &Function,
+        args: &A,
+    ) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+        A: IntoArgs + ?Sized,
+    {
+        let function = function.as_global(&mut self.deno_runtime().handle_scope());
+        self.with_timeout(|runtime| async move {
+            let result = runtime
+                .inner
+                .call_function_by_ref(module_context, function, args)
+                .await?;
+            let result = runtime.inner.resolve_with_event_loop(result).await?;
+            runtime.inner.decode_value(result)
+        })
+        .await
+    }
+
+    /// Calls a stored javascript function and deserializes its return value.
+    /// Arguments are converted straight to v8, without going through `serde_json::Value`
+    /// Blocks until:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// # Arguments
+    /// * `module_context` - Optional handle to a module providing global context for the function
+    /// * `function` - A The function object
+    /// * `args` - The arguments to pass to the function - a tuple of `Serialize` values, or any [IntoArgs]
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the function call (`T`)
+    /// or an error (`Error`) if the function cannot be found, if there are issues with
+    /// calling the function, or if the result cannot be deserialized.
+    pub fn call_stored_function_typed<T, A>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        function: &Function,
+        args: &A,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+        A: IntoArgs + ?Sized,
+    {
+        self.run_async_task(|runtime| async move {
+            runtime
+                .call_stored_function_typed_async(module_context, function, args)
+                .await
+        })
+    }
+
     /// Calls a stored javascript function and deserializes its return value.
     /// Will not attempt to resolve promises, or run the event loop
     /// Promises can be returned by specifying the return type as [crate::js_value::Promise]
@@ -451,13 +1033,16 @@ impl Runtime {
     where
         T: deno_core::serde::de::DeserializeOwned,
     {
-        let function = self.inner.get_function_by_name(module_context, name)?;
-        let result = self
-            .inner
-            .call_function_by_ref(module_context, function, args)
-            .await?;
-        let result = self.inner.resolve_with_event_loop(result).await?;
-        self.inner.decode_value(result)
+        self.with_timeout(|runtime| async move {
+            let function = runtime.inner.get_function_by_name(module_context, name)?;
+            let result = runtime
+                .inner
+                .call_function_by_ref(module_context, function, args)
+                .await?;
+            let result = runtime.inner.resolve_with_event_loop(result).await?;
+            runtime.inner.decode_value(result)
+        })
+        .await
     }
 
     /// Calls a javascript function within the Deno runtime by its name and deserializes its return value.
@@ -505,730 +1090,2927 @@ impl Runtime {
     }
 
     /// Calls a javascript function within the Deno runtime by its name and deserializes its return value.
-    /// Will not attempt to resolve promises, or run the event loop
-    /// Promises can be returned by specifying the return type as [crate::js_value::Promise]
-    /// The event loop should be run using [Runtime::await_event_loop]
+    /// Arguments are converted straight to v8, without going through `serde_json::Value`
+    /// Returns a future that resolves when:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
     ///
     /// # Arguments
     /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
     /// * `name` - A string representing the name of the javascript function to call.
-    /// * `args` - The arguments to pass to the function
+    /// * `args` - The arguments to pass to the function - a tuple of `Serialize` values, or any [IntoArgs]
     ///
     /// # Returns
     /// A `Result` containing the deserialized result of the function call (`T`)
     /// or an error (`Error`) if the function cannot be found, if there are issues with
     /// calling the function, or if the result cannot be deserialized.
     ///
-    /// # Example
-    ///
-    /// ```rust
-    /// use rustyscript::{ json_args, Runtime, Module, Error };
-    ///
-    /// # fn main() -> Result<(), Error> {
-    /// let mut runtime = Runtime::new(Default::default())?;
-    /// let module = Module::new("/path/to/module.js", "export function f() { return 2; };");
-    /// let module = runtime.load_module(&module)?;
-    /// let value: usize = runtime.call_function_immediate(Some(&module), "f", json_args!())?;
-    /// # Ok(())
-    /// # }
-    /// ```
-    pub fn call_function_immediate<T>(
+    /// See [Runtime::call_function_typed] for an example
+    pub async fn call_function_typed_async<T, A>(
         &mut self,
         module_context: Option<&ModuleHandle>,
         name: &str,
-        args: &FunctionArguments,
+        args: &A,
     ) -> Result<T, Error>
     where
         T: deno_core::serde::de::DeserializeOwned,
+        A: IntoArgs + ?Sized,
     {
-        let function = self.inner.get_function_by_name(module_context, name)?;
-        let result = self.run_async_task(|runtime| async move {
-            runtime
+        self.with_timeout(|runtime| async move {
+            let function = runtime.inner.get_function_by_name(module_context, name)?;
+            let result = runtime
                 .inner
                 .call_function_by_ref(module_context, function, args)
-                .await
-        })?;
-        self.inner.decode_value(result)
+                .await?;
+            let result = runtime.inner.resolve_with_event_loop(result).await?;
+            runtime.inner.decode_value(result)
+        })
+        .await
     }
 
-    /// Get a value from a runtime instance
+    /// Calls a javascript function within the Deno runtime by its name and deserializes its return value.
+    /// Arguments are converted straight to v8, without going through `serde_json::Value`
     /// Blocks until:
     /// - The event loop is resolved, and
     /// - If the value is a promise, the promise is resolved
     ///
     /// # Arguments
     /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
-    /// * `name` - A string representing the name of the value to find
+    /// * `name` - A string representing the name of the javascript function to call.
+    /// * `args` - The arguments to pass to the function - a tuple of `Serialize` values, or any [IntoArgs]
     ///
     /// # Returns
-    /// A `Result` containing the deserialized result or an error (`Error`) if the
-    /// value cannot be found, if there are issues with, or if the result cannot be
-    ///  deserialized.
+    /// A `Result` containing the deserialized result of the function call (`T`)
+    /// or an error (`Error`) if the function cannot be found, if there are issues with
+    /// calling the function, or if the result cannot be deserialized.
     ///
     /// # Example
     ///
     /// ```rust
     /// use rustyscript::{ Runtime, Module, Error };
     ///
+    /// #[derive(serde::Serialize)]
+    /// struct Point {
+    ///     x: i64,
+    ///     y: i64,
+    /// }
+    ///
     /// # fn main() -> Result<(), Error> {
     /// let mut runtime = Runtime::new(Default::default())?;
-    /// let module = Module::new("/path/to/module.js", "globalThis.my_value = 2;");
+    /// let module = Module::new("/path/to/module.js", "export function f(p, scale) { return (p.x + p.y) * scale; };");
     /// let module = runtime.load_module(&module)?;
-    /// let value: usize = runtime.get_value(Some(&module), "my_value")?;
+    /// let value: i64 = runtime.call_function_typed(Some(&module), "f", &(Point { x: 1, y: 2 }, 2))?;
+    /// assert_eq!(value, 6);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn get_value<T>(
+    pub fn call_function_typed<T, A>(
         &mut self,
         module_context: Option<&ModuleHandle>,
         name: &str,
+        args: &A,
     ) -> Result<T, Error>
     where
-        T: serde::de::DeserializeOwned,
+        T: deno_core::serde::de::DeserializeOwned,
+        A: IntoArgs + ?Sized,
     {
-        self.run_async_task(
-            |runtime| async move { runtime.get_value_async(module_context, name).await },
-        )
+        self.run_async_task(|runtime| async move {
+            runtime
+                .call_function_typed_async(module_context, name, args)
+                .await
+        })
     }
 
-    /// Get a value from a runtime instance
-    /// Returns a future that resolves when:
-    /// - The event loop is resolved, and
-    /// - If the value is a promise, the promise is resolved
+    /// Calls a javascript function within the Deno runtime by its name and deserializes its return value.
+    /// Returns a future that resolves according to the given [CallOptions]:
+    /// - The timeout replaces the runtime's timeout for this call
+    /// - `this` replaces the module namespace as the value of `this` in the function
+    /// - The event loop is either drained, or only run until the returned promise resolves
     ///
     /// # Arguments
     /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
-    /// * `name` - A string representing the name of the value to find
+    /// * `name` - A string representing the name of the javascript function to call.
+    /// * `args` - The arguments to pass to the function
+    /// * `options` - Settings for this call
     ///
     /// # Returns
-    /// A `Result` containing the future or an error (`Error`) if the value cannot be found,
-    /// or if the result cannot be deserialized.
+    /// A `Result` containing the deserialized result of the function call (`T`)
+    /// or an error (`Error`) if the function cannot be found, if there are issues with
+    /// calling the function, or if the result cannot be deserialized.
     ///
-    /// See [Runtime::get_value] for an example
-    pub async fn get_value_async<T>(
+    /// See [Runtime::call_function_with] for an example
+    pub async fn call_function_with_async<T>(
         &mut self,
         module_context: Option<&ModuleHandle>,
         name: &str,
+        args: &FunctionArguments,
+        options: &CallOptions,
     ) -> Result<T, Error>
     where
-        T: serde::de::DeserializeOwned,
+        T: deno_core::serde::de::DeserializeOwned,
     {
-        let result = self.inner.get_value_ref(module_context, name)?;
-        let result = self.inner.resolve_with_event_loop(result).await?;
-        self.inner.decode_value(result)
+        let timeout = options.timeout.unwrap_or(self.options().timeout);
+        let this = options.this.clone().map(|this| this.into_v8());
+        self.with_deadline(timeout, |runtime| async move {
+            let function = runtime.inner.get_function_by_name(module_context, name)?;
+            let result = runtime
+                .inner
+                .call_function_by_ref_with_this(module_context, this, function, args)
+                .await?;
+            let result = runtime.inner.resolve_with_event_loop(result).await?;
+            if options.event_loop == EventLoopPolicy::Drain {
+                runtime.inner.await_event_loop(Default::default()).await?;
+            }
+            runtime.inner.decode_value(result)
+        })
+        .await
     }
 
-    /// Get a value from a runtime instance
-    /// Will not attempt to resolve promises, or run the event loop
-    /// Promises can be returned by specifying the return type as [crate::js_value::Promise]
-    /// The event loop should be run using [Runtime::await_event_loop]
+    /// Calls a javascript function within the Deno runtime by its name and deserializes its return value.
+    /// Blocks until the call completes according to the given [CallOptions]
+    /// See [Runtime::call_function_with_async] for details
     ///
     /// # Arguments
     /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
-    /// * `name` - A string representing the name of the value to find
+    /// * `name` - A string representing the name of the javascript function to call.
+    /// * `args` - The arguments to pass to the function
+    /// * `options` - Settings for this call
     ///
     /// # Returns
-    /// A `Result` containing the deserialized result or an error (`Error`) if the
-    /// value cannot be found, if there are issues with, or if the result cannot be
-    ///  deserialized.
+    /// A `Result` containing the deserialized result of the function call (`T`)
+    /// or an error (`Error`) if the function cannot be found, if there are issues with
+    /// calling the function, or if the result cannot be deserialized.
     ///
     /// # Example
     ///
     /// ```rust
-    /// use rustyscript::{ Runtime, Module, Error };
+    /// use rustyscript::{ json_args, CallOptions, Runtime, Module, Error };
+    /// use std::time::Duration;
     ///
     /// # fn main() -> Result<(), Error> {
     /// let mut runtime = Runtime::new(Default::default())?;
-    /// let module = Module::new("/path/to/module.js", "globalThis.my_value = 2;");
+    /// let module = Module::new("/path/to/module.js", "export function f() { return 2; };");
     /// let module = runtime.load_module(&module)?;
-    /// let value: usize = runtime.get_value_immediate(Some(&module), "my_value")?;
+    ///
+    /// let options = CallOptions {
+    ///     timeout: Some(Duration::from_millis(50)),
+    ///     ..Default::default()
+    /// };
+    /// let value: usize = runtime.call_function_with(Some(&module), "f", json_args!(), &options)?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn get_value_immediate<T>(
+    pub fn call_function_with<T>(
         &mut self,
         module_context: Option<&ModuleHandle>,
         name: &str,
+        args: &FunctionArguments,
+        options: &CallOptions,
     ) -> Result<T, Error>
     where
-        T: serde::de::DeserializeOwned,
+        T: deno_core::serde::de::DeserializeOwned,
     {
-        let result = self.inner.get_value_ref(module_context, name)?;
-        self.inner.decode_value(result)
-    }
-
-    /// Executes the given module, and returns a handle allowing you to extract values
-    /// And call functions
-    ///
-    /// Blocks until the module has been executed
-    ///
-    /// # Arguments
-    /// * `module` - A `Module` object containing the module's filename and contents.
-    ///
-    /// # Returns
-    /// A `Result` containing a handle for the loaded module
-    /// or an error (`Error`) if there are issues with loading modules, executing the
-    /// module, or if the result cannot be deserialized.
-    ///
-    /// # Example
-    ///
-    /// ```rust
-    /// // Create a module with filename and contents
-    /// use rustyscript::{Runtime, Module, Error};
-    ///
-    /// # fn main() -> Result<(), Error> {
-    /// let mut runtime = Runtime::new(Default::default())?;
-    /// let module = Module::new("test.js", "export default () => 'test'");
-    /// runtime.load_module(&module);
-    /// # Ok(())
-    /// # }
-    /// ```
-    pub fn load_module(&mut self, module: &Module) -> Result<ModuleHandle, Error> {
-        self.run_async_task(|runtime| async move { runtime.load_module_async(module).await })
-    }
-
-    /// Executes the given module, and returns a handle allowing you to extract values
-    /// And call functions
-    ///
-    /// Returns a future that resolves to the handle for the loaded module
-    ///
-    /// # Arguments
-    /// * `module` - A `Module` object containing the module's filename and contents.
-    ///
-    /// # Returns
-    /// A `Result` containing a handle for the loaded module
-    /// or an error (`Error`) if there are issues with loading modules, executing the
-    /// module, or if the result cannot be deserialized.
-    ///
-    /// See [Runtime::load_module] for an example
-    pub async fn load_module_async(&mut self, module: &Module) -> Result<ModuleHandle, Error> {
-        self.inner.load_modules(None, vec![module]).await
+        // The outer blocking timeout must not cut the call short
+        let timeout = options.timeout.unwrap_or(self.options().timeout);
+        let rt = self.tokio_runtime();
+        rt.block_on(self.with_deadline(timeout, |runtime| async move {
+            runtime
+                .call_function_with_async(module_context, name, args, options)
+                .await
+        }))
     }
 
-    /// Executes the given module, and returns a handle allowing you to extract values
-    /// And call functions.
-    ///
-    /// Blocks until the module has been executed
-    ///
-    /// This will load 'module' as the main module, and the others as side-modules.
-    /// Only one main module can be loaded per runtime
+    /// Calls a javascript function within the Deno runtime by its name and deserializes its return value.
+    /// Will not attempt to resolve promises, or run the event loop
+    /// Promises can be returned by specifying the return type as [crate::js_value::Promise]
+    /// The event loop should be run using [Runtime::await_event_loop]
     ///
     /// # Arguments
-    /// * `module` - A `Module` object containing the module's filename and contents.
-    /// * `side_modules` - A set of additional modules to be loaded into memory for use
+    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
+    /// * `name` - A string representing the name of the javascript function to call.
+    /// * `args` - The arguments to pass to the function
     ///
     /// # Returns
-    /// A `Result` containing a handle for the loaded module
-    /// or an error (`Error`) if there are issues with loading modules, executing the
-    /// module, or if the result cannot be deserialized.
+    /// A `Result` containing the deserialized result of the function call (`T`)
+    /// or an error (`Error`) if the function cannot be found, if there are issues with
+    /// calling the function, or if the result cannot be deserialized.
     ///
     /// # Example
     ///
     /// ```rust
-    /// // Create a module with filename and contents
-    /// use rustyscript::{Runtime, Module, Error};
+    /// use rustyscript::{ json_args, Runtime, Module, Error };
     ///
     /// # fn main() -> Result<(), Error> {
     /// let mut runtime = Runtime::new(Default::default())?;
-    /// let module = Module::new("test.js", "export default () => 'test'");
-    /// runtime.load_modules(&module, vec![]);
+    /// let module = Module::new("/path/to/module.js", "export function f() { return 2; };");
+    /// let module = runtime.load_module(&module)?;
+    /// let value: usize = runtime.call_function_immediate(Some(&module), "f", json_args!())?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn load_modules(
-        &mut self,
-        module: &Module,
-        side_modules: Vec<&Module>,
-    ) -> Result<ModuleHandle, Error> {
-        self.run_async_task(move |runtime| async move {
-            runtime.load_modules_async(module, side_modules).await
-        })
-    }
-
-    /// Executes the given module, and returns a handle allowing you to extract values
-    /// And call functions.
-    ///
-    /// Returns a future that resolves to the handle for the loaded module
-    ///
-    /// This will load 'module' as the main module, and the others as side-modules.
-    /// Only one main module can be loaded per runtime
-    ///
-    /// # Arguments
-    /// * `module` - A `Module` object containing the module's filename and contents.
-    /// * `side_modules` - A set of additional modules to be loaded into memory for use
-    ///
-    /// # Returns
-    /// A `Result` containing a handle for the loaded module
-    /// or an error (`Error`) if there are issues with loading modules, executing the
-    /// module, or if the result cannot be deserialized.
-    ///
-    /// See [Runtime::load_modules] for an example
-    pub async fn load_modules_async(
+    pub fn call_function_immediate<T>(
         &mut self,
-        module: &Module,
-        side_modules: Vec<&Module>,
-    ) -> Result<ModuleHandle, Error> {
-        self.inner.load_modules(Some(module), side_modules).await
+        module_context: Option<&ModuleHandle>,
+        name: &str,
+        args: &FunctionArguments,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        let function = self.inner.get_function_by_name(module_context, name)?;
+        let result = self.run_async_task(|runtime| async move {
+            runtime
+                .inner
+                .call_function_by_ref(module_context, function, args)
+                .await
+        })?;
+        self.inner.decode_value(result)
     }
 
-    /// Executes the entrypoint function of a module within the Deno runtime.
+    /// Get a value from a runtime instance
     /// Blocks until:
     /// - The event loop is resolved, and
     /// - If the value is a promise, the promise is resolved
     ///
     /// # Arguments
-    /// * `module_context` - A handle returned by loading a module into the runtime
+    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
+    /// * `name` - A string representing the name of the value to find
     ///
     /// # Returns
-    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)
-    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
-    /// or the result cannot be deserialized.
+    /// A `Result` containing the deserialized result or an error (`Error`) if the
+    /// value cannot be found, if there are issues with, or if the result cannot be
+    ///  deserialized.
     ///
     /// # Example
     ///
     /// ```rust
-    /// use rustyscript::{json_args, Runtime, Module, Error};
+    /// use rustyscript::{ Runtime, Module, Error };
     ///
     /// # fn main() -> Result<(), Error> {
     /// let mut runtime = Runtime::new(Default::default())?;
-    /// let module = Module::new("test.js", "export default () => 'test'");
+    /// let module = Module::new("/path/to/module.js", "globalThis.my_value = 2;");
     /// let module = runtime.load_module(&module)?;
-    ///
-    /// // Run the entrypoint and handle the result
-    /// let value: String = runtime.call_entrypoint(&module, json_args!())?;
+    /// let value: usize = runtime.get_value(Some(&module), "my_value")?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn call_entrypoint<T>(
+    pub fn get_value<T>(
         &mut self,
-        module_context: &ModuleHandle,
-        args: &FunctionArguments,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
     ) -> Result<T, Error>
     where
-        T: deno_core::serde::de::DeserializeOwned,
+        T: serde::de::DeserializeOwned,
     {
-        self.run_async_task(|runtime| async move {
-            runtime.call_entrypoint_async(module_context, args).await
-        })
+        self.run_async_task(
+            |runtime| async move { runtime.get_value_async(module_context, name).await },
+        )
     }
 
-    /// Executes the entrypoint function of a module within the Deno runtime.
+    /// Get a value from a runtime instance
     /// Returns a future that resolves when:
     /// - The event loop is resolved, and
     /// - If the value is a promise, the promise is resolved
     ///
-    /// Note that synchronous functions are run synchronously. Returned promises will be run asynchronously, however.
-    ///
     /// # Arguments
-    /// * `module_context` - A handle returned by loading a module into the runtime
+    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
+    /// * `name` - A string representing the name of the value to find
     ///
     /// # Returns
-    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)
-    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
-    /// or the result cannot be deserialized.
+    /// A `Result` containing the future or an error (`Error`) if the value cannot be found,
+    /// or if the result cannot be deserialized.
     ///
-    /// See [Runtime::call_entrypoint] for an example
-    pub async fn call_entrypoint_async<T>(
+    /// See [Runtime::get_value] for an example
+    pub async fn get_value_async<T>(
         &mut self,
-        module_context: &ModuleHandle,
-        args: &FunctionArguments,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
     ) -> Result<T, Error>
     where
-        T: deno_core::serde::de::DeserializeOwned,
+        T: serde::de::DeserializeOwned,
     {
-        if let Some(entrypoint) = module_context.entrypoint() {
-            let result = self
-                .inner
-                .call_function_by_ref(Some(module_context), entrypoint.clone(), args)
-                .await?;
-            let result = self.inner.resolve_with_event_loop(result).await?;
-            self.inner.decode_value(result)
-        } else {
-            Err(Error::MissingEntrypoint(module_context.module().clone()))
-        }
+        self.with_timeout(|runtime| async move {
+            let result = runtime.inner.get_value_ref(module_context, name)?;
+            let result = runtime.inner.resolve_with_event_loop(result).await?;
+            runtime.inner.decode_value(result)
+        })
+        .await
     }
 
-    /// Executes the entrypoint function of a module within the Deno runtime.
+    /// Get a value from a runtime instance
     /// Will not attempt to resolve promises, or run the event loop
     /// Promises can be returned by specifying the return type as [crate::js_value::Promise]
     /// The event loop should be run using [Runtime::await_event_loop]
     ///
     /// # Arguments
-    /// * `module_context` - A handle returned by loading a module into the runtime
+    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
+    /// * `name` - A string representing the name of the value to find
     ///
     /// # Returns
-    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)
-    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
-    /// or the result cannot be deserialized.
+    /// A `Result` containing the deserialized result or an error (`Error`) if the
+    /// value cannot be found, if there are issues with, or if the result cannot be
+    ///  deserialized.
     ///
     /// # Example
     ///
     /// ```rust
-    /// use rustyscript::{json_args, Runtime, Module, Error};
+    /// use rustyscript::{ Runtime, Module, Error };
     ///
     /// # fn main() -> Result<(), Error> {
     /// let mut runtime = Runtime::new(Default::default())?;
-    /// let module = Module::new("test.js", "export default () => 'test'");
+    /// let module = Module::new("/path/to/module.js", "globalThis.my_value = 2;");
     /// let module = runtime.load_module(&module)?;
-    ///
-    /// // Run the entrypoint and handle the result
-    /// let value: String = runtime.call_entrypoint_immediate(&module, json_args!())?;
+    /// let value: usize = runtime.get_value_immediate(Some(&module), "my_value")?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn call_entrypoint_immediate<T>(
+    pub fn get_value_immediate<T>(
         &mut self,
-        module_context: &ModuleHandle,
-        args: &FunctionArguments,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
     ) -> Result<T, Error>
     where
-        T: deno_core::serde::de::DeserializeOwned,
+        T: serde::de::DeserializeOwned,
     {
-        if let Some(entrypoint) = module_context.entrypoint() {
-            let result = self.run_async_task(|runtime| async move {
-                runtime
-                    .inner
-                    .call_function_by_ref(Some(module_context), entrypoint.clone(), args)
-                    .await
-            })?;
-            self.inner.decode_value(result)
-        } else {
-            Err(Error::MissingEntrypoint(module_context.module().clone()))
-        }
+        let result = self.inner.get_value_ref(module_context, name)?;
+        self.inner.decode_value(result)
     }
 
-    /// Loads a module into a new runtime, executes the entry function and returns the
-    /// result of the module's execution, deserialized into the specified Rust type (`T`).
+    /// Set a value in the global context (globalThis.name)
+    /// The value is serialized directly into v8, and can be set before any module is loaded
     ///
     /// # Arguments
-    /// * `module` - A `Module` object containing the module's filename and contents.
-    /// * `side_modules` - A set of additional modules to be loaded into memory for use
-    /// * `runtime_options` - Options for the creation of the runtime
-    /// * `entrypoint_args` - Arguments to pass to the entrypoint function
-    ///
-    /// # Returns
-    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)
-    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
-    /// or the result cannot be deserialized.
+    /// * `name` - A string representing the name of the value to set
+    /// * `value` - The value to set
     ///
     /// # Example
     ///
     /// ```rust
-    /// // Create a module with filename and contents
-    /// use rustyscript::{json_args, Runtime, Module, Error};
+    /// use rustyscript::{ Runtime, Error };
     ///
     /// # fn main() -> Result<(), Error> {
-    /// let module = Module::new("test.js", "export default () => 2");
-    /// let value: usize = Runtime::execute_module(&module, vec![], Default::default(), json_args!())?;
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.set_global_value("my_value", &vec![1, 2, 3])?;
+    /// let value: usize = runtime.eval("my_value.length")?;
+    /// assert_eq!(3, value);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn execute_module<T>(
+    pub fn set_global_value<T>(&mut self, name: &str, value: &T) -> Result<(), Error>
+    where
+        T: serde::Serialize,
+    {
+        self.inner
+            .set_global_value(name, value, crate::PropertyKind::Writeable)
+    }
+
+    /// Define a value in the global context (globalThis.name)
+    /// Like [Runtime::set_global_value], but the property can be made read-only or non-enumerable
+    ///
+    /// # Arguments
+    /// * `name` - A string representing the name of the value to set
+    /// * `value` - The value to set
+    /// * `kind` - How the property is defined
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, PropertyKind, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.define_global_value("VERSION", &"1.0.0", PropertyKind::ReadOnly)?;
+    /// let value: String = runtime.eval("VERSION = '2.0.0'; VERSION")?;
+    /// assert_eq!("1.0.0", value);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn define_global_value<T>(
+        &mut self,
+        name: &str,
+        value: &T,
+        kind: crate::PropertyKind,
+    ) -> Result<(), Error>
+    where
+        T: serde::Serialize,
+    {
+        self.inner.set_global_value(name, value, kind)
+    }
+
+    /// Remove a value from the global context (globalThis.name)
+    /// Removing a value that does not exist is not an error
+    ///
+    /// # Arguments
+    /// * `name` - A string representing the name of the value to remove
+    pub fn delete_global_value(&mut self, name: &str) -> Result<(), Error> {
+        self.inner.delete_global_value(name)
+    }
+
+    /// Create a new realm - a separate global environment sharing this runtime's isolate
+    /// Code running in one realm cannot see the globals of another, or of the main realm
+    ///
+    /// Each realm gets its own copies of the extension globals, backed by the main realm's:
+    /// - `console`, the timers, `setImmediate` and the `rustyscript` object (`functions`, `async_functions`,
+    ///   `register_entrypoint` and `bail`)
+    /// - `DOMException`, when the `web` or `web_stub` feature is enabled
+    /// - `atob`, `btoa`, `TextEncoder` and `TextDecoder` (without streaming), when the `web` feature is enabled
+    /// - `URL` and `URLSearchParams`, when the `url` feature is enabled
+    /// - `crypto.getRandomValues` and `crypto.randomUUID`, when the `crypto` feature is enabled
+    ///
+    /// Other extension globals, such as `fetch`, streams or `crypto.subtle`, are not available in realms.
+    /// Values passed between a realm and these globals are copied, so a realm can never reach an object
+    /// belonging to the main realm or to another realm.
+    ///
+    /// Modules loaded into a realm can only import modules previously loaded into the same realm.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let realm = runtime.create_realm()?;
+    ///
+    /// runtime.eval_in_realm::<usize>(&realm, "globalThis.value = 2")?;
+    /// let visible: bool = runtime.eval("'value' in globalThis")?;
+    /// assert!(!visible);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_realm(&mut self) -> Result<crate::RealmHandle, Error> {
+        self.inner.create_realm()
+    }
+
+    /// Evaluate a piece of non-ECMAScript-module JavaScript code in the given realm
+    /// See [Runtime::eval]
+    ///
+    /// The code is given an empty file name - use [Runtime::eval_in_realm_named] to name it
+    ///
+    /// # Arguments
+    /// * `realm` - The realm to evaluate the code in
+    /// * `expr` - A string representing the JavaScript expression to evaluate
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the expression (`T`)
+    /// or an error (`Error`) if the expression cannot be evaluated or if the
+    /// result cannot be deserialized.
+    pub fn eval_in_realm<T>(&mut self, realm: &crate::RealmHandle, expr: &str) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.eval_in_realm_named(realm, "", expr)
+    }
+
+    /// Evaluate a piece of non-ECMAScript-module JavaScript code in the given realm,
+    /// under the given file name
+    /// See [Runtime::eval_named]
+    ///
+    /// # Arguments
+    /// * `realm` - The realm to evaluate the code in
+    /// * `name` - The file name shown in stack traces
+    /// * `expr` - A string representing the JavaScript expression to evaluate
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the expression (`T`)
+    /// or an error (`Error`) if the expression cannot be evaluated or if the
+    /// result cannot be deserialized.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let realm = runtime.create_realm()?;
+    ///
+    /// let value: usize = runtime.eval_in_realm_named(&realm, "plugin.js", "1 + 1")?;
+    /// assert_eq!(2, value);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn eval_in_realm_named<T>(
+        &mut self,
+        realm: &crate::RealmHandle,
+        name: &str,
+        expr: &str,
+    ) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let watchdog = self.inner.watchdog.arm(self.options().timeout)?;
+        let result = realm
+            .eval(&mut self.inner.deno_runtime, name, expr)
+            .and_then(|result| self.inner.decode_value(result));
+        watchdog.finish(result)
+    }
+
+    /// Load a module into the given realm
+    /// Blocks until the module has been evaluated
+    ///
+    /// The module can only import modules previously loaded into the same realm
+    ///
+    /// # Arguments
+    /// * `realm` - The realm to load the module into
+    /// * `module` - The module to load
+    ///
+    /// # Returns
+    /// A `Result` containing a handle to the loaded module,
+    /// or an error (`Error`) if the module could not be loaded
+    pub fn load_module_in_realm(
+        &mut self,
+        realm: &crate::RealmHandle,
         module: &Module,
-        side_modules: Vec<&Module>,
-        runtime_options: RuntimeOptions,
-        entrypoint_args: &FunctionArguments,
+    ) -> Result<crate::RealmModuleHandle, Error> {
+        let default_entrypoint = self.options().default_entrypoint.clone();
+        self.run_async_task(|runtime| async move {
+            let loaded = match realm.load_module(&mut runtime.inner.deno_runtime, module) {
+                Ok((handle, result)) => runtime
+                    .inner
+                    .resolve_with_event_loop(result)
+                    .await
+                    .map(|_| handle),
+                Err(e) => Err(e),
+            };
+
+            // Taken even if the load failed, so that it does not carry over to the next module
+            let registered = realm.take_entrypoint(&mut runtime.inner.deno_runtime)?;
+            let mut handle = loaded?;
+            realm.add_module(
+                &mut runtime.inner.deno_runtime,
+                &mut handle,
+                registered,
+                default_entrypoint.as_deref(),
+            )?;
+            Ok(handle)
+        })
+    }
+
+    /// Calls the entrypoint of a module loaded into the given realm, and deserializes its return value
+    /// The entrypoint is the function passed to `rustyscript.register_entrypoint` by the module, its
+    /// default export, or the runtime's default entrypoint, in that order
+    ///
+    /// Blocks until:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// # Arguments
+    /// * `realm` - The realm the module was loaded into
+    /// * `module_context` - A handle returned by [Runtime::load_module_in_realm]
+    /// * `args` - The arguments to pass to the entrypoint
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the entrypoint (`T`)
+    /// or an error (`Error`) if the module has no entrypoint, the call fails,
+    /// or the result cannot be deserialized.
+    pub fn call_entrypoint_in_realm<T>(
+        &mut self,
+        realm: &crate::RealmHandle,
+        module_context: &crate::RealmModuleHandle,
+        args: &FunctionArguments,
     ) -> Result<T, Error>
     where
         T: deno_core::serde::de::DeserializeOwned,
     {
-        let mut runtime = Runtime::new(runtime_options)?;
-        let module = runtime.load_modules(module, side_modules)?;
-        let value: T = runtime.call_entrypoint(&module, entrypoint_args)?;
-        Ok(value)
+        self.run_async_task(|runtime| async move {
+            let result =
+                realm.call_entrypoint(&mut runtime.inner.deno_runtime, module_context, args)?;
+            let result = runtime.inner.resolve_with_event_loop(result).await?;
+            runtime.inner.decode_value(result)
+        })
     }
 
-    /// Used for blocking functions
-    pub(crate) fn run_async_task<'a, T, F, U>(&'a mut self, f: F) -> Result<T, Error>
+    /// Calls a javascript function in the given realm by its name and deserializes its return value.
+    /// Blocks until:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// # Arguments
+    /// * `realm` - The realm to call the function in
+    /// * `module_context` - Optional handle to a module in the realm to search - if None, or if the search fails, the realm's global context is used
+    /// * `name` - A string representing the name of the javascript function to call.
+    /// * `args` - The arguments to pass to the function
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the function call (`T`)
+    /// or an error (`Error`) if the function cannot be found, if there are issues with
+    /// calling the function, or if the result cannot be deserialized.
+    pub fn call_function_in_realm<T>(
+        &mut self,
+        realm: &crate::RealmHandle,
+        module_context: Option<&crate::RealmModuleHandle>,
+        name: &str,
+        args: &FunctionArguments,
+    ) -> Result<T, Error>
     where
-        U: std::future::Future<Output = Result<T, Error>>,
-        F: FnOnce(&'a mut Runtime) -> U,
+        T: deno_core::serde::de::DeserializeOwned,
     {
-        let timeout = self.options().timeout;
-        let rt = self.tokio_runtime();
-        rt.block_on(async move { tokio::time::timeout(timeout, f(self)).await })?
+        self.run_async_task(|runtime| async move {
+            let result =
+                realm.call_function(&mut runtime.inner.deno_runtime, module_context, name, args)?;
+            let result = runtime.inner.resolve_with_event_loop(result).await?;
+            runtime.inner.decode_value(result)
+        })
+    }
+
+    /// Executes the given module, and returns a handle allowing you to extract values
+    /// And call functions
+    ///
+    /// Blocks until the module has been executed
+    ///
+    /// # Arguments
+    /// * `module` - A `Module` object containing the module's filename and contents.
+    ///
+    /// # Returns
+    /// A `Result` containing a handle for the loaded module
+    /// or an error (`Error`) if there are issues with loading modules, executing the
+    /// module, or if the result cannot be deserialized.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// // Create a module with filename and contents
+    /// use rustyscript::{Runtime, Module, Error};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.js", "export default () => 'test'");
+    /// runtime.load_module(&module);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_module(&mut self, module: &Module) -> Result<ModuleHandle, Error> {
+        self.run_async_task(|runtime| async move { runtime.load_module_async(module).await })
+    }
+
+    /// Executes the given module, and returns a handle allowing you to extract values
+    /// And call functions
+    ///
+    /// Returns a future that resolves to the handle for the loaded module
+    ///
+    /// # Arguments
+    /// * `module` - A `Module` object containing the module's filename and contents.
+    ///
+    /// # Returns
+    /// A `Result` containing a handle for the loaded module
+    /// or an error (`Error`) if there are issues with loading modules, executing the
+    /// module, or if the result cannot be deserialized.
+    ///
+    /// See [Runtime::load_module] for an example
+    pub async fn load_module_async(&mut self, module: &Module) -> Result<ModuleHandle, Error> {
+        self.with_timeout(
+            |runtime| async move { runtime.inner.load_modules(None, vec![module]).await },
+        )
+        .await
+    }
+
+    /// Executes the given module, and returns a handle allowing you to extract values
+    /// And call functions.
+    ///
+    /// Blocks until the module has been executed
+    ///
+    /// This will load 'module' as the main module, and the others as side-modules.
+    /// Only one main module can be loaded per runtime
+    ///
+    /// # Arguments
+    /// * `module` - A `Module` object containing the module's filename and contents.
+    /// * `side_modules` - A set of additional modules to be loaded into memory for use
+    ///
+    /// # Returns
+    /// A `Result` containing a handle for the loaded module
+    /// or an error (`Error`) if there are issues with loading modules, executing the
+    /// module, or if the result cannot be deserialized.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// // Create a module with filename and contents
+    /// use rustyscript::{Runtime, Module, Error};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.js", "export default () => 'test'");
+    /// runtime.load_modules(&module, vec![]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_modules(
+        &mut self,
+        module: &Module,
+        side_modules: Vec<&Module>,
+    ) -> Result<ModuleHandle, Error> {
+        self.run_async_task(move |runtime| async move {
+            runtime.load_modules_async(module, side_modules).await
+        })
+    }
+
+    /// Executes the given module, and returns a handle allowing you to extract values
+    /// And call functions.
+    ///
+    /// Returns a future that resolves to the handle for the loaded module
+    ///
+    /// This will load 'module' as the main module, and the others as side-modules.
+    /// Only one main module can be loaded per runtime
+    ///
+    /// # Arguments
+    /// * `module` - A `Module` object containing the module's filename and contents.
+    /// * `side_modules` - A set of additional modules to be loaded into memory for use
+    ///
+    /// # Returns
+    /// A `Result` containing a handle for the loaded module
+    /// or an error (`Error`) if there are issues with loading modules, executing the
+    /// module, or if the result cannot be deserialized.
+    ///
+    /// See [Runtime::load_modules] for an example
+    pub async fn load_modules_async(
+        &mut self,
+        module: &Module,
+        side_modules: Vec<&Module>,
+    ) -> Result<ModuleHandle, Error> {
+        self.with_timeout(|runtime| async move {
+            runtime.inner.load_modules(Some(module), side_modules).await
+        })
+        .await
+    }
+
+    /// Executes the entrypoint function of a module within the Deno runtime.
+    /// Blocks until:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// # Arguments
+    /// * `module_context` - A handle returned by loading a module into the runtime
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)
+    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
+    /// or the result cannot be deserialized.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{json_args, Runtime, Module, Error};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.js", "export default () => 'test'");
+    /// let module = runtime.load_module(&module)?;
+    ///
+    /// // Run the entrypoint and handle the result
+    /// let value: String = runtime.call_entrypoint(&module, json_args!())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_entrypoint<T>(
+        &mut self,
+        module_context: &ModuleHandle,
+        args: &FunctionArguments,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        self.run_async_task(|runtime| async move {
+            runtime.call_entrypoint_async(module_context, args).await
+        })
+    }
+
+    /// Executes the entrypoint function of a module within the Deno runtime.
+    /// Returns a future that resolves when:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// Note that synchronous functions are run synchronously. Returned promises will be run asynchronously, however.
+    ///
+    /// # Arguments
+    /// * `module_context` - A handle returned by loading a module into the runtime
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)
+    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
+    /// or the result cannot be deserialized.
+    ///
+    /// See [Runtime::call_entrypoint] for an example
+    pub async fn call_entrypoint_async<T>(
+        &mut self,
+        module_context: &ModuleHandle,
+        args: &FunctionArguments,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        if let Some(entrypoint) = module_context.entrypoint() {
+            self.with_timeout(|runtime| async move {
+                let result = runtime
+                    .inner
+                    .call_function_by_ref(Some(module_context), entrypoint.clone(), args)
+                    .await?;
+                let result = runtime.inner.resolve_with_event_loop(result).await?;
+                runtime.inner.decode_value(result)
+            })
+            .await
+        } else {
+            Err(Error::MissingEntrypoint(module_context.module().clone()))
+        }
+    }
+
+    /// Executes the entrypoint function of a module within the Deno runtime.
+    /// Will not attempt to resolve promises, or run the event loop
+    /// Promises can be returned by specifying the return type as [crate::js_value::Promise]
+    /// The event loop should be run using [Runtime::await_event_loop]
+    ///
+    /// # Arguments
+    /// * `module_context` - A handle returned by loading a module into the runtime
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)
+    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
+    /// or the result cannot be deserialized.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{json_args, Runtime, Module, Error};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.js", "export default () => 'test'");
+    /// let module = runtime.load_module(&module)?;
+    ///
+    /// // Run the entrypoint and handle the result
+    /// let value: String = runtime.call_entrypoint_immediate(&module, json_args!())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_entrypoint_immediate<T>(
+        &mut self,
+        module_context: &ModuleHandle,
+        args: &FunctionArguments,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        if let Some(entrypoint) = module_context.entrypoint() {
+            let result = self.run_async_task(|runtime| async move {
+                runtime
+                    .inner
+                    .call_function_by_ref(Some(module_context), entrypoint.clone(), args)
+                    .await
+            })?;
+            self.inner.decode_value(result)
+        } else {
+            Err(Error::MissingEntrypoint(module_context.module().clone()))
+        }
+    }
+
+    /// Loads a module into a new runtime, executes the entry function and returns the
+    /// result of the module's execution, deserialized into the specified Rust type (`T`).
+    ///
+    /// # Arguments
+    /// * `module` - A `Module` object containing the module's filename and contents.
+    /// * `side_modules` - A set of additional modules to be loaded into memory for use
+    /// * `runtime_options` - Options for the creation of the runtime
+    /// * `entrypoint_args` - Arguments to pass to the entrypoint function
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)
+    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
+    /// or the result cannot be deserialized.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// // Create a module with filename and contents
+    /// use rustyscript::{json_args, Runtime, Module, Error};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let module = Module::new("test.js", "export default () => 2");
+    /// let value: usize = Runtime::execute_module(&module, vec![], Default::default(), json_args!())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn execute_module<T>(
+        module: &Module,
+        side_modules: Vec<&Module>,
+        runtime_options: RuntimeOptions,
+        entrypoint_args: &FunctionArguments,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        let mut runtime = Runtime::new(runtime_options)?;
+        let module = runtime.load_modules(module, side_modules)?;
+        let value: T = runtime.call_entrypoint(&module, entrypoint_args)?;
+        Ok(value)
+    }
+
+    /// Used for blocking functions
+    pub(crate) fn run_async_task<'a, T, F, U>(&'a mut self, f: F) -> Result<T, Error>
+    where
+        U: std::future::Future<Output = Result<T, Error>>,
+        F: FnOnce(&'a mut Runtime) -> U,
+    {
+        let rt = self.tokio_runtime();
+        rt.block_on(self.with_timeout(f))
+    }
+
+    /// Runs a task with the runtime's timeout enforced
+    ///
+    /// The tokio timeout covers time spent waiting on the event loop, while the
+    /// watchdog terminates synchronous JS, which never yields back to tokio
+    pub(crate) async fn with_timeout<'a, T, F, U>(&'a mut self, f: F) -> Result<T, Error>
+    where
+        U: std::future::Future<Output = Result<T, Error>>,
+        F: FnOnce(&'a mut Runtime) -> U,
+    {
+        let timeout = self.options().timeout;
+        self.with_deadline(timeout, f).await
+    }
+
+    /// Runs a task with the given timeout enforced, instead of the runtime's
+    pub(crate) async fn with_deadline<'a, T, F, U>(
+        &'a mut self,
+        timeout: std::time::Duration,
+        f: F,
+    ) -> Result<T, Error>
+    where
+        U: std::future::Future<Output = Result<T, Error>>,
+        F: FnOnce(&'a mut Runtime) -> U,
+    {
+        let watchdog = self.inner.watchdog.arm(timeout)?;
+        let result = watchdog
+            .watch(async move {
+                tokio::time::timeout(timeout, f(self))
+                    .await
+                    .unwrap_or_else(|e| Err(e.into()))
+            })
+            .await;
+        watchdog.finish(result)
+    }
+}
+
+#[cfg(test)]
+mod test_runtime {
+    use crate::json_args;
+    use std::time::Duration;
+
+    use super::*;
+    use deno_core::extension;
+
+    #[test]
+    fn test_new() {
+        Runtime::new(Default::default()).expect("Could not create the runtime");
+
+        extension!(test_extension);
+        Runtime::new(RuntimeOptions {
+            extensions: vec![test_extension::init_ops_and_esm()],
+            ..Default::default()
+        })
+        .expect("Could not create runtime with extensions");
+    }
+
+    #[test]
+    fn test_into_arg() {
+        assert_eq!(2, Runtime::into_arg(2));
+        assert_eq!("test", Runtime::into_arg("test"));
+        assert_ne!("test", Runtime::into_arg(2));
+    }
+
+    #[test]
+    fn test_get_value() {
+        let module = Module::new(
+            "test.js",
+            "
+            globalThis.a = 2;
+            export const b = 'test';
+            export const fnc = null;
+        ",
+        );
+
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        let module = runtime
+            .load_modules(&module, vec![])
+            .expect("Could not load module");
+
+        assert_eq!(
+            2,
+            runtime
+                .get_value::<usize>(Some(&module), "a")
+                .expect("Could not find global")
+        );
+        assert_eq!(
+            "test",
+            runtime
+                .get_value::<String>(Some(&module), "b")
+                .expect("Could not find export")
+        );
+        runtime
+            .get_value::<Undefined>(Some(&module), "c")
+            .expect_err("Could not detect null");
+        runtime
+            .get_value::<Undefined>(Some(&module), "d")
+            .expect_err("Could not detect undeclared");
+    }
+
+    #[test]
+    fn test_load_module() {
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        let module = Module::new(
+            "test.js",
+            "
+            export default () => 2;
+        ",
+        );
+        let module = runtime
+            .load_modules(&module, vec![])
+            .expect("Could not load module");
+        assert_ne!(0, module.id());
+
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        let module1 = Module::new(
+            "importme.js",
+            "
+            export const value = 2;
+        ",
+        );
+        let module2 = Module::new(
+            "test.js",
+            "
+            import { value } from './importme.js';
+            rustyscript.register_entrypoint(() => value);
+        ",
+        );
+        runtime
+            .load_module(&module1)
+            .expect("Could not load modules");
+        let module = runtime
+            .load_module(&module2)
+            .expect("Could not load modules");
+        let value: usize = runtime
+            .call_entrypoint(&module, json_args!())
+            .expect("Could not call exported fn");
+        assert_eq!(2, value);
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            timeout: Duration::from_millis(50),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let module = Module::new(
+            "test.js",
+            "
+            await new Promise(r => setTimeout(r, 2000));
+        ",
+        );
+        runtime
+            .load_modules(&module, vec![])
+            .expect_err("Did not interupt after timeout");
+    }
+
+    #[test]
+    fn test_load_modules() {
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        let module = Module::new(
+            "test.js",
+            "
+            rustyscript.register_entrypoint(() => 2);
+        ",
+        );
+        let module = runtime
+            .load_modules(&module, vec![])
+            .expect("Could not load module");
+        assert_ne!(0, module.id());
+
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        let module1 = Module::new(
+            "importme.js",
+            "
+            export const value = 2;
+        ",
+        );
+        let module2 = Module::new(
+            "test.js",
+            "
+            import { value } from './importme.js';
+            rustyscript.register_entrypoint(() => value);
+        ",
+        );
+        let module = runtime
+            .load_modules(&module2, vec![&module1])
+            .expect("Could not load modules");
+        let value: usize = runtime
+            .call_entrypoint(&module, json_args!())
+            .expect("Could not call exported fn");
+        assert_eq!(2, value);
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            timeout: Duration::from_millis(50),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let module = Module::new(
+            "test.js",
+            "
+            await new Promise(r => setTimeout(r, 5000));
+        ",
+        );
+        runtime
+            .load_modules(&module, vec![])
+            .expect_err("Did not interupt after timeout");
+    }
+
+    #[test]
+    fn test_call_entrypoint() {
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        let module = Module::new(
+            "test.js",
+            "
+            rustyscript.register_entrypoint(() => 2);
+        ",
+        );
+        let module = runtime
+            .load_modules(&module, vec![])
+            .expect("Could not load module");
+        let value: usize = runtime
+            .call_entrypoint(&module, json_args!())
+            .expect("Could not call registered fn");
+        assert_eq!(2, value);
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            default_entrypoint: Some("load".to_string()),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let module = Module::new(
+            "test.js",
+            "
+            export const load = () => 2;
+        ",
+        );
+        let module = runtime
+            .load_modules(&module, vec![])
+            .expect("Could not load module");
+        let value: usize = runtime
+            .call_entrypoint(&module, json_args!())
+            .expect("Could not call exported fn");
+        assert_eq!(2, value);
+
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        let module = Module::new(
+            "test.js",
+            "
+            export const load = () => 2;
+        ",
+        );
+        let module = runtime
+            .load_modules(&module, vec![])
+            .expect("Could not load module");
+        runtime
+            .call_entrypoint::<Undefined>(&module, json_args!())
+            .expect_err("Did not detect no entrypoint");
+    }
+
+    #[test]
+    fn test_execute_module() {
+        let module = Module::new(
+            "test.js",
+            "
+            rustyscript.register_entrypoint(() => 2);
+        ",
+        );
+        let value: usize =
+            Runtime::execute_module(&module, vec![], Default::default(), json_args!())
+                .expect("Could not exec module");
+        assert_eq!(2, value);
+
+        let module = Module::new(
+            "test.js",
+            "
+            function load() { return 2; }
+        ",
+        );
+        Runtime::execute_module::<Undefined>(&module, vec![], Default::default(), json_args!())
+            .expect_err("Could not detect no entrypoint");
+    }
+
+    #[test]
+    fn call_function() {
+        let module = Module::new(
+            "test.js",
+            "
+            globalThis.fna = (i) => i;
+            export function fnb() { return 'test'; }
+            export const fnc = 2;
+            export const fne = () => {};
+        ",
+        );
+
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        let module = runtime
+            .load_modules(&module, vec![])
+            .expect("Could not load module");
+
+        let result: usize = runtime
+            .call_function(Some(&module), "fna", json_args!(2))
+            .expect("Could not call global");
+        assert_eq!(2, result);
+
+        let result: String = runtime
+            .call_function(Some(&module), "fnb", json_args!())
+            .expect("Could not call export");
+        assert_eq!("test", result);
+
+        runtime
+            .call_function::<Undefined>(Some(&module), "fnc", json_args!())
+            .expect_err("Did not detect non-function");
+        runtime
+            .call_function::<Undefined>(Some(&module), "fnd", json_args!())
+            .expect_err("Did not detect undefined");
+        runtime
+            .call_function::<Undefined>(Some(&module), "fne", json_args!())
+            .expect("Did not allow undefined return");
+    }
+
+    #[test]
+    fn test_timeout_sync_js() {
+        let module = Module::new(
+            "test.js",
+            "
+            export const spin = () => { while(true) {} };
+        ",
+        );
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            timeout: Duration::from_millis(100),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let module = runtime.load_module(&module).expect("Could not load module");
+
+        let e = runtime
+            .eval::<Undefined>("while(true) {}")
+            .expect_err("Did not interrupt eval");
+        assert!(matches!(e, Error::Timeout(_)));
+
+        let e = runtime
+            .call_function::<Undefined>(Some(&module), "spin", json_args!())
+            .expect_err("Did not interrupt blocking call");
+        assert!(matches!(e, Error::Timeout(_)));
+
+        let e = runtime
+            .call_function_immediate::<Undefined>(Some(&module), "spin", json_args!())
+            .expect_err("Did not interrupt immediate call");
+        assert!(matches!(e, Error::Timeout(_)));
+
+        let tokio = runtime.tokio_runtime();
+        let e = tokio
+            .block_on(runtime.call_function_async::<Undefined>(Some(&module), "spin", json_args!()))
+            .expect_err("Did not interrupt async call");
+        assert!(matches!(e, Error::Timeout(_)));
+
+        // The runtime must remain usable after being interrupted
+        let value: usize = runtime.eval("2 + 2").expect("Runtime was left terminated");
+        assert_eq!(4, value);
+    }
+
+    #[test]
+    fn test_heap_exhausted() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            max_heap_size: Some(20 * 1024 * 1024),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        let e = runtime
+            .eval::<Undefined>("let s = []; while(true) { s.push(new Array(1000).fill('Hello')); }")
+            .expect_err("Did not stop at the heap limit");
+        assert!(matches!(e, Error::HeapExhausted));
+        assert!(runtime.is_poisoned());
+
+        runtime
+            .eval::<usize>("2 + 2")
+            .expect_err("Poisoned runtime accepted a call");
+    }
+
+    #[test]
+    fn test_call_function_typed() {
+        #[derive(serde::Serialize)]
+        struct Point {
+            x: i64,
+            y: i64,
+        }
+
+        let module = Module::new(
+            "test.js",
+            "
+            export function scale(p, factor, label) { return `${label}:${(p.x + p.y) * factor}`; }
+            export function count() { return arguments.length; }
+        ",
+        );
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        let handle = runtime.load_module(&module).expect("Could not load module");
+
+        let value: String = runtime
+            .call_function_typed(Some(&handle), "scale", &(Point { x: 1, y: 2 }, 3, "sum"))
+            .expect("Could not call function");
+        assert_eq!("sum:9", value);
+
+        let value: usize = runtime
+            .call_function_typed(Some(&handle), "count", &())
+            .expect("Could not call function");
+        assert_eq!(0, value);
+
+        // JSON arguments still work
+        let value: usize = runtime
+            .call_function_typed(Some(&handle), "count", json_args!(1, 2))
+            .expect("Could not call function");
+        assert_eq!(2, value);
+
+        let function: Function = runtime
+            .get_value(Some(&handle), "count")
+            .expect("Could not get function");
+        let value: usize = runtime
+            .call_stored_function_typed(Some(&handle), &function, &(1, "a", true))
+            .expect("Could not call function");
+        assert_eq!(3, value);
+    }
+
+    #[test]
+    fn test_call_function_with() {
+        let module = Module::new(
+            "test.js",
+            "
+            export const value = 2;
+            export function this_value() { return this.value; }
+            export function spin() { while(true) {} }
+            export function schedule() {
+                setTimeout(() => { globalThis.drained = true; }, 50);
+                return 1;
+            }
+        ",
+        );
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            timeout: Duration::from_millis(100),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let handle = runtime.load_module(&module).expect("Could not load module");
+
+        // Default options behave like call_function
+        let value: usize = runtime
+            .call_function_with(
+                Some(&handle),
+                "this_value",
+                json_args!(),
+                &Default::default(),
+            )
+            .expect("Could not call function");
+        assert_eq!(2, value);
+
+        // Explicit `this`
+        let this = runtime
+            .eval("({ value: 3 })")
+            .expect("Could not create object");
+        let options = CallOptions {
+            this: Some(this),
+            ..Default::default()
+        };
+        let value: usize = runtime
+            .call_function_with(Some(&handle), "this_value", json_args!(), &options)
+            .expect("Could not call function");
+        assert_eq!(3, value);
+
+        // The per-call timeout replaces the runtime's, in both directions
+        let options = CallOptions {
+            timeout: Some(Duration::from_millis(10)),
+            ..Default::default()
+        };
+        let e = runtime
+            .call_function_with::<Undefined>(Some(&handle), "spin", json_args!(), &options)
+            .expect_err("Did not time out");
+        assert!(matches!(e, Error::Timeout(_)));
+
+        // Draining the event loop runs timers before returning
+        #[cfg(any(feature = "web", feature = "web_stub"))]
+        {
+            let options = CallOptions {
+                timeout: Some(Duration::from_millis(500)),
+                event_loop: EventLoopPolicy::Drain,
+                ..Default::default()
+            };
+            let value: usize = runtime
+                .call_function_with(Some(&handle), "schedule", json_args!(), &options)
+                .expect("Could not call function");
+            assert_eq!(1, value);
+
+            let drained: bool = runtime
+                .eval("globalThis.drained")
+                .expect("Timer did not run");
+            assert!(drained);
+        }
     }
-}
 
-#[cfg(test)]
-mod test_runtime {
-    use crate::json_args;
-    use std::time::Duration;
+    #[test]
+    #[cfg(any(feature = "web", feature = "web_stub"))]
+    fn test_bounded_event_loop() {
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        assert!(!runtime
+            .poll_event_loop_once()
+            .expect("Could not poll event loop"));
 
-    use super::*;
-    use deno_core::extension;
+        runtime
+            .eval::<Undefined>(
+                "globalThis.ticks = 0; globalThis.timer = setInterval(() => { ticks++; }, 10); undefined",
+            )
+            .expect("Could not start timer");
+
+        // The interval never finishes, so work always remains
+        assert!(runtime
+            .run_event_loop_for(Duration::from_millis(100))
+            .expect("Could not run event loop"));
+        let ticks: usize = runtime.eval("ticks").expect("Could not get ticks");
+        assert!(ticks > 0);
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(runtime
+            .poll_event_loop_once()
+            .expect("Could not poll event loop"));
+        let more_ticks: usize = runtime.eval("ticks").expect("Could not get ticks");
+        assert!(more_ticks > ticks);
+
+        // Once the interval is cleared, the event loop completes
+        runtime
+            .eval::<Undefined>("clearInterval(timer); undefined")
+            .expect("Could not stop timer");
+        assert!(!runtime
+            .run_event_loop_for(Duration::from_millis(100))
+            .expect("Could not run event loop"));
+
+        // A slice longer than the runtime timeout returns instead of timing out
+        let mut runtime = Runtime::new(RuntimeOptions {
+            timeout: Duration::from_millis(50),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        runtime
+            .eval::<Undefined>("setInterval(() => {}, 10); undefined")
+            .expect("Could not start timer");
+        assert!(runtime
+            .run_event_loop_for(Duration::from_millis(500))
+            .expect("Long slice should not time out"));
+    }
 
     #[test]
-    fn test_new() {
-        Runtime::new(Default::default()).expect("Could not create the runtime");
+    fn test_unhandled_rejection() {
+        let module = Module::new(
+            "test.js",
+            "
+            Promise.reject(new Error('oops'));
+            export function f() { return 2; }
+        ",
+        );
 
-        extension!(test_extension);
-        Runtime::new(RuntimeOptions {
-            extensions: vec![test_extension::init_ops_and_esm()],
+        // Reported to the callback, then ignored
+        let rejections = Rc::new(std::cell::RefCell::new(vec![]));
+        let rejections_ref = rejections.clone();
+        let mut runtime = Runtime::new(RuntimeOptions {
+            on_unhandled_rejection: Some(Box::new(move |e| {
+                rejections_ref.borrow_mut().push(e);
+            })),
+            unhandled_rejection_policy: crate::UnhandledRejectionPolicy::Ignore,
             ..Default::default()
         })
-        .expect("Could not create runtime with extensions");
+        .expect("Could not create the runtime");
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        let value: usize = runtime
+            .call_function(Some(&handle), "f", json_args!())
+            .expect("Rejection was not ignored");
+        assert_eq!(2, value);
+
+        let rejections = rejections.borrow();
+        assert_eq!(1, rejections.len());
+        match &rejections[0] {
+            Error::JsError(e) => {
+                assert!(e.exception_message.contains("oops"));
+                assert!(e.stack.is_some());
+            }
+            e => panic!("Unexpected error: {e:?}"),
+        }
+
+        // By default, the rejection fails the next call
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        runtime
+            .load_module(&module)
+            .expect_err("Rejection was ignored");
     }
 
     #[test]
-    fn test_into_arg() {
-        assert_eq!(2, Runtime::into_arg(2));
-        assert_eq!("test", Runtime::into_arg("test"));
-        assert_ne!("test", Runtime::into_arg(2));
+    fn test_eval_named() {
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        let value: usize = runtime
+            .eval_named("first.js", "2 + 2")
+            .expect("Could not eval");
+        assert_eq!(4, value);
+
+        let e = runtime
+            .eval_named::<Undefined>("second.js", "1 + 1;\nthrow new Error('oops')")
+            .expect_err("Did not throw");
+        match e {
+            Error::JsError(e) => {
+                assert!(e.stack.unwrap_or_default().contains("second.js:2"));
+            }
+            e => panic!("Unexpected error: {e:?}"),
+        }
     }
 
     #[test]
-    fn test_get_value() {
+    fn test_eval_async() {
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        let tokio_runtime = runtime.tokio_runtime();
+
+        // Expressions return their value
+        let value: usize = tokio_runtime
+            .block_on(runtime.eval_async("await Promise.resolve(2) + 2;"))
+            .expect("Could not eval");
+        assert_eq!(4, value);
+
+        // Statements run to completion
+        tokio_runtime
+            .block_on(
+                runtime.eval_async::<Undefined>(
+                    "const v = await Promise.resolve(5); globalThis.v = v;",
+                ),
+            )
+            .expect("Could not eval");
+        let value: usize = runtime.eval("v").expect("Could not get value");
+        assert_eq!(5, value);
+
+        // Rejections are returned as errors
+        tokio_runtime
+            .block_on(runtime.eval_async::<Undefined>("await Promise.reject(new Error('oops'))"))
+            .expect_err("Did not reject");
+
+        // Syntax errors are reported as they are
+        let e = tokio_runtime
+            .block_on(runtime.eval_async::<Undefined>("let x = ;"))
+            .expect_err("Did not fail to compile");
+        assert!(e.to_string().contains("SyntaxError"));
+
+        // Named code shows up in stack traces
+        let e = tokio_runtime
+            .block_on(
+                runtime
+                    .eval_async_named::<Undefined>("repl.js", "await 1;\nthrow new Error('oops')"),
+            )
+            .expect_err("Did not throw");
+        match e {
+            Error::JsError(e) => {
+                assert!(e.stack.unwrap_or_default().contains("repl.js:2"));
+            }
+            e => panic!("Unexpected error: {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_global_values() {
+        #[derive(serde::Serialize)]
+        struct Config {
+            name: String,
+            retries: usize,
+        }
+
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        let config = Config {
+            name: "test".to_string(),
+            retries: 3,
+        };
+        runtime
+            .set_global_value("config", &config)
+            .expect("Could not set value");
+
+        let module = Module::new("test.js", "export const retries = config.retries;");
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        let value: usize = runtime
+            .get_value(Some(&handle), "retries")
+            .expect("Could not get value");
+        assert_eq!(3, value);
+        let name: String = runtime.eval("config.name").expect("Could not get value");
+        assert_eq!("test", name);
+
+        // Read-only values cannot be reassigned, and are hidden
+        runtime
+            .define_global_value("VERSION", &1, crate::PropertyKind::ReadOnly)
+            .expect("Could not define value");
+        let value: usize = runtime
+            .eval("VERSION = 2; VERSION")
+            .expect("Could not get value");
+        assert_eq!(1, value);
+        let visible: bool = runtime
+            .eval("Object.keys(globalThis).includes('VERSION')")
+            .expect("Could not get keys");
+        assert!(!visible);
+
+        runtime
+            .define_global_value("hidden", &2, crate::PropertyKind::NonEnumerable)
+            .expect("Could not define value");
+        let value: usize = runtime
+            .eval("hidden = 3; hidden")
+            .expect("Could not get value");
+        assert_eq!(3, value);
+
+        runtime
+            .delete_global_value("config")
+            .expect("Could not delete value");
+        let deleted: bool = runtime
+            .eval("typeof config === 'undefined'")
+            .expect("Could not check value");
+        assert!(deleted);
+    }
+
+    #[test]
+    fn test_reset() {
         let module = Module::new(
             "test.js",
             "
-            globalThis.a = 2;
-            export const b = 'test';
-            export const fnc = null;
+            globalThis.loads = (globalThis.loads ?? 0) + 1;
+            export const loads = globalThis.loads;
+            export const url = import.meta.url;
+            export const fail = () => { throw new Error('fail'); };
+            rustyscript.register_entrypoint(() => loads);
         ",
         );
 
         let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
-        let module = runtime
-            .load_modules(&module, vec![])
-            .expect("Could not load module");
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        runtime
+            .eval::<Undefined>("globalThis.leak = 1; globalThis.console = null; var hoisted = 2;")
+            .expect("Could not set globals");
 
-        assert_eq!(
-            2,
-            runtime
-                .get_value::<usize>(Some(&module), "a")
-                .expect("Could not find global")
-        );
-        assert_eq!(
-            "test",
-            runtime
-                .get_value::<String>(Some(&module), "b")
-                .expect("Could not find export")
-        );
+        runtime.reset().expect("Could not reset runtime");
+
+        let leaked: bool = runtime
+            .eval("'leak' in globalThis || globalThis.hoisted !== undefined")
+            .expect("Could not check globals");
+        assert!(!leaked);
+        let console: bool = runtime
+            .eval("typeof console.log === 'function'")
+            .expect("Could not check globals");
+        assert!(console);
+
+        // The module is run again from a clean state
+        let handle2 = runtime.load_module(&module).expect("Could not load module");
+        let loads: usize = runtime
+            .get_value(Some(&handle2), "loads")
+            .expect("Could not get value");
+        assert_eq!(1, loads);
+        let loads: usize = runtime
+            .call_entrypoint(&handle2, json_args!())
+            .expect("Could not call entrypoint");
+        assert_eq!(1, loads);
+        assert_ne!(handle.id(), handle2.id());
+
+        // The reloaded module keeps its own specifier in import.meta and errors
+        let url: String = runtime
+            .get_value(Some(&handle), "url")
+            .expect("Could not get value");
+        let url2: String = runtime
+            .get_value(Some(&handle2), "url")
+            .expect("Could not get value");
+        assert_eq!(url, url2);
+        let e = runtime
+            .call_function::<Undefined>(Some(&handle2), "fail", json_args!())
+            .unwrap_err();
+        assert!(!e.to_string().contains("rustyscript-"));
+
+        // Resets are refused until pending async ops settle
         runtime
-            .get_value::<Undefined>(Some(&module), "c")
-            .expect_err("Could not detect null");
+            .register_async_function(
+                "wait",
+                crate::async_callback!(|ms: u64| async move {
+                    tokio::time::sleep(Duration::from_millis(ms)).await;
+                    Ok::<u64, Error>(ms)
+                }),
+            )
+            .expect("Could not register function");
         runtime
-            .get_value::<Undefined>(Some(&module), "d")
-            .expect_err("Could not detect undeclared");
+            .eval::<Undefined>("rustyscript.async_functions.wait(50); undefined")
+            .expect("Could not call function");
+        assert!(runtime.reset().is_err());
+        assert!(!runtime
+            .run_event_loop_for(Duration::from_secs(1))
+            .expect("Could not run event loop"));
+        runtime.reset().expect("Could not reset runtime");
+
+        // Pending timers are cancelled
+        #[cfg(any(feature = "web", feature = "web_stub"))]
+        {
+            runtime
+                .eval::<Undefined>("setInterval(() => {}, 10); undefined")
+                .expect("Could not start timer");
+            runtime.reset().expect("Could not reset runtime");
+            assert_eq!(0, runtime.metrics().pending_timers);
+        }
     }
 
     #[test]
-    fn test_load_module() {
+    fn test_metrics() {
         let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        runtime
+            .register_function(
+                "add",
+                crate::sync_callback!(|a: i64, b: i64| Ok::<i64, Error>(a + b)),
+            )
+            .expect("Could not register function");
+        runtime
+            .register_function("unused", |_| Ok(serde_json::Value::Null))
+            .expect("Could not register function");
+
         let module = Module::new(
             "test.js",
             "
-            export default () => 2;
+            rustyscript.functions.add(1, 2);
+            rustyscript.functions.add(3, 4);
         ",
         );
-        let module = runtime
-            .load_modules(&module, vec![])
-            .expect("Could not load module");
-        assert_ne!(0, module.id());
+        runtime.load_module(&module).expect("Could not load module");
+
+        let metrics = runtime.metrics();
+        assert!(metrics.used_heap_size > 0);
+        assert!(metrics.total_heap_size >= metrics.used_heap_size);
+        assert_eq!(1, metrics.loaded_modules);
+        assert_eq!(2, metrics.functions["add"].calls);
+        assert_eq!(0, metrics.functions["unused"].calls);
+        assert_eq!(0, metrics.pending_ops);
+
+        // Modules that fail to load are not counted
+        let module = Module::new("broken.js", "throw new Error('broken');");
+        runtime
+            .load_module(&module)
+            .expect_err("Loaded a module that throws");
+        assert_eq!(1, runtime.metrics().loaded_modules);
 
+        #[cfg(any(feature = "web", feature = "web_stub"))]
+        {
+            runtime
+                .eval::<Undefined>("setTimeout(() => {}, 100000); undefined")
+                .expect("Could not start timer");
+            assert_eq!(1, runtime.metrics().pending_timers);
+        }
+    }
+
+    #[test]
+    fn test_collect_garbage() {
         let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
-        let module1 = Module::new(
-            "importme.js",
-            "
-            export const value = 2;
-        ",
-        );
-        let module2 = Module::new(
-            "test.js",
-            "
-            import { value } from './importme.js';
-            rustyscript.register_entrypoint(() => value);
-        ",
-        );
         runtime
-            .load_module(&module1)
-            .expect("Could not load modules");
-        let module = runtime
-            .load_module(&module2)
-            .expect("Could not load modules");
-        let value: usize = runtime
-            .call_entrypoint(&module, json_args!())
-            .expect("Could not call exported fn");
+            .eval::<Undefined>(
+                "globalThis.data = Array.from({ length: 200000 }, (_, i) => ({ i })); undefined",
+            )
+            .expect("Could not allocate");
+        let before = runtime.metrics().used_heap_size;
+
+        runtime
+            .eval::<Undefined>("globalThis.data = null; undefined")
+            .expect("Could not release");
+        runtime.collect_garbage();
+        let after = runtime.metrics().used_heap_size;
+        assert!(after < before);
+
+        // These are hints, but must leave the runtime usable
+        runtime.notify_low_memory();
+        runtime.idle_notification(Duration::from_millis(10));
+        let value: usize = runtime.eval("1 + 1").expect("Could not eval");
         assert_eq!(2, value);
+    }
+
+    #[test]
+    fn test_function_capabilities() {
+        let mut capabilities = crate::FunctionCapabilities::default();
+        capabilities
+            .grant("plugin_a.js", &["db_read"])
+            .expect("Could not grant function");
+        capabilities.grant_all_modules(&["log"]);
 
+        let denials = Rc::new(std::cell::RefCell::new(Vec::new()));
+        let denials_ = denials.clone();
         let mut runtime = Runtime::new(RuntimeOptions {
-            timeout: Duration::from_millis(50),
+            function_capabilities: Some(capabilities),
+            on_capability_denied: Some(Box::new(move |denial| {
+                denials_.borrow_mut().push(denial.clone());
+            })),
             ..Default::default()
         })
         .expect("Could not create the runtime");
-        let module = Module::new(
-            "test.js",
-            "
-            await new Promise(r => setTimeout(r, 2000));
-        ",
-        );
         runtime
-            .load_modules(&module, vec![])
-            .expect_err("Did not interupt after timeout");
+            .register_function("db_read", |_| Ok(serde_json::Value::from("data")))
+            .expect("Could not register function");
+        runtime
+            .register_function("log", |_| Ok(serde_json::Value::Null))
+            .expect("Could not register function");
+
+        let source = "
+            rustyscript.functions.log();
+            export function read() {
+                try {
+                    return rustyscript.functions.db_read();
+                } catch (e) {
+                    return e instanceof rustyscript.CapabilityError ? e.name : 'other';
+                }
+            }
+        ";
+        let plugin_a = Module::new("plugin_a.js", source);
+        let plugin_b = Module::new("plugin_b.js", source);
+        let a = runtime
+            .load_module(&plugin_a)
+            .expect("Could not load module");
+        let b = runtime
+            .load_module(&plugin_b)
+            .expect("Could not load module");
+
+        let value: String = runtime
+            .call_function(Some(&a), "read", json_args!())
+            .expect("Could not call function");
+        assert_eq!("data", value);
+
+        let value: String = runtime
+            .call_function(Some(&b), "read", json_args!())
+            .expect("Could not call function");
+        assert_eq!("CapabilityError", value);
+
+        let denials = denials.borrow();
+        assert_eq!(1, denials.len());
+        assert_eq!("db_read", denials[0].function);
+        assert!(denials[0]
+            .module
+            .as_deref()
+            .is_some_and(|m| m.ends_with("plugin_b.js")));
+    }
+
+    #[test]
+    fn test_register_typed_function() {
+        #[derive(serde::Serialize)]
+        struct Point {
+            x: i64,
+            y: i64,
+        }
+
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        runtime
+            .register_typed_function("point", |x: i64, y: i64| Ok(Point { x, y }))
+            .expect("Could not register function");
+        runtime
+            .register_typed_function("label", |name: String, count: Option<u32>| {
+                Ok(format!("{name}:{}", count.unwrap_or_default()))
+            })
+            .expect("Could not register function");
+
+        let sum: i64 = runtime
+            .eval("const p = rustyscript.functions.point(1, 2); p.x + p.y")
+            .expect("Could not eval");
+        assert_eq!(3, sum);
+
+        let label: String = runtime
+            .eval("rustyscript.functions.label('a', null)")
+            .expect("Could not eval");
+        assert_eq!("a:0", label);
+
+        // Trailing optional arguments can be left out
+        let label: String = runtime
+            .eval("rustyscript.functions.label('b')")
+            .expect("Could not eval");
+        assert_eq!("b:0", label);
+
+        let errors: Vec<String> = runtime
+            .eval(
+                "[
+                    () => rustyscript.functions.point(1),
+                    () => rustyscript.functions.point('a', 2),
+                    () => rustyscript.functions.point(1, 2, 3),
+                ].map(f => { try { f(); return 'none'; } catch (e) { return e.name; } })",
+            )
+            .expect("Could not eval");
+        assert_eq!(vec!["TypeError", "TypeError", "TypeError"], errors);
+    }
+
+    #[test]
+    fn test_function_callbacks() {
+        use crate::{js_value::Function, AsyncFunctionContext, FunctionContext};
+
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        runtime
+            .register_typed_function(
+                "for_each_row",
+                |context: &mut FunctionContext, rows: Vec<String>, callback: Function| {
+                    let mut results = Vec::new();
+                    for row in rows {
+                        results.push(context.call::<String, _>(&callback, &(row,))?);
+                    }
+                    Ok(results)
+                },
+            )
+            .expect("Could not register function");
+        runtime
+            .register_typed_function("upper", |value: String| Ok(value.to_uppercase()))
+            .expect("Could not register function");
+        runtime
+            .register_typed_async_function(
+                "sum_later",
+                |context: AsyncFunctionContext, values: Vec<i64>, callback: Function| async move {
+                    let mut total = 0;
+                    for value in values {
+                        total += context.call::<i64, _>(&callback, &(value,)).await?;
+                    }
+                    Ok(total)
+                },
+            )
+            .expect("Could not register function");
+
+        // Callbacks can call back into rust
+        let rows: Vec<String> = runtime
+            .eval("rustyscript.functions.for_each_row(['a', 'b'], row => rustyscript.functions.upper(row))")
+            .expect("Could not eval");
+        assert_eq!(vec!["A", "B"], rows);
+
+        // Errors thrown by callbacks reach the caller
+        let message: String = runtime
+            .eval("try { rustyscript.functions.for_each_row(['a'], () => { throw new Error('bad row'); }) } catch (e) { e.message }")
+            .expect("Could not eval");
+        assert!(message.contains("bad row"));
+
+        // Async functions can call callbacks later, including async ones
+        let tokio_runtime = runtime.tokio_runtime();
+        let total: i64 = tokio_runtime
+            .block_on(runtime.eval_async(
+                "await rustyscript.async_functions.sum_later([1, 2, 3], async value => value * 2)",
+            ))
+            .expect("Could not eval");
+        assert_eq!(12, total);
+    }
+
+    #[test]
+    fn test_function_callback_timeout() {
+        use crate::{js_value::Function, FunctionContext};
+
+        let timed_out = Rc::new(std::cell::Cell::new(false));
+        let timed_out_ = timed_out.clone();
+        let mut runtime = Runtime::new(RuntimeOptions {
+            timeout: Duration::from_millis(200),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        runtime
+            .register_typed_function(
+                "run",
+                move |context: &mut FunctionContext, callback: Function| {
+                    let result = context.call::<serde_json::Value, _>(&callback, &());
+                    timed_out_.set(matches!(result, Err(Error::Timeout(_))));
+                    result.map(|_| ())
+                },
+            )
+            .expect("Could not register function");
+
+        // The callback sees the same error as the call it belongs to
+        let e = runtime
+            .eval::<Undefined>("rustyscript.functions.run(() => { while (true) {} })")
+            .unwrap_err();
+        assert!(matches!(e, Error::Timeout(_)), "{e:?}");
+        assert!(timed_out.get());
+    }
+
+    #[test]
+    fn test_register_class() {
+        use crate::{ClassBuilder, HostClass};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+        struct Connection {
+            queries: Vec<String>,
+        }
+
+        impl Drop for Connection {
+            fn drop(&mut self) {
+                DROPPED.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        impl HostClass for Connection {
+            const NAME: &'static str = "Connection";
+            type Args = (String,);
+
+            fn construct((url,): Self::Args) -> Result<Self, Error> {
+                if url.is_empty() {
+                    return Err(Error::Runtime("Missing url".to_string()));
+                }
+                Ok(Self {
+                    queries: Vec::new(),
+                })
+            }
+
+            fn members(class: &mut ClassBuilder<Self>) {
+                class
+                    .method("query", |connection: &mut Self, sql: String| {
+                        connection.queries.push(sql);
+                        Ok(connection.queries.len())
+                    })
+                    .getter("count", |connection| Ok(connection.queries.len()));
+            }
+        }
+
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        runtime
+            .register_class::<Connection>()
+            .expect("Could not register class");
+
+        let count: usize = runtime
+            .eval(
+                "
+                const connection = new rustyscript.classes.Connection('db://test');
+                connection.query('select 1');
+                connection.query('select 2');
+                connection.count
+            ",
+            )
+            .expect("Could not eval");
+        assert_eq!(2, count);
+
+        let errors: Vec<String> = runtime
+            .eval(
+                "[
+                    () => new rustyscript.classes.Connection(''),
+                    () => new rustyscript.classes.Connection(1),
+                    () => rustyscript.classes.Connection.prototype.query.call({}, 'x'),
+                ].map(f => { try { f(); return 'none'; } catch (e) { return e.name; } })",
+            )
+            .expect("Could not eval");
+        assert_eq!(vec!["Error", "TypeError", "TypeError"], errors);
+
+        let missing: bool = runtime
+            .eval("rustyscript.classes.Missing === undefined")
+            .expect("Could not eval");
+        assert!(missing);
+
+        // Instances are freed once their JS object is collected
+        runtime
+            .eval::<Undefined>("(() => { new rustyscript.classes.Connection('db://temp'); })()")
+            .expect("Could not eval");
+        for _ in 0..10 {
+            runtime.collect_garbage();
+            runtime
+                .block_on_event_loop(Default::default())
+                .expect("Could not run event loop");
+            if DROPPED.load(Ordering::SeqCst) > 0 {
+                break;
+            }
+        }
+        assert_eq!(1, DROPPED.load(Ordering::SeqCst));
+
+        // Scripts can only use the drop op to free instances of the class they name
+        struct Document;
+        impl HostClass for Document {
+            const NAME: &'static str = "Document";
+            type Args = ();
+
+            fn construct(_: Self::Args) -> Result<Self, Error> {
+                Ok(Self)
+            }
+
+            fn members(class: &mut ClassBuilder<Self>) {
+                class.method("title", |_: &mut Self| Ok("doc"));
+            }
+        }
+        runtime
+            .register_class::<Document>()
+            .expect("Could not register class");
+        let title: String = runtime
+            .eval(
+                "
+                const doc = new rustyscript.classes.Document();
+                const [rid] = Object.entries(Deno.core.resources()).find(([, name]) => name === 'Document');
+                Deno.core.ops.op_host_object_drop('Connection', Number(rid));
+                Deno.core.ops.op_host_object_drop('Missing', Number(rid));
+                doc.title()
+            ",
+            )
+            .expect("Could not eval");
+        assert_eq!("doc", title);
+
+        // Registering a class again replaces the one already looked up
+        struct ConnectionV2;
+        impl HostClass for ConnectionV2 {
+            const NAME: &'static str = "Connection";
+            type Args = (String,);
+
+            fn construct(_: Self::Args) -> Result<Self, Error> {
+                Ok(Self)
+            }
+
+            fn members(class: &mut ClassBuilder<Self>) {
+                class.method("version", |_: &mut Self| Ok(2));
+            }
+        }
+        runtime
+            .register_class::<ConnectionV2>()
+            .expect("Could not register class");
+        let version: usize = runtime
+            .eval("new rustyscript.classes.Connection('db://v2').version()")
+            .expect("Could not eval");
+        assert_eq!(2, version);
+    }
+
+    #[test]
+    fn test_register_class_capabilities() {
+        use crate::{ClassBuilder, FunctionCapabilities, HostClass};
+
+        struct Counter(usize);
+        impl HostClass for Counter {
+            const NAME: &'static str = "Counter";
+            type Args = ();
+
+            fn construct(_: Self::Args) -> Result<Self, Error> {
+                Ok(Self(0))
+            }
+
+            fn members(class: &mut ClassBuilder<Self>) {
+                class
+                    .method("increment", |counter: &mut Self| {
+                        counter.0 += 1;
+                        Ok(counter.0)
+                    })
+                    .method("reset", |counter: &mut Self| {
+                        counter.0 = 0;
+                        Ok(())
+                    });
+            }
+        }
+
+        let events = Rc::new(std::cell::RefCell::new(Vec::new()));
+        let events_ = events.clone();
+        let mut capabilities = FunctionCapabilities::default();
+        capabilities.grant_all_modules(&["Counter.constructor", "Counter.increment"]);
+        let mut runtime = Runtime::new(RuntimeOptions {
+            function_capabilities: Some(capabilities),
+            audit_sink: Some(Box::new(move |event: crate::AuditEvent| {
+                events_.borrow_mut().push(event);
+            })),
+            lockdown: Some(Default::default()),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        runtime
+            .register_class::<Counter>()
+            .expect("Could not register class");
+
+        let results: Vec<String> = runtime
+            .eval(
+                "
+                const counter = new rustyscript.classes.Counter();
+                [
+                    () => counter.increment(),
+                    () => counter.reset(),
+                ].map(f => { try { return String(f()); } catch (e) { return e.name; } })
+            ",
+            )
+            .expect("Could not eval");
+        assert_eq!(vec!["1", "CapabilityError"], results);
+
+        let calls: Vec<(String, bool)> = events
+            .borrow()
+            .iter()
+            .filter_map(|event| match &event.kind {
+                crate::AuditEventKind::FunctionCall {
+                    function, allowed, ..
+                } => Some((function.clone(), *allowed)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("Counter.constructor".to_string(), true),
+                ("Counter.increment".to_string(), true),
+                ("Counter.reset".to_string(), false),
+            ],
+            calls
+        );
+
+        // Classes are shared between scripts, so they cannot be patched once locked down
+        let patched: bool = runtime
+            .eval(
+                "
+                const Counter = rustyscript.classes.Counter;
+                Counter.prototype.increment = () => 100;
+                Counter.patched = true;
+                Counter.prototype.increment.patched = true;
+                [Counter, Counter.prototype, Counter.prototype.increment].some(o => !Object.isFrozen(o))
+                    || 'patched' in Counter
+                    || new Counter().increment() !== 1
+            ",
+            )
+            .expect("Could not eval");
+        assert!(!patched);
     }
 
     #[test]
-    fn test_load_modules() {
+    fn test_register_host_module() {
         let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        runtime
+            .register_typed_function("readConfig", |key: String| Ok(format!("{key}=1")))
+            .expect("Could not register function");
+        runtime
+            .register_typed_async_function(
+                "fetchConfig",
+                |_: crate::AsyncFunctionContext| async move { Ok(2) },
+            )
+            .expect("Could not register function");
+        runtime
+            .register_host_module("config", &["readConfig", "fetchConfig"])
+            .expect("Could not register host module");
+
+        runtime
+            .register_host_module("missing", &["notRegistered"])
+            .expect_err("Registered a host module with an unknown function");
+
         let module = Module::new(
             "test.js",
             "
-            rustyscript.register_entrypoint(() => 2);
+            import { readConfig, fetchConfig } from 'host:config';
+            export const value = readConfig('port');
+            export const later = await fetchConfig();
         ",
         );
-        let module = runtime
-            .load_modules(&module, vec![])
-            .expect("Could not load module");
-        assert_ne!(0, module.id());
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        let value: String = runtime
+            .get_value(Some(&handle), "value")
+            .expect("Could not get value");
+        assert_eq!("port=1", value);
+        let later: i64 = runtime
+            .get_value(Some(&handle), "later")
+            .expect("Could not get value");
+        assert_eq!(2, later);
 
-        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
-        let module1 = Module::new(
-            "importme.js",
+        // Missing names fail when the module is linked, before any of it runs
+        let module = Module::new(
+            "missing_name.js",
             "
-            export const value = 2;
+            globalThis.ran = true;
+            import { writeConfig } from 'host:config';
         ",
         );
-        let module2 = Module::new(
-            "test.js",
+        runtime
+            .load_module(&module)
+            .expect_err("Imported a function that is not in the module");
+        let ran: bool = runtime
+            .eval("globalThis.ran === true")
+            .expect("Could not eval");
+        assert!(!ran);
+
+        let module = Module::new("missing_module.js", "import { a } from 'host:other';");
+        runtime
+            .load_module(&module)
+            .expect_err("Imported a host module that is not registered");
+
+        // After a reset, importing the module again picks up its new exports
+        runtime.reset().expect("Could not reset the runtime");
+        runtime
+            .register_typed_function("writeConfig", |key: String| Ok(format!("wrote {key}")))
+            .expect("Could not register function");
+        runtime
+            .register_host_module("config", &["readConfig", "writeConfig"])
+            .expect("Could not register host module");
+
+        let module = Module::new(
+            "after_reset.js",
             "
-            import { value } from './importme.js';
-            rustyscript.register_entrypoint(() => value);
+            import { readConfig, writeConfig } from 'host:config';
+            export const value = readConfig('port') + ' ' + writeConfig('port');
         ",
         );
-        let module = runtime
-            .load_modules(&module2, vec![&module1])
-            .expect("Could not load modules");
-        let value: usize = runtime
-            .call_entrypoint(&module, json_args!())
-            .expect("Could not call exported fn");
-        assert_eq!(2, value);
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        let value: String = runtime
+            .get_value(Some(&handle), "value")
+            .expect("Could not get value");
+        assert_eq!("port=1 wrote port", value);
 
+        // Host modules keep working once every extension global is removed,
+        // while scripts cannot reach the helper that builds them
         let mut runtime = Runtime::new(RuntimeOptions {
-            timeout: Duration::from_millis(50),
+            lockdown: Some(crate::LockdownOptions {
+                allowed_globals: Some(vec![]),
+                ..Default::default()
+            }),
             ..Default::default()
         })
         .expect("Could not create the runtime");
+        runtime
+            .register_typed_function("readConfig", |key: String| Ok(format!("{key}=1")))
+            .expect("Could not register function");
+        runtime
+            .register_host_module("config", &["readConfig"])
+            .expect("Could not register host module");
+
         let module = Module::new(
-            "test.js",
+            "locked.js",
             "
-            await new Promise(r => setTimeout(r, 5000));
+            import { readConfig } from 'host:config';
+            export const value = readConfig('port');
+            export const symbol = Reflect.ownKeys(globalThis).includes(Symbol.for('rustyscript.host'));
+            export const helper = await import('ext:rustyscript/rustyscript.js').then(() => true, () => false);
         ",
         );
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        let value: String = runtime
+            .get_value(Some(&handle), "value")
+            .expect("Could not get value");
+        assert_eq!("port=1", value);
+        let symbol: bool = runtime
+            .get_value(Some(&handle), "symbol")
+            .expect("Could not get value");
+        assert!(!symbol);
+        let helper: bool = runtime
+            .get_value(Some(&handle), "helper")
+            .expect("Could not get value");
+        assert!(!helper);
+    }
+
+    #[test]
+    fn test_audit_sink() {
+        let events = Rc::new(std::cell::RefCell::new(Vec::new()));
+        let events_ = events.clone();
+        let mut runtime = Runtime::new(RuntimeOptions {
+            audit_sink: Some(Box::new(move |event: crate::AuditEvent| {
+                events_.borrow_mut().push(event);
+            })),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
         runtime
-            .load_modules(&module, vec![])
-            .expect_err("Did not interupt after timeout");
+            .register_function("lookup", |_| Ok(serde_json::Value::from(1)))
+            .expect("Could not register function");
+
+        let module = Module::new(
+            "plugin.js",
+            "export const value = rustyscript.functions.lookup('key', 2);",
+        );
+        runtime.load_module(&module).expect("Could not load module");
+
+        let events = events.borrow();
+        let load = events
+            .iter()
+            .find_map(|event| match &event.kind {
+                crate::AuditEventKind::ModuleLoad { specifier } => Some((specifier, &event.module)),
+                _ => None,
+            })
+            .expect("Module load was not recorded");
+        assert!(load.0.ends_with("plugin.js"));
+        assert!(load.1.is_none());
+
+        let call = events
+            .iter()
+            .find_map(|event| match &event.kind {
+                crate::AuditEventKind::FunctionCall {
+                    function,
+                    args,
+                    allowed,
+                } => Some((function, args, allowed, &event.module)),
+                _ => None,
+            })
+            .expect("Function call was not recorded");
+        assert_eq!("lookup", call.0);
+        assert_eq!(
+            &vec![serde_json::json!("key"), serde_json::json!(2)],
+            call.1
+        );
+        assert!(call.2);
+        assert!(call.3.as_deref().is_some_and(|m| m.ends_with("plugin.js")));
     }
 
+    #[cfg(any(feature = "web", feature = "webstorage"))]
     #[test]
-    fn test_call_entrypoint() {
-        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+    fn test_audit_sink_web() {
+        let events = Rc::new(std::cell::RefCell::new(Vec::new()));
+        let events_ = events.clone();
+        let mut runtime = Runtime::new(RuntimeOptions {
+            audit_sink: Some(Box::new(move |event: crate::AuditEvent| {
+                events_.borrow_mut().push(event);
+            })),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
         let module = Module::new(
-            "test.js",
+            "web_plugin.js",
             "
-            rustyscript.register_entrypoint(() => 2);
+            if (typeof fetch === 'function') {
+                fetch('http://127.0.0.1:1/').catch(() => {});
+            }
+            if (typeof sessionStorage === 'object') {
+                sessionStorage.setItem('key', 'value');
+                'key' in sessionStorage;
+                Object.keys(sessionStorage);
+            }
         ",
         );
-        let module = runtime
-            .load_modules(&module, vec![])
-            .expect("Could not load module");
+        runtime.load_module(&module).expect("Could not load module");
+
+        // Permission checks and storage access are attributed to the module that caused them
+        let events = events.borrow();
+        let from_module = |event: &&crate::AuditEvent| {
+            event
+                .module
+                .as_deref()
+                .is_some_and(|m| m.ends_with("web_plugin.js"))
+        };
+
+        #[cfg(feature = "web")]
+        assert!(events
+            .iter()
+            .filter(from_module)
+            .any(|event| matches!(event.kind, crate::AuditEventKind::NetPermission { .. })));
+
+        #[cfg(feature = "webstorage")]
+        for expected in ["setItem", "has", "keys"] {
+            assert!(
+                events.iter().filter(from_module).any(|event| matches!(
+                    &event.kind,
+                    crate::AuditEventKind::StorageAccess { operation, .. } if operation == expected
+                )),
+                "{expected} was not recorded"
+            );
+        }
+    }
+
+    #[test]
+    fn test_lockdown() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            lockdown: Some(crate::LockdownOptions {
+                hidden_globals: vec!["Deno.core".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        runtime
+            .register_function("add", |args| {
+                let a = args[0].as_i64().unwrap_or_default();
+                let b = args[1].as_i64().unwrap_or_default();
+                Ok(serde_json::Value::from(a + b))
+            })
+            .expect("Could not register function");
+
+        let frozen: bool = runtime
+            .eval("[globalThis, Array.prototype, JSON, rustyscript, Object.prototype].every(Object.isFrozen)")
+            .expect("Could not eval");
+        assert!(frozen);
+
+        let tampered: bool = runtime
+            .eval("Array.prototype.map = null; JSON.parse = null; typeof Array.prototype.map !== 'function' || JSON.parse === null")
+            .expect("Could not eval");
+        assert!(!tampered);
+
+        let hidden: bool = runtime
+            .eval("typeof Deno.core === 'undefined'")
+            .expect("Could not eval");
+        assert!(hidden);
+
+        // Objects added by extensions are frozen too
+        let frozen: bool = runtime
+            .eval("Object.isFrozen(Deno)")
+            .expect("Could not eval");
+        assert!(frozen);
+
+        // Instances can still override inherited properties
+        let name: String = runtime
+            .eval("class MyError extends Error { constructor() { super('x'); this.name = 'MyError'; } }; new MyError().name")
+            .expect("Could not eval");
+        assert_eq!("MyError", name);
+
+        // Extensions keep working
+        let sum: i64 = runtime
+            .eval("rustyscript.functions.add(1, 2)")
+            .expect("Could not eval");
+        assert_eq!(3, sum);
+
+        // Only allowed globals are kept, along with the built-ins
+        let mut runtime = Runtime::new(RuntimeOptions {
+            lockdown: Some(crate::LockdownOptions {
+                allowed_globals: Some(vec!["rustyscript".to_string()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let globals: (bool, bool, bool) = runtime
+            .eval("[typeof Deno === 'undefined', typeof rustyscript === 'object', typeof Array === 'function']")
+            .expect("Could not eval");
+        assert_eq!((true, true, true), globals);
+
+        // Globals can be left open to patching
+        let mut runtime = Runtime::new(RuntimeOptions {
+            lockdown: Some(crate::LockdownOptions {
+                unfrozen_globals: vec!["Deno".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let patched: (bool, bool) = runtime
+            .eval("Deno.patched = true; rustyscript.patched = true; [Deno.patched === true, 'patched' in rustyscript]")
+            .expect("Could not eval");
+        assert_eq!((true, false), patched);
+    }
+
+    #[cfg(feature = "web")]
+    #[test]
+    fn test_lockdown_web() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            lockdown: Some(Default::default()),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        // The web extension's globals keep working once the intrinsics are frozen
+        let path: String = runtime
+            .eval("new URL('https://example.com/a/b?c=d').pathname")
+            .expect("Could not eval");
+        assert_eq!("/a/b", path);
+
+        let text: String = runtime
+            .eval("new TextDecoder().decode(new TextEncoder().encode('hello'))")
+            .expect("Could not eval");
+        assert_eq!("hello", text);
+
         let value: usize = runtime
-            .call_entrypoint(&module, json_args!())
-            .expect("Could not call registered fn");
+            .eval("structuredClone({ value: 2 }).value")
+            .expect("Could not eval");
         assert_eq!(2, value);
 
+        // Neither the extension globals nor the intrinsics can be patched
+        let patched: Vec<bool> = runtime
+            .eval(
+                "
+                Deno.patched = true;
+                Array.prototype.patched = true;
+                URL.prototype.patched = true;
+                TextEncoder.prototype.encode = null;
+                console.log = null;
+                [
+                    'patched' in Deno,
+                    'patched' in Array.prototype,
+                    'patched' in URL.prototype,
+                    TextEncoder.prototype.encode === null,
+                    console.log === null,
+                ]
+            ",
+            )
+            .expect("Could not eval");
+        assert_eq!(vec![false; 5], patched);
+
+        let frozen: bool = runtime
+            .eval("[Deno, console, URL, URL.prototype, TextEncoder.prototype, TextDecoder.prototype].every(Object.isFrozen)")
+            .expect("Could not eval");
+        assert!(frozen);
+    }
+
+    #[test]
+    fn test_disallow_code_generation() {
         let mut runtime = Runtime::new(RuntimeOptions {
-            default_entrypoint: Some("load".to_string()),
+            disallow_code_generation: true,
+            code_generation_filter: Some(Box::new(|source| source == "1 + 1")),
             ..Default::default()
         })
         .expect("Could not create the runtime");
-        let module = Module::new(
-            "test.js",
-            "
-            export const load = () => 2;
-        ",
-        );
-        let module = runtime
-            .load_modules(&module, vec![])
-            .expect("Could not load module");
+
+        for expr in [
+            "eval('2 + 2')",
+            "new Function('return 1')()",
+            "Function('return 1')()",
+        ] {
+            let error: String = runtime
+                .eval(&format!(
+                    "(() => {{ try {{ {expr}; return ''; }} catch (e) {{ return e.name; }} }})()"
+                ))
+                .expect("Could not eval");
+            assert_eq!("EvalError", error, "{expr} was not blocked");
+        }
+
+        // Strings allowed by the filter can still be compiled
+        let value: usize = runtime.eval("eval('1 + 1')").expect("Could not eval");
+        assert_eq!(2, value);
+
+        // Ordinary scripts are not affected
+        let value: usize = runtime.eval("2 + 2").expect("Could not eval");
+        assert_eq!(4, value);
+
+        // Realms follow the same policy
+        let realm = runtime.create_realm().expect("Could not create realm");
+        let error: String = runtime
+            .eval_in_realm(
+                &realm,
+                "(() => { try { eval('2 + 2'); return ''; } catch (e) { return e.name; } })()",
+            )
+            .expect("Could not eval");
+        assert_eq!("EvalError", error);
         let value: usize = runtime
-            .call_entrypoint(&module, json_args!())
-            .expect("Could not call exported fn");
+            .eval_in_realm(&realm, "eval('1 + 1')")
+            .expect("Could not eval");
         assert_eq!(2, value);
 
-        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
-        let module = Module::new(
-            "test.js",
-            "
-            export const load = () => 2;
-        ",
-        );
-        let module = runtime
-            .load_modules(&module, vec![])
-            .expect("Could not load module");
-        runtime
-            .call_entrypoint::<Undefined>(&module, json_args!())
-            .expect_err("Did not detect no entrypoint");
+        #[cfg(all(not(feature = "web"), feature = "web_stub"))]
+        {
+            let error: String = runtime
+                .eval("(() => { try { setTimeout('2 + 2'); return ''; } catch (e) { return e.name; } })()")
+                .expect("Could not eval");
+            assert_eq!("EvalError", error);
+        }
     }
 
     #[test]
-    fn test_execute_module() {
+    fn test_stack_overflow() {
         let module = Module::new(
             "test.js",
             "
-            rustyscript.register_entrypoint(() => 2);
+            export function recurse(n) { return recurse(n + 1) + 1; }
         ",
         );
-        let value: usize =
-            Runtime::execute_module(&module, vec![], Default::default(), json_args!())
-                .expect("Could not exec module");
+
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        let handle = runtime.load_module(&module).expect("Could not load module");
+
+        let e = runtime
+            .call_function::<Undefined>(Some(&handle), "recurse", json_args!(0))
+            .unwrap_err();
+        match e {
+            Error::StackOverflow(frames) => {
+                assert!(!frames.is_empty());
+                assert_eq!(Some("recurse"), frames[0].function_name.as_deref());
+            }
+            _ => panic!("Expected a stack overflow, got {e:?}"),
+        }
+
+        let e = runtime
+            .eval::<Undefined>("(function f() { f(); })()")
+            .unwrap_err();
+        assert!(matches!(e, Error::StackOverflow(_)));
+
+        // The runtime remains usable
+        let value: usize = runtime.eval("1 + 1").expect("Could not eval");
         assert_eq!(2, value);
+    }
 
-        let module = Module::new(
-            "test.js",
-            "
-            function load() { return 2; }
-        ",
+    #[test]
+    fn test_max_stack_size() {
+        let depth = "globalThis.depth = 0; (function f() { depth++; f(); })()";
+        let measure = |runtime: &mut Runtime| {
+            let e = runtime.eval::<Undefined>(depth).unwrap_err();
+            assert!(matches!(e, Error::StackOverflow(_)));
+            runtime.eval::<usize>("depth").expect("Could not eval")
+        };
+
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        let default_depth = measure(&mut runtime);
+
+        // The limit only applies to the runtime it was set on
+        let mut limited = Runtime::new(RuntimeOptions {
+            max_stack_size: Some(64 * 1024),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let limited_depth = measure(&mut limited);
+        assert!(limited_depth < default_depth);
+        assert!(measure(&mut runtime) > limited_depth);
+    }
+
+    #[test]
+    fn test_stack_overflow_through_rust() {
+        use crate::{js_value::Function, FunctionContext};
+
+        let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
+        runtime
+            .register_typed_function(
+                "call",
+                |context: &mut FunctionContext, callback: Function| {
+                    context.call::<serde_json::Value, _>(&callback, &())?;
+                    Ok(())
+                },
+            )
+            .expect("Could not register function");
+
+        // Each level re-enters rust, through the registered function
+        let e = runtime
+            .eval::<Undefined>("(function f() { rustyscript.functions.call(f); })()")
+            .unwrap_err();
+        assert!(matches!(e, Error::StackOverflow(_)), "{e:?}");
+
+        // Scripts see it as a RangeError
+        let name: String = runtime
+            .eval(
+                "
+                (() => {
+                    const f = () => rustyscript.functions.call(f);
+                    try { f(); } catch (e) { return e.name; }
+                })()
+            ",
+            )
+            .expect("Could not eval");
+        assert_eq!("RangeError", name);
+
+        // The depth is released once the calls return
+        let value: usize = runtime
+            .eval("rustyscript.functions.call(() => 1); 2")
+            .expect("Could not eval");
+        assert_eq!(2, value);
+
+        // The limit can be set per runtime, and the error lists the JS frames that exceeded it
+        let mut runtime = Runtime::new(RuntimeOptions {
+            max_call_depth: 4,
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        runtime
+            .register_typed_function(
+                "call",
+                |context: &mut FunctionContext, callback: Function| {
+                    context.call::<serde_json::Value, _>(&callback, &())?;
+                    Ok(())
+                },
+            )
+            .expect("Could not register function");
+
+        let e = runtime
+            .eval::<Undefined>(
+                "globalThis.depth = 0; (function f() { depth++; rustyscript.functions.call(f); })()",
+            )
+            .unwrap_err();
+        match e {
+            Error::StackOverflow(frames) => {
+                assert!(frames
+                    .iter()
+                    .any(|frame| frame.function_name.as_deref() == Some("f")));
+            }
+            _ => panic!("Expected a stack overflow, got {e:?}"),
+        }
+        let depth: usize = runtime.eval("depth").expect("Could not eval");
+        assert_eq!(5, depth);
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let run = || {
+            let clock = crate::VirtualClock::new(Duration::from_secs(1_000));
+            let mut runtime = Runtime::new(RuntimeOptions {
+                deterministic: Some(crate::DeterministicOptions { seed: 7 }),
+                clock: Some(clock.clone()),
+                ..Default::default()
+            })
+            .expect("Could not create the runtime");
+
+            let mut output: Vec<f64> = runtime
+                .eval("[Math.random(), Math.random(), Date.now(), new Date().getTime()]")
+                .expect("Could not eval");
+
+            #[cfg(feature = "crypto")]
+            output.extend(
+                runtime
+                    .eval::<Vec<f64>>("Array.from(crypto.getRandomValues(new Uint8Array(8)))")
+                    .expect("Could not eval"),
+            );
+
+            clock.advance(Duration::from_millis(1500));
+            output.push(runtime.eval("Date.now()").expect("Could not eval"));
+            output
+        };
+
+        let first = run();
+        assert_eq!(first, run());
+        assert_eq!(1_000_000.0, first[2]);
+        assert_eq!(1_000_000.0, first[3]);
+        assert_eq!(1_001_500.0, *first.last().unwrap());
+        assert!((0.0..1.0).contains(&first[0]));
+        assert_ne!(first[0], first[1]);
+    }
+
+    #[cfg(all(not(feature = "web"), feature = "web_stub"))]
+    #[test]
+    fn test_deterministic_timers() {
+        // Deterministic runtimes use a virtual clock even if none is given, so timers wait for it
+        let mut runtime = Runtime::new(RuntimeOptions {
+            deterministic: Some(crate::DeterministicOptions { seed: 7 }),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        runtime
+            .eval::<Undefined>(
+                "globalThis.fired = false; setTimeout(() => { fired = true; }, 10); undefined",
+            )
+            .expect("Could not start timer");
+        runtime
+            .run_event_loop_for(Duration::from_millis(50))
+            .expect("Could not run event loop");
+        let fired: bool = runtime.eval("fired").expect("Could not eval");
+        assert!(!fired);
+
+        runtime
+            .advance_time(Duration::from_millis(10))
+            .expect("Could not advance time");
+        let fired: bool = runtime.eval("fired").expect("Could not eval");
+        assert!(fired);
+    }
+
+    #[cfg(all(not(feature = "web"), feature = "web_stub"))]
+    #[test]
+    fn test_advance_time() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            clock: Some(crate::VirtualClock::default()),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        runtime
+            .eval::<Undefined>(
+                "
+                globalThis.fired = [];
+                setTimeout(() => fired.push(['b', Date.now()]), 200);
+                setTimeout(() => fired.push(['a', Date.now()]), 100);
+                const id = setInterval(() => {
+                    fired.push(['i', Date.now()]);
+                    if (fired.filter(([n]) => n === 'i').length === 3) clearInterval(id);
+                }, 75);
+                setTimeout(() => fired.push(['never', 0]), 100000);
+                undefined
+            ",
+            )
+            .expect("Could not schedule timers");
+
+        // Nothing fires until the clock moves
+        let fired: Vec<(String, f64)> = runtime.eval("fired").expect("Could not get value");
+        assert!(fired.is_empty());
+
+        runtime
+            .advance_time(Duration::from_millis(500))
+            .expect("Could not advance time");
+        let fired: Vec<(String, f64)> = runtime.eval("fired").expect("Could not get value");
+        assert_eq!(
+            vec![
+                ("i".to_string(), 75.0),
+                ("a".to_string(), 100.0),
+                ("i".to_string(), 150.0),
+                ("b".to_string(), 200.0),
+                ("i".to_string(), 225.0),
+            ],
+            fired
         );
-        Runtime::execute_module::<Undefined>(&module, vec![], Default::default(), json_args!())
-            .expect_err("Could not detect no entrypoint");
+
+        let now: f64 = runtime.eval("Date.now()").expect("Could not get value");
+        assert_eq!(500.0, now);
+        assert_eq!(1, runtime.metrics().pending_timers);
     }
 
     #[test]
-    fn call_function() {
+    fn test_interrupt_handle() {
         let module = Module::new(
             "test.js",
             "
-            globalThis.fna = (i) => i;
-            export function fnb() { return 'test'; }
-            export const fnc = 2;
-            export const fne = () => {};
+            export function spin() { while(true) {} }
+            export function wait() { return new Promise(r => setTimeout(r, 60000)); }
         ",
         );
 
         let mut runtime = Runtime::new(Default::default()).expect("Could not create the runtime");
-        let module = runtime
-            .load_modules(&module, vec![])
-            .expect("Could not load module");
+        let handle = runtime.load_module(&module).expect("Could not load module");
 
-        let result: usize = runtime
-            .call_function(Some(&module), "fna", json_args!(2))
-            .expect("Could not call global");
-        assert_eq!(2, result);
+        // The same interrupt handle must work across several calls
+        let interrupt = runtime.interrupt_handle();
+        for _ in 0..3 {
+            let interrupt = interrupt.clone();
+            let thread = std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(250));
+                interrupt.interrupt();
+            });
 
-        let result: String = runtime
-            .call_function(Some(&module), "fnb", json_args!())
-            .expect("Could not call export");
-        assert_eq!("test", result);
+            let e = runtime
+                .call_function::<Undefined>(Some(&handle), "spin", json_args!())
+                .expect_err("Synchronous call was not interrupted");
+            assert!(matches!(e, Error::Interrupted));
+            thread.join().unwrap();
+        }
 
-        runtime
-            .call_function::<Undefined>(Some(&module), "fnc", json_args!())
-            .expect_err("Did not detect non-function");
-        runtime
-            .call_function::<Undefined>(Some(&module), "fnd", json_args!())
-            .expect_err("Did not detect undefined");
-        runtime
-            .call_function::<Undefined>(Some(&module), "fne", json_args!())
-            .expect("Did not allow undefined return");
+        // Calls waiting on the event loop can also be interrupted
+        #[cfg(any(feature = "web", feature = "web_stub"))]
+        {
+            let interrupt = interrupt.clone();
+            let thread = std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(250));
+                interrupt.interrupt();
+            });
+            let e = runtime
+                .call_function::<Undefined>(Some(&handle), "wait", json_args!())
+                .expect_err("Pending call was not interrupted");
+            assert!(matches!(e, Error::Interrupted));
+            thread.join().unwrap();
+        }
+
+        // Interrupting an idle runtime does not affect the next call
+        runtime.interrupt_handle().interrupt();
+        let value: usize = runtime.eval("2 + 2").expect("Runtime was left terminated");
+        assert_eq!(4, value);
     }
 }
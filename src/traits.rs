@@ -1,7 +1,8 @@
 use crate::Error;
 use deno_core::resolve_path;
 use deno_core::v8::{self, HandleScope};
-use deno_core::ModuleSpecifier;
+use deno_core::{serde_json, serde_v8, ModuleSpecifier};
+use serde::Serialize;
 use std::env::current_dir;
 
 pub trait ToModuleSpecifier {
@@ -30,6 +31,63 @@ impl ToV8String for str {
     }
 }
 
+/// Arguments for a javascript function, converted straight to v8 with serde_v8
+/// Implemented for tuples of up to 8 `Serialize` values, and for slices of `serde_json::Value`
+///
+/// See [crate::Runtime::call_function_typed]
+pub trait IntoArgs {
+    /// Convert the arguments to v8 values
+    fn to_v8_args<'a>(
+        &self,
+        scope: &mut HandleScope<'a>,
+    ) -> Result<Vec<v8::Local<'a, v8::Value>>, Error>;
+}
+
+impl IntoArgs for [serde_json::Value] {
+    fn to_v8_args<'a>(
+        &self,
+        scope: &mut HandleScope<'a>,
+    ) -> Result<Vec<v8::Local<'a, v8::Value>>, Error> {
+        self.iter()
+            .map(|arg| Ok(serde_v8::to_v8(scope, arg)?))
+            .collect()
+    }
+}
+
+impl<const N: usize> IntoArgs for [serde_json::Value; N] {
+    fn to_v8_args<'a>(
+        &self,
+        scope: &mut HandleScope<'a>,
+    ) -> Result<Vec<v8::Local<'a, v8::Value>>, Error> {
+        self.as_slice().to_v8_args(scope)
+    }
+}
+
+macro_rules! impl_into_args {
+    ($($arg:ident),*) => {
+        impl<$($arg: Serialize),*> IntoArgs for ($($arg,)*) {
+            #[allow(non_snake_case, unused_variables)]
+            fn to_v8_args<'a>(
+                &self,
+                scope: &mut HandleScope<'a>,
+            ) -> Result<Vec<v8::Local<'a, v8::Value>>, Error> {
+                let ($($arg,)*) = self;
+                Ok(vec![$(serde_v8::to_v8(scope, $arg)?),*])
+            }
+        }
+    };
+}
+
+impl_into_args!();
+impl_into_args!(A1);
+impl_into_args!(A1, A2);
+impl_into_args!(A1, A2, A3);
+impl_into_args!(A1, A2, A3, A4);
+impl_into_args!(A1, A2, A3, A4, A5);
+impl_into_args!(A1, A2, A3, A4, A5, A6);
+impl_into_args!(A1, A2, A3, A4, A5, A6, A7);
+impl_into_args!(A1, A2, A3, A4, A5, A6, A7, A8);
+
 pub trait ToDefinedValue<T> {
     fn if_defined(&self) -> Option<T>;
 }
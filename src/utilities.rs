@@ -47,6 +47,7 @@ pub fn validate(javascript: &str) -> Result<bool, Error> {
         Ok(_) => Ok(true),
         Err(Error::Runtime(_)) => Ok(false),
         Err(Error::JsError(_)) => Ok(false),
+        Err(Error::StackOverflow(_)) => Ok(false),
         Err(e) => Err(e),
     }
 }
@@ -89,7 +90,10 @@ pub fn resolve_path(path: &str) -> Result<String, Error> {
 ///
 /// This is done automatically the first time Runtime::new is called,
 /// but for multi-threaded applications, it may be necessary to call this function manually
+///
+/// Any [crate::V8Flags] must be applied before this is called
 pub fn init_platform(thread_pool_size: u32, idle_task_support: bool) {
+    crate::v8_flags::platform_initialized();
     let platform = deno_core::v8::Platform::new(thread_pool_size, idle_task_support);
     deno_core::JsRuntime::init_platform(Some(platform.into()))
 }
@@ -0,0 +1,236 @@
+//! Process-wide configuration of the V8 engine
+use crate::Error;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+
+/// The flags applied to this process, if any
+static APPLIED_FLAGS: Mutex<Option<V8Flags>> = Mutex::new(None);
+
+/// Set once the V8 platform has been initialized, after which flags can no longer be applied
+static PLATFORM_INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Typed flags for the V8 engine
+///
+/// V8 flags are global to the process, and must be applied before the V8 platform is initialized;
+/// either with [V8Flags::apply] before calling [crate::init_platform], or by setting
+/// [crate::RuntimeOptions::v8_flags] on the first runtime created.
+///
+/// Applying the same flags again is allowed, but applying different flags, or applying flags
+/// once the platform is running, will return [Error::InvalidV8Flags]
+///
+/// ```rust
+/// use rustyscript::{ Runtime, RuntimeOptions, V8Flags, Error };
+///
+/// # fn main() -> Result<(), Error> {
+/// let flags = V8Flags {
+///     expose_gc: true,
+///     ..Default::default()
+/// };
+///
+/// let mut runtime = Runtime::new(RuntimeOptions {
+///     v8_flags: Some(flags),
+///     ..Default::default()
+/// })?;
+///
+/// let has_gc: bool = runtime.eval("typeof gc === 'function'")?;
+/// assert!(has_gc);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct V8Flags {
+    /// Maximum size of the old generation of the heap, in megabytes (`--max-old-space-size`)
+    pub max_old_space_size: Option<usize>,
+
+    /// Size of the stack region used by V8, in kilobytes (`--stack-size`)
+    /// Must be smaller than the native stack of the threads running the runtimes
+    ///
+    /// Like every flag, this applies to all runtimes in the process.
+    /// Use [crate::RuntimeOptions::max_stack_size] to limit the stack of a single runtime
+    pub stack_size: Option<usize>,
+
+    /// Disables runtime code generation, including the JIT and WebAssembly (`--jitless`)
+    pub jitless: bool,
+
+    /// Exposes a global `gc()` function (`--expose-gc`)
+    pub expose_gc: bool,
+
+    /// Additional raw flags, such as `--no-opt`
+    pub extra: Vec<String>,
+}
+
+impl V8Flags {
+    /// Returns the flags as command-line arguments for V8
+    ///
+    /// # Errors
+    /// Returns [Error::InvalidV8Flags] if a value is out of range, or an extra flag is malformed
+    pub fn to_args(&self) -> Result<Vec<String>, Error> {
+        let mut args = Vec::new();
+
+        if let Some(size) = self.max_old_space_size {
+            if size == 0 {
+                return Err(Error::InvalidV8Flags(
+                    "max_old_space_size must be greater than 0".to_string(),
+                ));
+            }
+            args.push(format!("--max-old-space-size={size}"));
+        }
+
+        if let Some(size) = self.stack_size {
+            if size == 0 {
+                return Err(Error::InvalidV8Flags(
+                    "stack_size must be greater than 0".to_string(),
+                ));
+            }
+            args.push(format!("--stack-size={size}"));
+        }
+
+        if self.jitless {
+            args.push("--jitless".to_string());
+        }
+
+        if self.expose_gc {
+            args.push("--expose-gc".to_string());
+        }
+
+        for flag in &self.extra {
+            if !flag.starts_with("--") || flag.contains(char::is_whitespace) {
+                return Err(Error::InvalidV8Flags(format!(
+                    "`{flag}` is not a single V8 flag of the form --name[=value]"
+                )));
+            }
+            args.push(flag.clone());
+        }
+
+        Ok(args)
+    }
+
+    /// Apply the flags to the V8 engine
+    /// Must be called before the V8 platform is initialized, and only has an effect once per process
+    ///
+    /// # Errors
+    /// Returns [Error::InvalidV8Flags] if the flags are invalid, not recognized by V8,
+    /// different from flags applied earlier, or if the platform is already initialized
+    pub fn apply(&self) -> Result<(), Error> {
+        let mut applied = APPLIED_FLAGS
+            .lock()
+            .map_err(|e| Error::Runtime(e.to_string()))?;
+
+        if !self.can_apply(
+            applied.as_ref(),
+            PLATFORM_INITIALIZED.load(Ordering::SeqCst),
+        )? {
+            return Ok(());
+        }
+
+        let args = self.to_args()?;
+
+        // V8 expects the program name as the first argument, and returns it along with any unrecognized flags
+        let unrecognized =
+            deno_core::v8_set_flags(std::iter::once(String::new()).chain(args).collect());
+        if unrecognized.len() > 1 {
+            return Err(Error::InvalidV8Flags(format!(
+                "unrecognized flags: {}",
+                unrecognized[1..].join(" ")
+            )));
+        }
+
+        *applied = Some(self.clone());
+        Ok(())
+    }
+
+    /// Check whether the flags can be applied, given the flags already applied to the process
+    /// and whether the platform is initialized
+    ///
+    /// Returns false if the same flags were already applied, and there is nothing left to do
+    fn can_apply(&self, applied: Option<&V8Flags>, initialized: bool) -> Result<bool, Error> {
+        match applied {
+            Some(flags) if flags == self => Ok(false),
+            Some(flags) => Err(Error::InvalidV8Flags(format!(
+                "different flags were already applied to this process: {:?}",
+                flags.to_args().unwrap_or_default()
+            ))),
+            None if initialized => Err(Error::InvalidV8Flags(
+                "flags must be applied before the V8 platform is initialized".to_string(),
+            )),
+            None => Ok(true),
+        }
+    }
+}
+
+/// Returns true if `--expose-gc` was applied to this process
+pub(crate) fn expose_gc() -> bool {
+    APPLIED_FLAGS
+        .lock()
+        .map(|flags| flags.as_ref().is_some_and(|flags| flags.expose_gc))
+        .unwrap_or_default()
+}
+
+/// Records that the V8 platform has been initialized
+pub(crate) fn platform_initialized() {
+    PLATFORM_INITIALIZED.store(true, Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_args() {
+        let flags = V8Flags {
+            max_old_space_size: Some(512),
+            stack_size: Some(984),
+            jitless: true,
+            expose_gc: true,
+            extra: vec!["--no-opt".to_string()],
+        };
+        assert_eq!(
+            vec![
+                "--max-old-space-size=512",
+                "--stack-size=984",
+                "--jitless",
+                "--expose-gc",
+                "--no-opt"
+            ],
+            flags.to_args().expect("Could not build flags")
+        );
+
+        let flags = V8Flags {
+            stack_size: Some(0),
+            ..Default::default()
+        };
+        assert!(matches!(flags.to_args(), Err(Error::InvalidV8Flags(_))));
+
+        let flags = V8Flags {
+            extra: vec!["--no-opt --jitless".to_string()],
+            ..Default::default()
+        };
+        assert!(matches!(flags.to_args(), Err(Error::InvalidV8Flags(_))));
+    }
+
+    #[test]
+    fn test_can_apply() {
+        let flags = V8Flags {
+            jitless: true,
+            ..Default::default()
+        };
+        assert!(flags.can_apply(None, false).expect("Could not apply flags"));
+
+        // Too late once the platform is running
+        assert!(matches!(
+            flags.can_apply(None, true),
+            Err(Error::InvalidV8Flags(_))
+        ));
+
+        // Applying the same flags again does nothing, but different flags are rejected
+        assert!(!flags
+            .can_apply(Some(&flags), true)
+            .expect("Could not apply flags"));
+        assert!(matches!(
+            flags.can_apply(Some(&V8Flags::default()), false),
+            Err(Error::InvalidV8Flags(_))
+        ));
+    }
+}
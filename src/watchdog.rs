@@ -0,0 +1,350 @@
+//! Provides a watchdog thread that can forcibly stop JS execution on a runtime
+//!
+//! tokio timeouts can only fire when the future yields, which never happens
+//! while V8 is stuck inside synchronous code such as `while(true){}`.
+//! The watchdog instead terminates execution through the isolate's thread-safe handle
+//!
+//! It also keeps track of why execution was terminated, so that other sources of
+//! termination (such as the heap limit, or an [InterruptHandle]) can be reported as the right error
+use crate::Error;
+use deno_core::v8;
+use std::{
+    future::Future,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    task::{Poll, Waker},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// The reason execution was terminated on the isolate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminationReason {
+    /// The call ran past the runtime's timeout
+    Timeout,
+
+    /// The isolate ran out of heap space
+    HeapExhausted,
+
+    /// The call was cancelled through an [InterruptHandle]
+    Interrupted,
+}
+
+impl TerminationReason {
+    /// Returns the error reported by calls terminated for this reason
+    /// `timeout` is the timeout of the call, for [Error::Timeout]
+    pub fn to_error(self, timeout: Duration) -> Error {
+        match self {
+            Self::Timeout => Error::Timeout(format!("{timeout:?}")),
+            Self::HeapExhausted => Error::HeapExhausted,
+            Self::Interrupted => Error::Interrupted,
+        }
+    }
+}
+
+/// State shared between the runtime and the watchdog thread
+#[derive(Default)]
+struct WatchdogState {
+    /// Number of calls currently being watched
+    /// Calls can be nested, for example a blocking call wrapping its async variant
+    depth: usize,
+
+    /// Point at which execution will be terminated, if any
+    deadline: Option<Instant>,
+
+    /// Timeout of the call that set the deadline, used to report timeouts
+    timeout: Duration,
+
+    /// Set when execution has been terminated during the current call
+    terminated: Option<TerminationReason>,
+
+    /// Set once the heap has been exhausted - the isolate cannot be trusted after that
+    poisoned: bool,
+
+    /// Wakes the task waiting on the current call, if it is not running JS
+    waker: Option<Waker>,
+
+    /// Set when the runtime is dropped, to stop the thread
+    shutdown: bool,
+}
+
+struct Shared {
+    isolate: v8::IsolateHandle,
+    state: Mutex<WatchdogState>,
+    condvar: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, WatchdogState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Terminate execution on the isolate, recording the reason
+    /// The first reason recorded during a call wins
+    fn terminate(&self, reason: TerminationReason) {
+        self.terminate_locked(&mut self.lock(), reason);
+    }
+
+    /// Terminate execution on the isolate, with the state already locked
+    fn terminate_locked(&self, state: &mut WatchdogState, reason: TerminationReason) {
+        if reason == TerminationReason::HeapExhausted {
+            state.poisoned = true;
+        }
+        state.terminated.get_or_insert(reason);
+        self.isolate.terminate_execution();
+
+        // The call may be waiting on the event loop rather than running JS
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Interrupt the current call, if there is one
+    /// Does nothing while the runtime is idle, so that the next call is not affected
+    ///
+    /// The state stays locked between the check and the termination,
+    /// so that a call finishing in between cannot pass the interrupt on to the next one
+    fn interrupt(&self) {
+        let mut state = self.lock();
+        if state.depth > 0 {
+            self.terminate_locked(&mut state, TerminationReason::Interrupted);
+        }
+    }
+
+    /// Stop watching a call
+    /// Returns the reason execution was terminated during that call, if it was
+    fn disarm(&self) -> Option<TerminationReason> {
+        let mut state = self.lock();
+        state.depth = state.depth.saturating_sub(1);
+        let terminated = state.terminated;
+
+        // Only the outermost call resets the watchdog, and makes the isolate usable again
+        if state.depth == 0 {
+            state.deadline = None;
+            state.timeout = Duration::ZERO;
+            state.terminated = None;
+            state.waker = None;
+            if terminated.is_some() {
+                self.isolate.cancel_terminate_execution();
+            }
+        }
+
+        terminated
+    }
+
+    /// Main loop for the watchdog thread
+    fn run(&self) {
+        let mut state = self.lock();
+        while !state.shutdown {
+            match state.deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        state.deadline = None;
+                        state.terminated.get_or_insert(TerminationReason::Timeout);
+                        self.isolate.terminate_execution();
+                    } else {
+                        state = match self.condvar.wait_timeout(state, deadline - now) {
+                            Ok((state, _)) => state,
+                            Err(e) => e.into_inner().0,
+                        };
+                    }
+                }
+
+                None => {
+                    state = self.condvar.wait(state).unwrap_or_else(|e| e.into_inner());
+                }
+            }
+        }
+    }
+}
+
+/// Terminates execution on an isolate once a deadline has passed
+///
+/// The thread is only started the first time a finite timeout is armed,
+/// so runtimes without a timeout do not pay for it
+pub struct Watchdog {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Create a new watchdog for the given isolate
+    pub fn new(isolate: v8::IsolateHandle) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                isolate,
+                state: Mutex::new(WatchdogState::default()),
+                condvar: Condvar::new(),
+            }),
+            thread: None,
+        }
+    }
+
+    /// Returns a handle that can terminate execution from outside the watchdog
+    pub fn handle(&self) -> WatchdogHandle {
+        WatchdogHandle(self.shared.clone())
+    }
+
+    /// True if the heap was exhausted at some point, and the runtime should not be used anymore
+    pub fn is_poisoned(&self) -> bool {
+        self.shared.lock().poisoned
+    }
+
+    /// Start watching a call, which will be terminated after `timeout`
+    /// The returned guard must be used to collect the result of the call
+    ///
+    /// Fails with [Error::HeapExhausted] if the runtime has been poisoned
+    pub fn arm(&mut self, timeout: Duration) -> Result<WatchdogGuard, Error> {
+        let mut state = self.shared.lock();
+        if state.poisoned {
+            return Err(Error::HeapExhausted);
+        }
+
+        state.depth += 1;
+        if state.depth == 1 {
+            state.terminated = None;
+        }
+
+        // Nested calls can only shorten the deadline
+        if let Some(deadline) = Instant::now().checked_add(timeout) {
+            if state.deadline.map_or(true, |current| deadline < current) {
+                state.deadline = Some(deadline);
+                state.timeout = timeout;
+            }
+
+            if self.thread.is_none() {
+                let shared = self.shared.clone();
+                self.thread = Some(std::thread::spawn(move || shared.run()));
+            }
+            self.shared.condvar.notify_all();
+        }
+
+        Ok(WatchdogGuard {
+            shared: self.shared.clone(),
+            timeout,
+            finished: false,
+        })
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.shared.lock().shutdown = true;
+        self.shared.condvar.notify_all();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+/// Represents a call being watched by a [Watchdog]
+/// Dropping the guard without calling `finish` will still stop the watch
+pub struct WatchdogGuard {
+    shared: Arc<Shared>,
+    timeout: Duration,
+    finished: bool,
+}
+
+impl WatchdogGuard {
+    /// Drive a future to completion, stopping early if execution is terminated while it waits
+    /// Termination alone only stops running JS, and not pending work on the event loop
+    pub async fn watch<T, F>(&self, future: F) -> Result<T, Error>
+    where
+        F: Future<Output = Result<T, Error>>,
+    {
+        let mut future = std::pin::pin!(future);
+        std::future::poll_fn(|cx| {
+            {
+                let mut state = self.shared.lock();
+                if state.terminated.is_some() {
+                    // The actual error is filled in by `finish`
+                    return Poll::Ready(Err(Error::Interrupted));
+                }
+                state.waker = Some(cx.waker().clone());
+            }
+
+            future.as_mut().poll(cx)
+        })
+        .await
+    }
+
+    /// Stop watching the call, and return its result
+    /// If execution was terminated during the call, the result is replaced by the matching error
+    pub fn finish<T>(mut self, result: Result<T, Error>) -> Result<T, Error> {
+        self.finished = true;
+        match self.shared.disarm() {
+            Some(reason) => Err(reason.to_error(self.timeout)),
+            None => result,
+        }
+    }
+}
+
+impl Drop for WatchdogGuard {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.shared.disarm();
+        }
+    }
+}
+
+/// A clonable handle used to terminate execution on a watched isolate
+/// Calls in progress will return the error matching the given reason
+#[derive(Clone)]
+pub struct WatchdogHandle(Arc<Shared>);
+
+impl WatchdogHandle {
+    /// Terminate execution on the isolate
+    pub fn terminate(&self, reason: TerminationReason) {
+        self.0.terminate(reason);
+    }
+
+    /// Terminate the current call on the isolate, if there is one
+    pub fn interrupt(&self) {
+        self.0.interrupt();
+    }
+
+    /// Returns the error matching the reason execution was terminated during the current call, if it was
+    pub fn termination_error(&self) -> Option<Error> {
+        let state = self.0.lock();
+        state
+            .terminated
+            .map(|reason| reason.to_error(state.timeout))
+    }
+}
+
+/// A thread-safe handle used to cancel whatever a [crate::Runtime] is currently executing
+///
+/// The interrupted call returns [Error::Interrupted], and the runtime remains usable afterwards.
+/// Interrupting an idle runtime does nothing.
+///
+/// ```rust
+/// use rustyscript::{ Runtime, Error, Undefined };
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// let mut runtime = Runtime::new(Default::default())?;
+/// let handle = runtime.interrupt_handle();
+///
+/// std::thread::spawn(move || {
+///     std::thread::sleep(Duration::from_millis(100));
+///     handle.interrupt();
+/// });
+///
+/// let result = runtime.eval::<Undefined>("while(true) {}");
+/// assert!(matches!(result, Err(Error::Interrupted)));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct InterruptHandle(WatchdogHandle);
+
+impl InterruptHandle {
+    pub(crate) fn new(handle: WatchdogHandle) -> Self {
+        Self(handle)
+    }
+
+    /// Cancel the call currently running on the runtime, if any
+    pub fn interrupt(&self) {
+        self.0.interrupt();
+    }
+}
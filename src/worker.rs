@@ -95,6 +95,7 @@ where
     where
         T: serde::de::DeserializeOwned + Send + 'static,
     {
+        crate::v8_flags::platform_initialized();
         deno_core::JsRuntime::init_platform(None);
         std::thread::spawn(move || {
             let mut runtime = crate::Runtime::new(Default::default())?;
@@ -392,6 +393,21 @@ impl InnerWorker for DefaultWorker {
                     Err(e) => Self::Response::Error(e),
                 }
             }
+
+            DefaultWorkerQuery::CollectGarbage => {
+                runtime.collect_garbage();
+                Self::Response::Ok(())
+            }
+
+            DefaultWorkerQuery::NotifyLowMemory => {
+                runtime.notify_low_memory();
+                Self::Response::Ok(())
+            }
+
+            DefaultWorkerQuery::IdleNotification(idle_time) => {
+                runtime.idle_notification(idle_time);
+                Self::Response::Ok(())
+            }
         }
     }
 }
@@ -497,6 +513,37 @@ impl DefaultWorker {
         }
     }
 
+    /// Run a full garbage collection in the worker's runtime
+    /// See [crate::Runtime::collect_garbage]
+    pub fn collect_garbage(&self) -> Result<(), Error> {
+        self.send_and_expect_ok(DefaultWorkerQuery::CollectGarbage)
+    }
+
+    /// Tell the worker's runtime that the system is running low on memory
+    /// See [crate::Runtime::notify_low_memory]
+    pub fn notify_low_memory(&self) -> Result<(), Error> {
+        self.send_and_expect_ok(DefaultWorkerQuery::NotifyLowMemory)
+    }
+
+    /// Hint that the worker will be idle for `idle_time`, so V8 can run idle tasks such as garbage collection
+    /// See [crate::Runtime::idle_notification]
+    ///
+    /// Does nothing unless the platform was initialized with idle task support - see [crate::init_platform].
+    /// The default platform, initialized when the first runtime is created, does not have it
+    pub fn idle_notification(&self, idle_time: std::time::Duration) -> Result<(), Error> {
+        self.send_and_expect_ok(DefaultWorkerQuery::IdleNotification(idle_time))
+    }
+
+    fn send_and_expect_ok(&self, query: DefaultWorkerQuery) -> Result<(), Error> {
+        match self.0.send_and_await(query)? {
+            DefaultWorkerResponse::Ok(()) => Ok(()),
+            DefaultWorkerResponse::Error(e) => Err(e),
+            _ => Err(Error::Runtime(
+                "Unexpected response from the worker".to_string(),
+            )),
+        }
+    }
+
     /// Get a value from a module
     /// The module id must be the id of a module loaded with `load_main_module` or `load_module`
     pub fn get_value<T>(
@@ -565,6 +612,16 @@ pub enum DefaultWorkerQuery {
 
     /// Gets a value from a module
     GetValue(Option<deno_core::ModuleId>, String),
+
+    /// Runs a full garbage collection
+    CollectGarbage,
+
+    /// Tells the runtime that the system is running low on memory
+    NotifyLowMemory,
+
+    /// Lets the runtime run idle tasks for the given time
+    /// Does nothing without idle task support on the platform
+    IdleNotification(std::time::Duration),
 }
 
 /// Response types for the default worker
@@ -581,3 +638,39 @@ pub enum DefaultWorkerResponse {
     /// An error response
     Error(Error),
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Module;
+
+    #[test]
+    fn test_default_worker_memory() {
+        let worker = DefaultWorker::new(Default::default()).expect("Could not create worker");
+        let module = Module::new(
+            "test.js",
+            "
+            globalThis.garbage = Array.from({ length: 10000 }, (_, i) => ({ i }));
+            export function count() { return globalThis.garbage?.length ?? 0; }
+        ",
+        );
+        let module_id = worker.load_module(module).expect("Could not load module");
+
+        worker
+            .eval::<usize>("globalThis.garbage = null; 0".to_string())
+            .expect("Could not eval");
+        worker.collect_garbage().expect("Could not collect garbage");
+        worker
+            .notify_low_memory()
+            .expect("Could not notify low memory");
+        worker
+            .idle_notification(std::time::Duration::from_millis(10))
+            .expect("Could not send idle notification");
+
+        // The worker keeps running afterwards
+        let count: usize = worker
+            .call_function(Some(module_id), "count".to_string(), Vec::new())
+            .expect("Could not call function");
+        assert_eq!(0, count);
+    }
+}